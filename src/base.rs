@@ -1,12 +1,24 @@
 //! Common types and functions.
 
 use crate::kind::Rs2DistortionModel;
-use num_traits::FromPrimitive;
+use num_traits::{FromPrimitive, ToPrimitive};
 use realsense_sys as sys;
 use std::{ffi::CString, time::Duration};
+use thiserror::Error;
 
 pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(sys::RS2_DEFAULT_TIMEOUT as u64);
 
+/// Occurs when intrinsics are scaled or decimated by an invalid factor.
+#[derive(Error, Debug)]
+pub enum IntrinsicsScaleError {
+    /// The scale factor is not a positive, finite number.
+    #[error("Scale factor must be positive and finite, got {0}.")]
+    InvalidScale(f32),
+    /// The decimation factor is zero.
+    #[error("Decimation factor must be at least 1.")]
+    InvalidDecimation,
+}
+
 // Thanks, Tenders McChiken.
 // https://stackoverflow.com/questions/38948669/whats-the-most-direct-way-to-convert-a-path-to-a-c-char
 pub(crate) fn from_path<P>(path: P) -> anyhow::Result<CString>
@@ -41,7 +53,7 @@ where
     Ok(CString::new(buf)?)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Rs2MotionDeviceIntrinsics(pub sys::rs2_motion_device_intrinsic);
 
 /// Profile the scale, bias, and variances for a given motion device
@@ -51,6 +63,17 @@ pub struct Rs2MotionDeviceIntrinsics(pub sys::rs2_motion_device_intrinsic);
 ///
 /// Use the function `stream_profile.motion_intrinsics()` to retrieve these intrinsics from a certain stream.
 impl Rs2MotionDeviceIntrinsics {
+    /// Construct motion intrinsics from a 3x4 scale and bias matrix and per-axis variances.
+    ///
+    /// See [`data`](Self::data) for the layout of the matrix.
+    pub fn new(data: [[f32; 4]; 3], noise_variances: [f32; 3], bias_variances: [f32; 3]) -> Self {
+        Rs2MotionDeviceIntrinsics(sys::rs2_motion_device_intrinsic {
+            data,
+            noise_variances,
+            bias_variances,
+        })
+    }
+
     /// A 3x4 matrix describing the scale and bias intrinsics of the motion device.
     ///
     /// This matrix is stored internally like so:
@@ -69,10 +92,86 @@ impl Rs2MotionDeviceIntrinsics {
     pub fn bias_variances(&self) -> [f32; 3usize] {
         self.0.bias_variances
     }
+
+    /// Correct a raw motion sample with the scale and bias of these intrinsics.
+    ///
+    /// The corrected sample is `S * raw - b`, where `S` is the 3x3 scale and cross axis block of
+    /// [`data`](Self::data) and `b` is its bias column. This is the same correction librealsense2
+    /// applies when motion correction is enabled on the sensor, so it should only be applied to
+    /// samples streamed with motion correction disabled.
+    pub fn correct(&self, raw: [f32; 3]) -> [f32; 3] {
+        let mut corrected = [0.0; 3];
+        for (axis, row) in self.0.data.iter().enumerate() {
+            corrected[axis] = row[0] * raw[0] + row[1] * raw[1] + row[2] * raw[2] - row[3];
+        }
+        corrected
+    }
+
+    /// Get the noise model of the motion device for a stream sampled at `rate` Hz.
+    ///
+    /// The variances reported by librealsense2 are per sample, whereas visual-inertial frameworks
+    /// (e.g. Kalibr, VINS-Mono, OpenVINS) expect continuous-time densities. See [`ImuNoiseModel`]
+    /// for the conversion.
+    pub fn noise_model(&self, rate: f32) -> ImuNoiseModel {
+        let noise_density = self
+            .0
+            .noise_variances
+            .map(|variance| (variance / rate).sqrt());
+        let random_walk = self
+            .0
+            .bias_variances
+            .map(|variance| (variance * rate).sqrt());
+
+        ImuNoiseModel {
+            noise_density,
+            random_walk,
+        }
+    }
+}
+
+impl PartialEq for Rs2MotionDeviceIntrinsics {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.data == other.0.data
+            && self.0.noise_variances == other.0.noise_variances
+            && self.0.bias_variances == other.0.bias_variances
+    }
 }
 
 unsafe impl Send for Rs2MotionDeviceIntrinsics {}
 
+/// The continuous-time noise model of a motion device, per axis.
+///
+/// Given a discrete sample rate `r`, the per-sample noise variance `σ²` and bias variance `σ_b²`
+/// convert to densities as:
+///
+/// - Noise density: `σ / sqrt(r)`, in units of `[unit] / sqrt(Hz)`.
+/// - Bias random walk: `σ_b * sqrt(r)`, in units of `[unit] * sqrt(Hz)`.
+///
+/// Here `[unit]` is m/s^2 for accelerometers and radians/s for gyroscopes. These are the
+/// `accelerometer_noise_density`, `accelerometer_random_walk`, `gyroscope_noise_density` and
+/// `gyroscope_random_walk` values of a Kalibr IMU configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImuNoiseModel {
+    /// The white noise density for the X, Y, and Z axis.
+    pub noise_density: [f32; 3],
+    /// The bias random walk for the X, Y, and Z axis.
+    pub random_walk: [f32; 3],
+}
+
+impl ImuNoiseModel {
+    /// Get the largest noise density over all axes.
+    ///
+    /// Most frameworks take a single value per sensor rather than one per axis.
+    pub fn max_noise_density(&self) -> f32 {
+        self.noise_density.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Get the largest bias random walk over all axes.
+    pub fn max_random_walk(&self) -> f32 {
+        self.random_walk.iter().copied().fold(0.0, f32::max)
+    }
+}
+
 /// Type representing the intrinsic scale, bias, and variances for a given motion device.
 ///
 /// The data in `coeffs` means different things for different models.
@@ -83,7 +182,8 @@ unsafe impl Send for Rs2MotionDeviceIntrinsics {}
 ///
 /// The Intel RealSense documentation claims that "Other models are subject to their own interpretations". This is
 /// admittedly not too helpful, but it's worth noting in case your model isn't covered here.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rs2Distortion {
     /// Distortion model of the image.
     pub model: Rs2DistortionModel,
@@ -99,10 +199,32 @@ unsafe impl Send for Rs2Distortion {}
 /// documentation for [Rs2Distortion] for specifics on the available distortion models for RealSense devices.
 ///
 /// Use the function `stream_profile.intrinsics()` to retrieve these intrinsics from a certain stream.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Rs2Intrinsics(pub sys::rs2_intrinsics);
 
 impl Rs2Intrinsics {
+    /// Construct intrinsics for a `width` x `height` image.
+    ///
+    /// The principal point and focal length are given as `[x, y]` pairs, in pixels.
+    pub fn new(
+        width: usize,
+        height: usize,
+        principal_point: [f32; 2],
+        focal_length: [f32; 2],
+        distortion: Rs2Distortion,
+    ) -> Self {
+        Rs2Intrinsics(sys::rs2_intrinsics {
+            width: width as i32,
+            height: height as i32,
+            ppx: principal_point[0],
+            ppy: principal_point[1],
+            fx: focal_length[0],
+            fy: focal_length[1],
+            model: distortion.model.to_u32().unwrap(),
+            coeffs: distortion.coeffs,
+        })
+    }
+
     /// Width of the image in pixels
     pub fn width(&self) -> usize {
         self.0.width as usize
@@ -135,6 +257,276 @@ impl Rs2Intrinsics {
             coeffs: self.0.coeffs,
         }
     }
+
+    /// Get the intrinsics of this image after scaling it by `factor`, e.g. `0.5` for an image
+    /// downsampled by a factor of 2.
+    ///
+    /// Each pixel of the scaled image covers `1 / factor` pixels of the original image in each
+    /// direction, so the scaled width and height are rounded down to whole pixels.
+    ///
+    /// This does not reproduce the output of librealsense's decimation filter; use
+    /// [`Rs2Intrinsics::decimated`] for frames that went through the filter.
+    ///
+    /// Distortion is applied to normalized image coordinates in every distortion model, so the
+    /// distortion coefficients are unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IntrinsicsScaleError::InvalidScale`] if `factor` is not positive and finite.
+    pub fn scaled(&self, factor: f32) -> Result<Self, IntrinsicsScaleError> {
+        if !(factor > 0.0 && factor.is_finite()) {
+            return Err(IntrinsicsScaleError::InvalidScale(factor));
+        }
+
+        let width = (self.width() as f32 * factor).floor() as usize;
+        let height = (self.height() as f32 * factor).floor() as usize;
+        Ok(self.rescaled(width, height, factor, factor))
+    }
+
+    /// Get the intrinsics of this image after librealsense's decimation filter reduced it by
+    /// `factor`, i.e. the filter's magnitude option.
+    ///
+    /// This reproduces the intrinsics the filter assigns to its output. The filter keeps only
+    /// whole blocks of `factor` x `factor` pixels, then pads the width and height up to a multiple
+    /// of 4 pixels. The focal lengths and principal point are divided by `factor`. A factor of 1
+    /// leaves the intrinsics unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IntrinsicsScaleError::InvalidDecimation`] if `factor` is zero.
+    pub fn decimated(&self, factor: u32) -> Result<Self, IntrinsicsScaleError> {
+        match factor {
+            0 => return Err(IntrinsicsScaleError::InvalidDecimation),
+            1 => return Ok(*self),
+            _ => {}
+        }
+
+        let padded = |size: i32| (size / factor as i32 + 3) / 4 * 4;
+        let scale = factor as f32;
+
+        let mut intrinsics = *self;
+        intrinsics.0.width = padded(self.0.width);
+        intrinsics.0.height = padded(self.0.height);
+        intrinsics.0.fx = self.0.fx / scale;
+        intrinsics.0.fy = self.0.fy / scale;
+        intrinsics.0.ppx = self.0.ppx / scale;
+        intrinsics.0.ppy = self.0.ppy / scale;
+        Ok(intrinsics)
+    }
+
+    /// Get the intrinsics of this image after resizing it to `width` x `height` pixels.
+    ///
+    /// The image may be resized by a different factor in each direction. Distortion is applied to
+    /// normalized image coordinates in every distortion model, so the distortion coefficients are
+    /// unchanged.
+    pub fn resized(&self, width: usize, height: usize) -> Self {
+        let scale_x = width as f32 / self.width() as f32;
+        let scale_y = height as f32 / self.height() as f32;
+        self.rescaled(width, height, scale_x, scale_y)
+    }
+
+    /// Get the intrinsics of the region `roi` cropped out of this image.
+    ///
+    /// The bounds of the region are inclusive, and are clamped to the image. Cropping only moves
+    /// the principal point, so the focal lengths and distortion coefficients are unchanged.
+    pub fn cropped(&self, roi: &Rs2Roi) -> Self {
+        let clamp = |value: i32, upper: usize| value.max(0).min(upper as i32);
+        let min_x = clamp(roi.min_x, self.width());
+        let min_y = clamp(roi.min_y, self.height());
+        let max_x = clamp(roi.max_x.saturating_add(1), self.width()).max(min_x);
+        let max_y = clamp(roi.max_y.saturating_add(1), self.height()).max(min_y);
+
+        let mut intrinsics = *self;
+        intrinsics.0.width = max_x - min_x;
+        intrinsics.0.height = max_y - min_y;
+        intrinsics.0.ppx -= min_x as f32;
+        intrinsics.0.ppy -= min_y as f32;
+        intrinsics
+    }
+
+    /// Scale the focal lengths and principal point by independent factors in each direction.
+    ///
+    /// Pixel centers lie at integer coordinates, so the edge of the image is at `-0.5`. Scaling
+    /// about the edge of the image rather than the center of the first pixel keeps every pixel of
+    /// the scaled image centered over the pixels it was computed from.
+    fn rescaled(&self, width: usize, height: usize, scale_x: f32, scale_y: f32) -> Self {
+        let mut intrinsics = *self;
+        intrinsics.0.width = width as i32;
+        intrinsics.0.height = height as i32;
+        intrinsics.0.fx *= scale_x;
+        intrinsics.0.fy *= scale_y;
+        intrinsics.0.ppx = (self.0.ppx + 0.5) * scale_x - 0.5;
+        intrinsics.0.ppy = (self.0.ppy + 0.5) * scale_y - 0.5;
+        intrinsics
+    }
+
+    /// Project a 3D point in the stream's coordinate frame (in meters) to a pixel in the image.
+    ///
+    /// This is a native Rust implementation of `rs2_project_point_to_pixel` from librealsense2's
+    /// `rsutil.h`, and gives the same results for every distortion model without crossing the FFI
+    /// boundary.
+    pub fn project(&self, point: [f32; 3]) -> [f32; 2] {
+        let c = &self.0.coeffs;
+        let mut x = point[0] / point[2];
+        let mut y = point[1] / point[2];
+
+        match self.distortion().model {
+            Rs2DistortionModel::None => (),
+            Rs2DistortionModel::BrownConradyModified | Rs2DistortionModel::BrownConradyInverse => {
+                let [dx, dy] = modified_brown_conrady(c, x, y);
+                x = dx;
+                y = dy;
+            }
+            Rs2DistortionModel::BrownConrady => {
+                let r2 = x * x + y * y;
+                let f = 1.0 + c[0] * r2 + c[1] * r2 * r2 + c[4] * r2 * r2 * r2;
+
+                let dx = x * f + 2.0 * c[2] * x * y + c[3] * (r2 + 2.0 * x * x);
+                let dy = y * f + 2.0 * c[3] * x * y + c[2] * (r2 + 2.0 * y * y);
+                x = dx;
+                y = dy;
+            }
+            Rs2DistortionModel::FThetaFisheye => {
+                let r = (x * x + y * y).sqrt().max(f32::EPSILON);
+                let rd = 1.0 / c[0] * (2.0 * r * (c[0] / 2.0).tan()).atan();
+                x *= rd / r;
+                y *= rd / r;
+            }
+            Rs2DistortionModel::KannalaBrandt => {
+                let r = (x * x + y * y).sqrt().max(f32::EPSILON);
+                let theta = r.atan();
+                let theta2 = theta * theta;
+                let series =
+                    1.0 + theta2 * (c[0] + theta2 * (c[1] + theta2 * (c[2] + theta2 * c[3])));
+                let rd = theta * series;
+                x *= rd / r;
+                y *= rd / r;
+            }
+        }
+
+        [x * self.0.fx + self.0.ppx, y * self.0.fy + self.0.ppy]
+    }
+
+    /// Deproject a pixel in the image to a 3D point in the stream's coordinate frame, given the
+    /// depth (in meters) at that pixel.
+    ///
+    /// This is a native Rust implementation of `rs2_deproject_pixel_to_point` from librealsense2's
+    /// `rsutil.h`, and gives the same results for every distortion model without crossing the FFI
+    /// boundary.
+    ///
+    /// librealsense2 refuses to deproject pixels from an image with
+    /// [`BrownConradyModified`](Rs2DistortionModel::BrownConradyModified) distortion, since the
+    /// model has no closed-form inverse. Here the model is instead inverted iteratively, which is
+    /// accurate to well within a pixel for the distortion seen on RealSense devices.
+    pub fn deproject(&self, pixel: [f32; 2], depth: f32) -> [f32; 3] {
+        let c = &self.0.coeffs;
+        let xo = (pixel[0] - self.0.ppx) / self.0.fx;
+        let yo = (pixel[1] - self.0.ppy) / self.0.fy;
+        let mut x = xo;
+        let mut y = yo;
+
+        match self.distortion().model {
+            Rs2DistortionModel::None => (),
+            Rs2DistortionModel::BrownConradyModified => {
+                // Fixed-point iteration on the forward model, nudging the undistorted point by the
+                // error between its distortion and the observed point.
+                for _ in 0..20 {
+                    let [dx, dy] = modified_brown_conrady(c, x, y);
+                    x += xo - dx;
+                    y += yo - dy;
+                }
+            }
+            Rs2DistortionModel::BrownConradyInverse => {
+                // 10 iterations determined empirically by librealsense2
+                for _ in 0..10 {
+                    let r2 = x * x + y * y;
+                    let icdist = 1.0 / (1.0 + ((c[4] * r2 + c[1]) * r2 + c[0]) * r2);
+                    let xq = x / icdist;
+                    let yq = y / icdist;
+                    let delta_x = 2.0 * c[2] * xq * yq + c[3] * (r2 + 2.0 * xq * xq);
+                    let delta_y = 2.0 * c[3] * xq * yq + c[2] * (r2 + 2.0 * yq * yq);
+                    x = (xo - delta_x) * icdist;
+                    y = (yo - delta_y) * icdist;
+                }
+            }
+            Rs2DistortionModel::BrownConrady => {
+                // 10 iterations determined empirically by librealsense2
+                for _ in 0..10 {
+                    let r2 = x * x + y * y;
+                    let icdist = 1.0 / (1.0 + ((c[4] * r2 + c[1]) * r2 + c[0]) * r2);
+                    let delta_x = 2.0 * c[2] * x * y + c[3] * (r2 + 2.0 * x * x);
+                    let delta_y = 2.0 * c[3] * x * y + c[2] * (r2 + 2.0 * y * y);
+                    x = (xo - delta_x) * icdist;
+                    y = (yo - delta_y) * icdist;
+                }
+            }
+            Rs2DistortionModel::FThetaFisheye => {
+                let rd = (x * x + y * y).sqrt().max(f32::EPSILON);
+                let r = (c[0] * rd).tan() / (2.0 * (c[0] / 2.0).tan()).atan();
+                x *= r / rd;
+                y *= r / rd;
+            }
+            Rs2DistortionModel::KannalaBrandt => {
+                let rd = (x * x + y * y).sqrt().max(f32::EPSILON);
+
+                // Newton's method on theta * series(theta) - rd
+                let mut theta = rd;
+                let mut theta2 = rd * rd;
+                for _ in 0..4 {
+                    let f = theta
+                        * (1.0
+                            + theta2 * (c[0] + theta2 * (c[1] + theta2 * (c[2] + theta2 * c[3]))))
+                        - rd;
+                    if f.abs() < f32::EPSILON {
+                        break;
+                    }
+                    let df = 1.0
+                        + theta2
+                            * (3.0 * c[0]
+                                + theta2
+                                    * (5.0 * c[1] + theta2 * (7.0 * c[2] + 9.0 * theta2 * c[3])));
+                    theta -= f / df;
+                    theta2 = theta * theta;
+                }
+
+                let r = theta.tan();
+                x *= r / rd;
+                y *= r / rd;
+            }
+        }
+
+        [depth * x, depth * y, depth]
+    }
+}
+
+/// Apply modified Brown-Conrady distortion to a normalized image point.
+///
+/// Tangential distortion is applied to the radially distorted point, which is what distinguishes
+/// this model from plain Brown-Conrady.
+#[inline]
+fn modified_brown_conrady(c: &[f32; 5], x: f32, y: f32) -> [f32; 2] {
+    let r2 = x * x + y * y;
+    let f = 1.0 + c[0] * r2 + c[1] * r2 * r2 + c[4] * r2 * r2 * r2;
+    let x = x * f;
+    let y = y * f;
+
+    [
+        x + 2.0 * c[2] * x * y + c[3] * (r2 + 2.0 * x * x),
+        y + 2.0 * c[3] * x * y + c[2] * (r2 + 2.0 * y * y),
+    ]
+}
+
+impl PartialEq for Rs2Intrinsics {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.width == other.0.width
+            && self.0.height == other.0.height
+            && self.0.ppx == other.0.ppx
+            && self.0.ppy == other.0.ppy
+            && self.0.fx == other.0.fx
+            && self.0.fy == other.0.fy
+            && self.0.model == other.0.model
+            && self.0.coeffs == other.0.coeffs
+    }
 }
 
 unsafe impl Send for Rs2Intrinsics {}
@@ -143,10 +535,51 @@ unsafe impl Send for Rs2Intrinsics {}
 ///
 /// Use the function `stream_profile.extrinsics()` to retrieve these extrinsics from a certain stream in relation to
 /// another stream on the same device.
-#[derive(Debug)]
+///
+/// Extrinsics map points from the coordinate frame of one stream (the origin) into the coordinate
+/// frame of another (the target), as `target = rotation * origin + translation`.
+#[derive(Debug, Clone, Copy)]
 pub struct Rs2Extrinsics(pub sys::rs2_extrinsics);
 
 impl Rs2Extrinsics {
+    /// Construct extrinsics from a column-major 3x3 rotation matrix and a translation in meters.
+    pub fn new(rotation: [f32; 9], translation: [f32; 3]) -> Self {
+        Rs2Extrinsics(sys::rs2_extrinsics {
+            rotation,
+            translation,
+        })
+    }
+
+    /// Extrinsics that leave every point unchanged.
+    pub fn identity() -> Self {
+        Self::new([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0], [0.0; 3])
+    }
+
+    /// Construct extrinsics from a rotation quaternion and a translation in meters.
+    ///
+    /// Components of the quaternion are ordered as Qi, Qj, Qk, Qr, matching
+    /// [`PoseFrame::rotation`](crate::frame::PoseFrame::rotation). The quaternion does not need to
+    /// be normalized.
+    pub fn from_quaternion(quaternion: [f32; 4], translation: [f32; 3]) -> Self {
+        let norm = quaternion.iter().map(|q| q * q).sum::<f32>().sqrt();
+        let [x, y, z, w] = quaternion.map(|q| q / norm);
+
+        Self::new(
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y + z * w),
+                2.0 * (x * z - y * w),
+                2.0 * (x * y - z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z + x * w),
+                2.0 * (x * z + y * w),
+                2.0 * (y * z - x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+            translation,
+        )
+    }
+
     /// Column-major 3x3 rotation matrix
     pub fn rotation(&self) -> [f32; 9usize] {
         self.0.rotation
@@ -155,6 +588,118 @@ impl Rs2Extrinsics {
     pub fn translation(&self) -> [f32; 3usize] {
         self.0.translation
     }
+
+    /// Transform a point (in meters) from the origin stream's coordinate frame to the target
+    /// stream's coordinate frame.
+    ///
+    /// This is a native Rust implementation of `rs2_transform_point_to_point` from librealsense2's
+    /// `rsutil.h`.
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        let r = &self.0.rotation;
+        let t = &self.0.translation;
+        [
+            r[0] * point[0] + r[3] * point[1] + r[6] * point[2] + t[0],
+            r[1] * point[0] + r[4] * point[1] + r[7] * point[2] + t[1],
+            r[2] * point[0] + r[5] * point[1] + r[8] * point[2] + t[2],
+        ]
+    }
+
+    /// Get the extrinsics mapping from the target stream back to the origin stream.
+    pub fn inverse(&self) -> Self {
+        let r = &self.0.rotation;
+        let t = &self.0.translation;
+
+        // The inverse of a rotation is its transpose.
+        let rotation = [r[0], r[3], r[6], r[1], r[4], r[7], r[2], r[5], r[8]];
+        let rotated = Self::new(rotation, [0.0; 3]).transform_point(*t);
+
+        Self::new(rotation, [-rotated[0], -rotated[1], -rotated[2]])
+    }
+
+    /// Chain these extrinsics (from stream A to stream B) with `next` (from stream B to stream C),
+    /// producing extrinsics from stream A to stream C.
+    pub fn compose(&self, next: &Rs2Extrinsics) -> Self {
+        let a = &self.0.rotation;
+        let b = &next.0.rotation;
+
+        let mut rotation = [0.0; 9];
+        for col in 0..3 {
+            for row in 0..3 {
+                rotation[col * 3 + row] = (0..3).map(|k| b[k * 3 + row] * a[col * 3 + k]).sum();
+            }
+        }
+
+        Self::new(rotation, next.transform_point(self.0.translation))
+    }
+
+    /// Get the extrinsics as a 4x4 homogeneous transformation matrix.
+    ///
+    /// The matrix is returned as an array of rows, i.e. `matrix[row][col]`.
+    pub fn to_matrix(&self) -> [[f32; 4]; 4] {
+        let r = &self.0.rotation;
+        let t = &self.0.translation;
+        [
+            [r[0], r[3], r[6], t[0]],
+            [r[1], r[4], r[7], t[1]],
+            [r[2], r[5], r[8], t[2]],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
+    /// Get the rotation as a unit quaternion.
+    ///
+    /// Components are ordered as Qi, Qj, Qk, Qr, matching
+    /// [`PoseFrame::rotation`](crate::frame::PoseFrame::rotation).
+    pub fn quaternion(&self) -> [f32; 4] {
+        let r = &self.0.rotation;
+        // Row-major accessors for the column-major rotation.
+        let m = |row: usize, col: usize| r[col * 3 + row];
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+
+        // Branch on the largest diagonal term to stay numerically stable.
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [
+                (m(2, 1) - m(1, 2)) / s,
+                (m(0, 2) - m(2, 0)) / s,
+                (m(1, 0) - m(0, 1)) / s,
+                s / 4.0,
+            ]
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+            [
+                s / 4.0,
+                (m(0, 1) + m(1, 0)) / s,
+                (m(0, 2) + m(2, 0)) / s,
+                (m(2, 1) - m(1, 2)) / s,
+            ]
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+            [
+                (m(0, 1) + m(1, 0)) / s,
+                s / 4.0,
+                (m(1, 2) + m(2, 1)) / s,
+                (m(0, 2) - m(2, 0)) / s,
+            ]
+        } else {
+            let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+            [
+                (m(0, 2) + m(2, 0)) / s,
+                (m(1, 2) + m(2, 1)) / s,
+                s / 4.0,
+                (m(1, 0) - m(0, 1)) / s,
+            ]
+        };
+
+        let norm = q.iter().map(|c| c * c).sum::<f32>().sqrt();
+        [q[0] / norm, q[1] / norm, q[2] / norm, q[3] / norm]
+    }
+}
+
+impl PartialEq for Rs2Extrinsics {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.rotation == other.0.rotation && self.0.translation == other.0.translation
+    }
 }
 
 unsafe impl Send for Rs2Extrinsics {}
@@ -171,3 +716,463 @@ pub struct Rs2Roi {
     /// Bottom coordinate of the region of interest.
     pub max_y: i32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intrinsics(
+        size: [i32; 2],
+        principal_point: [f32; 2],
+        focal_length: [f32; 2],
+        model: Rs2DistortionModel,
+        coeffs: [f32; 5],
+    ) -> Rs2Intrinsics {
+        Rs2Intrinsics(sys::rs2_intrinsics {
+            width: size[0],
+            height: size[1],
+            ppx: principal_point[0],
+            ppy: principal_point[1],
+            fx: focal_length[0],
+            fy: focal_length[1],
+            model: model.to_u32().unwrap(),
+            coeffs,
+        })
+    }
+
+    fn color(model: Rs2DistortionModel, coeffs: [f32; 5]) -> Rs2Intrinsics {
+        intrinsics([1280, 720], [640.2, 360.8], [910.4, 908.9], model, coeffs)
+    }
+
+    fn fisheye(model: Rs2DistortionModel, coeffs: [f32; 5]) -> Rs2Intrinsics {
+        intrinsics([848, 800], [424.3, 400.1], [286.2, 286.4], model, coeffs)
+    }
+
+    const POINTS: [[f32; 3]; 3] = [[0.1, -0.05, 1.2], [-0.4, 0.3, 0.9], [0.0, 0.0, 2.0]];
+    const PIXELS: [[f32; 2]; 3] = [[300.0, 500.0], [700.5, 400.25], [424.3, 400.1]];
+    const DEPTH: f32 = 1.5;
+
+    /// Check projections and deprojections against values computed with librealsense2's rsutil.h.
+    fn check(intrinsics: &Rs2Intrinsics, projected: [[f32; 2]; 3], deprojected: [[f32; 3]; 3]) {
+        for (point, expected) in POINTS.iter().zip(projected.iter()) {
+            let pixel = intrinsics.project(*point);
+            for i in 0..2 {
+                assert!(
+                    (pixel[i] - expected[i]).abs() < 1e-3,
+                    "Projected {:?} to {:?}, expected {:?}",
+                    point,
+                    pixel,
+                    expected
+                );
+            }
+        }
+
+        for (pixel, expected) in PIXELS.iter().zip(deprojected.iter()) {
+            let point = intrinsics.deproject(*pixel, DEPTH);
+            for i in 0..3 {
+                assert!(
+                    (point[i] - expected[i]).abs() < 1e-4,
+                    "Deprojected {:?} to {:?}, expected {:?}",
+                    pixel,
+                    point,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_rsutil_without_distortion() {
+        check(
+            &intrinsics(
+                [640, 480],
+                [321.5, 242.25],
+                [383.7, 383.7],
+                Rs2DistortionModel::None,
+                [0.0; 5],
+            ),
+            [[353.475, 226.2625], [150.96664, 370.15002], [321.5, 242.25]],
+            [
+                [-0.08405, 1.007623, 1.5],
+                [1.481626, 0.61767, 1.5],
+                [0.401876, 0.617084, 1.5],
+            ],
+        );
+    }
+
+    #[test]
+    fn matches_rsutil_with_brown_conrady() {
+        check(
+            &color(
+                Rs2DistortionModel::BrownConrady,
+                [-0.055, 0.065, -0.0008, 0.0011, -0.02],
+            ),
+            [[716.0585, 322.9313], [241.0994, 659.6393], [640.2, 360.8]],
+            [
+                [-0.56561, 0.231901, 1.5],
+                [0.099368, 0.065131, 1.5],
+                [-0.357103, 0.065163, 1.5],
+            ],
+        );
+    }
+
+    #[test]
+    fn matches_rsutil_with_inverse_brown_conrady() {
+        check(
+            &color(
+                Rs2DistortionModel::BrownConradyInverse,
+                [0.12, -0.25, 0.001, -0.002, 0.09],
+            ),
+            [
+                [716.0968, 322.91412],
+                [227.57446, 669.61896],
+                [640.2, 360.8],
+            ],
+            [
+                [-0.551828, 0.226123, 1.5],
+                [0.099315, 0.06506, 1.5],
+                [-0.353042, 0.064316, 1.5],
+            ],
+        );
+    }
+
+    #[test]
+    fn matches_rsutil_with_ftheta() {
+        check(
+            &fisheye(
+                Rs2DistortionModel::FThetaFisheye,
+                [0.92, 0.0, 0.0, 0.0, 0.0],
+            ),
+            [[449.9153, 387.2834], [299.06235, 494.09387], [424.3, 400.1]],
+            [
+                [-0.842682, 0.676791, 1.5],
+                [2.361342, 0.001281, 1.5],
+                [0.0, 0.0, 1.5],
+            ],
+        );
+    }
+
+    #[test]
+    fn matches_rsutil_with_kannala_brandt() {
+        check(
+            &fisheye(
+                Rs2DistortionModel::KannalaBrandt,
+                [-0.0066, 0.0432, -0.0409, 0.0077, 0.0],
+            ),
+            [
+                [448.08005, 388.20166],
+                [308.13693, 487.28317],
+                [424.3, 400.1],
+            ],
+            [
+                [-0.727552, 0.584326, 1.5],
+                [2.147497, 0.001165, 1.5],
+                [0.0, 0.0, 1.5],
+            ],
+        );
+    }
+
+    #[test]
+    fn modified_brown_conrady_round_trips() {
+        let intrinsics = color(
+            Rs2DistortionModel::BrownConradyModified,
+            [0.12, -0.25, 0.001, -0.002, 0.09],
+        );
+
+        // Projection matches rsutil.h, which shares its implementation with inverse Brown-Conrady.
+        let pixel = intrinsics.project(POINTS[1]);
+        assert!((pixel[0] - 227.57446).abs() < 1e-3 && (pixel[1] - 669.61896).abs() < 1e-3);
+
+        for point in POINTS.iter() {
+            let pixel = intrinsics.project(*point);
+            let deprojected = intrinsics.deproject(pixel, point[2]);
+            for i in 0..3 {
+                assert!(
+                    (deprojected[i] - point[i]).abs() < 1e-4,
+                    "Round trip of {:?} gave {:?}",
+                    point,
+                    deprojected
+                );
+            }
+        }
+    }
+
+    /// A rotation of 90 degrees about the Z axis, followed by a translation.
+    fn rotate_z() -> Rs2Extrinsics {
+        Rs2Extrinsics::new(
+            [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+            [0.1, 0.2, 0.3],
+        )
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!(
+                (a - e).abs() < 1e-6,
+                "Got {:?}, expected {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn transforms_points() {
+        let point = [1.0, 2.0, 3.0];
+        assert_close(&Rs2Extrinsics::identity().transform_point(point), &point);
+        assert_close(&rotate_z().transform_point(point), &[-1.9, 1.2, 3.3]);
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let extrinsics = rotate_z();
+        let point = [0.5, -1.5, 2.0];
+        let round_trip = extrinsics
+            .inverse()
+            .transform_point(extrinsics.transform_point(point));
+        assert_close(&round_trip, &point);
+
+        let identity = extrinsics.compose(&extrinsics.inverse());
+        assert_close(&identity.rotation(), &Rs2Extrinsics::identity().rotation());
+        assert_close(&identity.translation(), &[0.0; 3]);
+    }
+
+    #[test]
+    fn compose_chains_transforms() {
+        let first = rotate_z();
+        let second = Rs2Extrinsics::new(
+            [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -1.0, 0.0],
+            [-0.4, 0.0, 1.0],
+        );
+        let point = [1.0, 2.0, 3.0];
+
+        assert_close(
+            &first.compose(&second).transform_point(point),
+            &second.transform_point(first.transform_point(point)),
+        );
+    }
+
+    #[test]
+    fn converts_to_matrix_and_quaternion() {
+        let matrix = rotate_z().to_matrix();
+        assert_close(&matrix[0], &[0.0, -1.0, 0.0, 0.1]);
+        assert_close(&matrix[1], &[1.0, 0.0, 0.0, 0.2]);
+        assert_close(&matrix[2], &[0.0, 0.0, 1.0, 0.3]);
+        assert_close(&matrix[3], &[0.0, 0.0, 0.0, 1.0]);
+
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(&rotate_z().quaternion(), &[0.0, 0.0, half, half]);
+        assert_close(
+            &Rs2Extrinsics::identity().quaternion(),
+            &[0.0, 0.0, 0.0, 1.0],
+        );
+
+        // A rotation of 180 degrees about the X axis has a zero trace.
+        let flip = Rs2Extrinsics::new([1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, -1.0], [0.0; 3]);
+        assert_close(&flip.quaternion(), &[1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn converts_from_quaternion() {
+        let extrinsics = Rs2Extrinsics::from_quaternion(rotate_z().quaternion(), [0.1, 0.2, 0.3]);
+        assert_close(&extrinsics.rotation(), &rotate_z().rotation());
+        assert_close(&extrinsics.translation(), &[0.1, 0.2, 0.3]);
+
+        // Quaternions are normalized first.
+        let flip = Rs2Extrinsics::from_quaternion([2.0, 0.0, 0.0, 0.0], [0.0; 3]);
+        assert_close(&flip.transform_point([1.0, 2.0, 3.0]), &[1.0, -2.0, -3.0]);
+    }
+
+    /// Intrinsics for a selection of distortion models.
+    fn all_models() -> Vec<Rs2Intrinsics> {
+        vec![
+            intrinsics(
+                [640, 480],
+                [321.5, 242.25],
+                [383.7, 383.7],
+                Rs2DistortionModel::None,
+                [0.0; 5],
+            ),
+            color(
+                Rs2DistortionModel::BrownConrady,
+                [-0.055, 0.065, -0.0008, 0.0011, -0.02],
+            ),
+            color(
+                Rs2DistortionModel::BrownConradyModified,
+                [0.12, -0.25, 0.001, -0.002, 0.09],
+            ),
+            fisheye(
+                Rs2DistortionModel::KannalaBrandt,
+                [-0.0066, 0.0432, -0.0409, 0.0077, 0.0],
+            ),
+            fisheye(
+                Rs2DistortionModel::FThetaFisheye,
+                [0.92, 0.0, 0.0, 0.0, 0.0],
+            ),
+        ]
+    }
+
+    #[test]
+    fn scaling_keeps_pixels_over_the_same_rays() {
+        for original in all_models().iter() {
+            let scaled = original.scaled(0.25).unwrap();
+            assert_eq!(scaled.width(), original.width() / 4);
+            assert_eq!(scaled.distortion(), original.distortion());
+
+            // Each scaled pixel is centered over a 4x4 block of original pixels.
+            for point in POINTS.iter() {
+                let [u, v] = original.project(*point);
+                let [du, dv] = scaled.project(*point);
+                assert!((du - ((u + 0.5) / 4.0 - 0.5)).abs() < 1e-3);
+                assert!((dv - ((v + 0.5) / 4.0 - 0.5)).abs() < 1e-3);
+
+                let expected = original.deproject([u, v], point[2]);
+                let deprojected = scaled.deproject([du, dv], point[2]);
+                for i in 0..3 {
+                    assert!((deprojected[i] - expected[i]).abs() < 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scaling_rejects_invalid_factors() {
+        let original = color(Rs2DistortionModel::None, [0.0; 5]);
+        for &factor in [0.0, -0.5, f32::NAN, f32::INFINITY].iter() {
+            assert!(matches!(
+                original.scaled(factor),
+                Err(IntrinsicsScaleError::InvalidScale(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn decimation_matches_the_decimation_filter() {
+        // A D435 depth stream; the expected values are what the SDK decimation filter reports.
+        let depth = intrinsics(
+            [1280, 720],
+            [639.5, 358.3],
+            [643.2, 643.2],
+            Rs2DistortionModel::BrownConrady,
+            [0.0; 5],
+        );
+
+        let decimated = depth.decimated(2).unwrap();
+        assert_eq!((decimated.width(), decimated.height()), (640, 360));
+        assert_eq!(decimated.fx(), 321.6);
+        assert_eq!(decimated.ppx(), 319.75);
+        assert_eq!(decimated.ppy(), 179.15);
+
+        // 1280 / 3 = 426 is padded to 428; 720 / 3 = 240 already is a multiple of 4.
+        let decimated = depth.decimated(3).unwrap();
+        assert_eq!((decimated.width(), decimated.height()), (428, 240));
+        assert_eq!(decimated.fx(), 643.2 / 3.0);
+        assert_eq!(decimated.ppx(), 639.5 / 3.0);
+
+        // 848 / 5 = 169 is padded to 172, 480 / 5 = 96.
+        let small = intrinsics(
+            [848, 480],
+            [423.8, 239.1],
+            [421.5, 421.5],
+            Rs2DistortionModel::BrownConrady,
+            [0.0; 5],
+        );
+        let decimated = small.decimated(5).unwrap();
+        assert_eq!((decimated.width(), decimated.height()), (172, 96));
+        assert_eq!(decimated.distortion(), small.distortion());
+
+        assert_eq!(depth.decimated(1).unwrap().0.ppx, depth.ppx());
+        assert!(matches!(
+            depth.decimated(0),
+            Err(IntrinsicsScaleError::InvalidDecimation)
+        ));
+    }
+
+    #[test]
+    fn resizing_scales_each_axis() {
+        let original = color(Rs2DistortionModel::None, [0.0; 5]);
+        let resized = original.resized(640, 480);
+        assert_eq!((resized.width(), resized.height()), (640, 480));
+        assert!((resized.fx() - original.fx() / 2.0).abs() < 1e-4);
+        assert!((resized.fy() - original.fy() * 2.0 / 3.0).abs() < 1e-4);
+
+        // Scaling up and back down again gives back the original intrinsics.
+        let round_trip = original.resized(2560, 1440).resized(1280, 720);
+        assert!((round_trip.ppx() - original.ppx()).abs() < 1e-3);
+        assert!((round_trip.ppy() - original.ppy()).abs() < 1e-3);
+        assert!((round_trip.fx() - original.fx()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cropping_shifts_pixels() {
+        let roi = Rs2Roi {
+            min_x: 100,
+            min_y: 50,
+            max_x: 419,
+            max_y: 329,
+        };
+
+        for original in all_models().iter() {
+            let cropped = original.cropped(&roi);
+            assert_eq!((cropped.width(), cropped.height()), (320, 280));
+
+            for point in POINTS.iter() {
+                let [u, v] = original.project(*point);
+                let [cu, cv] = cropped.project(*point);
+                assert!((cu - (u - 100.0)).abs() < 1e-3);
+                assert!((cv - (v - 50.0)).abs() < 1e-3);
+            }
+        }
+
+        // Regions are clamped to the image.
+        let original = color(Rs2DistortionModel::None, [0.0; 5]);
+        let outside = Rs2Roi {
+            min_x: -10,
+            min_y: 700,
+            max_x: 2000,
+            max_y: 800,
+        };
+        let cropped = original.cropped(&outside);
+        assert_eq!((cropped.width(), cropped.height()), (1280, 20));
+        assert_eq!(cropped.ppy(), original.ppy() - 700.0);
+    }
+
+    #[test]
+    fn motion_intrinsics_correct_samples() {
+        let intrinsics = Rs2MotionDeviceIntrinsics::new(
+            [
+                [1.02, 0.01, 0.0, 0.05],
+                [0.0, 0.98, 0.0, -0.1],
+                [0.0, 0.0, 1.0, 0.2],
+            ],
+            [1e-4; 3],
+            [1e-8; 3],
+        );
+
+        let corrected = intrinsics.correct([1.0, 2.0, -9.8]);
+        let expected = [1.02 + 0.02 - 0.05, 1.96 + 0.1, -9.8 - 0.2];
+        for (c, e) in corrected.iter().zip(expected.iter()) {
+            assert!((c - e).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn motion_intrinsics_noise_model() {
+        let intrinsics = Rs2MotionDeviceIntrinsics::new(
+            [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+            ],
+            [4e-4, 1e-4, 1e-4],
+            [1e-8, 1e-8, 4e-8],
+        );
+
+        let model = intrinsics.noise_model(400.0);
+        assert!((model.noise_density[0] - 0.001).abs() < 1e-7);
+        assert!((model.noise_density[1] - 0.0005).abs() < 1e-7);
+        assert!((model.random_walk[2] - 0.004).abs() < 1e-7);
+        assert_eq!(model.max_noise_density(), model.noise_density[0]);
+        assert_eq!(model.max_random_walk(), model.random_walk[2]);
+    }
+}
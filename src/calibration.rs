@@ -0,0 +1,830 @@
+//! Serialization of calibration types, and conversion to and from common calibration file formats.
+//!
+//! With the `serde` feature enabled, [`Rs2Intrinsics`], [`Rs2Extrinsics`], [`Rs2Distortion`] and
+//! [`Rs2MotionDeviceIntrinsics`] implement `Serialize` and `Deserialize`, so they can be stored in
+//! whatever format your application already uses.
+//!
+//! This module additionally reads and writes three calibration formats that are common in the
+//! robotics ecosystem:
+//!
+//! - ROS `CameraInfo` YAML, as written by `camera_calibration_parsers`.
+//! - OpenCV `FileStorage` YAML, as written by OpenCV's calibration samples.
+//! - Kalibr camchain YAML, as written by `kalibr_calibrate_cameras`.
+//!
+//! None of these formats can describe every distortion model used by RealSense devices.
+//! Converting intrinsics that use a model the format cannot represent is an error, rather than
+//! silently writing a different camera model.
+
+use crate::{
+    base::{Rs2Distortion, Rs2Extrinsics, Rs2Intrinsics, Rs2MotionDeviceIntrinsics},
+    kind::Rs2DistortionModel,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Occurs when calibration data cannot be converted to or from a calibration file format.
+#[derive(Error, Debug)]
+pub enum CalibrationError {
+    /// The calibration file could not be parsed.
+    #[error("Could not parse calibration file. Reason: {0}")]
+    CouldNotParse(String),
+    /// The calibration file could not be written.
+    #[error("Could not write calibration file. Reason: {0}")]
+    CouldNotWrite(String),
+    /// The distortion model (or its coefficients) cannot be represented in the requested format.
+    #[error("Distortion model {0:?} cannot be represented in the {1} format.")]
+    UnsupportedDistortion(Rs2DistortionModel, &'static str),
+    /// The calibration file uses a camera or distortion model that has no RealSense equivalent.
+    #[error("Unsupported model in calibration file: {0}")]
+    UnsupportedModel(String),
+    /// A matrix or vector in the calibration file has the wrong number of elements.
+    #[error("Field {0} has the wrong size. Expected: {1} elements; Found: {2}")]
+    InvalidSize(&'static str, usize, usize),
+}
+
+/// Serde representation of [`Rs2Intrinsics`].
+#[derive(Serialize, Deserialize)]
+struct IntrinsicsDef {
+    width: usize,
+    height: usize,
+    ppx: f32,
+    ppy: f32,
+    fx: f32,
+    fy: f32,
+    distortion: Rs2Distortion,
+}
+
+impl Serialize for Rs2Intrinsics {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        IntrinsicsDef {
+            width: self.width(),
+            height: self.height(),
+            ppx: self.ppx(),
+            ppy: self.ppy(),
+            fx: self.fx(),
+            fy: self.fy(),
+            distortion: self.distortion(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rs2Intrinsics {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let def = IntrinsicsDef::deserialize(deserializer)?;
+        Ok(Rs2Intrinsics::new(
+            def.width,
+            def.height,
+            [def.ppx, def.ppy],
+            [def.fx, def.fy],
+            def.distortion,
+        ))
+    }
+}
+
+/// Serde representation of [`Rs2Extrinsics`].
+#[derive(Serialize, Deserialize)]
+struct ExtrinsicsDef {
+    rotation: [f32; 9],
+    translation: [f32; 3],
+}
+
+impl Serialize for Rs2Extrinsics {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ExtrinsicsDef {
+            rotation: self.rotation(),
+            translation: self.translation(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rs2Extrinsics {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let def = ExtrinsicsDef::deserialize(deserializer)?;
+        Ok(Rs2Extrinsics::new(def.rotation, def.translation))
+    }
+}
+
+/// Serde representation of [`Rs2MotionDeviceIntrinsics`].
+#[derive(Serialize, Deserialize)]
+struct MotionDeviceIntrinsicsDef {
+    data: [[f32; 4]; 3],
+    noise_variances: [f32; 3],
+    bias_variances: [f32; 3],
+}
+
+impl Serialize for Rs2MotionDeviceIntrinsics {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MotionDeviceIntrinsicsDef {
+            data: self.data(),
+            noise_variances: self.noise_variances(),
+            bias_variances: self.bias_variances(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rs2MotionDeviceIntrinsics {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let def = MotionDeviceIntrinsicsDef::deserialize(deserializer)?;
+        Ok(Rs2MotionDeviceIntrinsics(
+            realsense_sys::rs2_motion_device_intrinsic {
+                data: def.data,
+                noise_variances: def.noise_variances,
+                bias_variances: def.bias_variances,
+            },
+        ))
+    }
+}
+
+/// Name of the ROS / OpenCV model for Brown-Conrady distortion.
+const PLUMB_BOB: &str = "plumb_bob";
+/// Name of the ROS / OpenCV / Kalibr model for Kannala-Brandt distortion.
+const EQUIDISTANT: &str = "equidistant";
+
+/// Split distortion into the name of its ROS / OpenCV model and its coefficients.
+fn plumb_bob_or_equidistant(
+    distortion: &Rs2Distortion,
+    format: &'static str,
+) -> Result<(&'static str, Vec<f32>), CalibrationError> {
+    match distortion.model {
+        Rs2DistortionModel::None => Ok((PLUMB_BOB, vec![0.0; 5])),
+        Rs2DistortionModel::BrownConrady => Ok((PLUMB_BOB, distortion.coeffs.to_vec())),
+        Rs2DistortionModel::KannalaBrandt => Ok((EQUIDISTANT, distortion.coeffs[..4].to_vec())),
+        model => Err(CalibrationError::UnsupportedDistortion(model, format)),
+    }
+}
+
+/// Build distortion from the name of a ROS / OpenCV model and its coefficients.
+///
+/// Brown-Conrady distortion with all-zero coefficients is treated as no distortion at all.
+fn distortion_from_model(model: &str, coeffs: &[f32]) -> Result<Rs2Distortion, CalibrationError> {
+    let mut padded = [0.0; 5];
+    match model {
+        PLUMB_BOB => {
+            if coeffs.len() != 4 && coeffs.len() != 5 {
+                return Err(CalibrationError::InvalidSize(
+                    "distortion_coefficients",
+                    5,
+                    coeffs.len(),
+                ));
+            }
+            padded[..coeffs.len()].copy_from_slice(coeffs);
+
+            let model = if padded.iter().all(|&c| c == 0.0) {
+                Rs2DistortionModel::None
+            } else {
+                Rs2DistortionModel::BrownConrady
+            };
+            Ok(Rs2Distortion {
+                model,
+                coeffs: padded,
+            })
+        }
+        EQUIDISTANT => {
+            if coeffs.len() != 4 {
+                return Err(CalibrationError::InvalidSize(
+                    "distortion_coefficients",
+                    4,
+                    coeffs.len(),
+                ));
+            }
+            padded[..4].copy_from_slice(coeffs);
+            Ok(Rs2Distortion {
+                model: Rs2DistortionModel::KannalaBrandt,
+                coeffs: padded,
+            })
+        }
+        other => Err(CalibrationError::UnsupportedModel(other.to_string())),
+    }
+}
+
+/// Build intrinsics from a row-major 3x3 camera matrix.
+fn intrinsics_from_camera_matrix(
+    width: usize,
+    height: usize,
+    camera_matrix: &[f32],
+    distortion: Rs2Distortion,
+) -> Result<Rs2Intrinsics, CalibrationError> {
+    if camera_matrix.len() != 9 {
+        return Err(CalibrationError::InvalidSize(
+            "camera_matrix",
+            9,
+            camera_matrix.len(),
+        ));
+    }
+
+    Ok(Rs2Intrinsics::new(
+        width,
+        height,
+        [camera_matrix[2], camera_matrix[5]],
+        [camera_matrix[0], camera_matrix[4]],
+        distortion,
+    ))
+}
+
+/// Get the row-major 3x3 camera matrix of a set of intrinsics.
+fn camera_matrix(intrinsics: &Rs2Intrinsics) -> Vec<f32> {
+    vec![
+        intrinsics.fx(),
+        0.0,
+        intrinsics.ppx(),
+        0.0,
+        intrinsics.fy(),
+        intrinsics.ppy(),
+        0.0,
+        0.0,
+        1.0,
+    ]
+}
+
+/// A matrix in a ROS `CameraInfo` YAML file.
+#[derive(Serialize, Deserialize)]
+struct RosMatrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f32>,
+}
+
+/// A ROS `CameraInfo` YAML file.
+#[derive(Serialize, Deserialize)]
+struct RosCameraInfo {
+    image_width: usize,
+    image_height: usize,
+    #[serde(default)]
+    camera_name: String,
+    camera_matrix: RosMatrix,
+    distortion_model: String,
+    distortion_coefficients: RosMatrix,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rectification_matrix: Option<RosMatrix>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    projection_matrix: Option<RosMatrix>,
+}
+
+/// Write intrinsics as a ROS `CameraInfo` YAML file, as read by `camera_calibration_parsers`.
+///
+/// The rectification matrix is the identity, and the projection matrix matches the camera matrix,
+/// as is the case for a monocular camera.
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::UnsupportedDistortion`] if the intrinsics do not use the
+/// [`None`](Rs2DistortionModel::None), [`BrownConrady`](Rs2DistortionModel::BrownConrady) or
+/// [`KannalaBrandt`](Rs2DistortionModel::KannalaBrandt) distortion models.
+///
+/// Returns [`CalibrationError::CouldNotWrite`] if the YAML cannot be generated.
+pub fn to_ros_camera_info(
+    intrinsics: &Rs2Intrinsics,
+    camera_name: &str,
+) -> Result<String, CalibrationError> {
+    let (distortion_model, coeffs) =
+        plumb_bob_or_equidistant(&intrinsics.distortion(), "ROS CameraInfo")?;
+
+    let info = RosCameraInfo {
+        image_width: intrinsics.width(),
+        image_height: intrinsics.height(),
+        camera_name: camera_name.to_string(),
+        camera_matrix: RosMatrix {
+            rows: 3,
+            cols: 3,
+            data: camera_matrix(intrinsics),
+        },
+        distortion_model: distortion_model.to_string(),
+        distortion_coefficients: RosMatrix {
+            rows: 1,
+            cols: coeffs.len(),
+            data: coeffs,
+        },
+        rectification_matrix: Some(RosMatrix {
+            rows: 3,
+            cols: 3,
+            data: vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        }),
+        projection_matrix: Some(RosMatrix {
+            rows: 3,
+            cols: 4,
+            data: vec![
+                intrinsics.fx(),
+                0.0,
+                intrinsics.ppx(),
+                0.0,
+                0.0,
+                intrinsics.fy(),
+                intrinsics.ppy(),
+                0.0,
+                0.0,
+                0.0,
+                1.0,
+                0.0,
+            ],
+        }),
+    };
+
+    serde_yaml::to_string(&info).map_err(|e| CalibrationError::CouldNotWrite(e.to_string()))
+}
+
+/// Read intrinsics from a ROS `CameraInfo` YAML file.
+///
+/// Only the image size, camera matrix and distortion are read. The `plumb_bob` distortion model
+/// is read as [`BrownConrady`](Rs2DistortionModel::BrownConrady) (or
+/// [`None`](Rs2DistortionModel::None) if every coefficient is zero), and `equidistant` as
+/// [`KannalaBrandt`](Rs2DistortionModel::KannalaBrandt).
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::CouldNotParse`] if the YAML is not a valid `CameraInfo` file.
+///
+/// Returns [`CalibrationError::UnsupportedModel`] if the file uses any other distortion model.
+///
+/// Returns [`CalibrationError::InvalidSize`] if the camera matrix or distortion coefficients have
+/// the wrong number of elements.
+pub fn from_ros_camera_info(yaml: &str) -> Result<Rs2Intrinsics, CalibrationError> {
+    let info: RosCameraInfo =
+        serde_yaml::from_str(yaml).map_err(|e| CalibrationError::CouldNotParse(e.to_string()))?;
+
+    let distortion =
+        distortion_from_model(&info.distortion_model, &info.distortion_coefficients.data)?;
+    intrinsics_from_camera_matrix(
+        info.image_width,
+        info.image_height,
+        &info.camera_matrix.data,
+        distortion,
+    )
+}
+
+/// A matrix in an OpenCV `FileStorage` YAML file, with its `!!opencv-matrix` tag removed.
+#[derive(Deserialize)]
+struct OpenCvMatrix {
+    data: Vec<f32>,
+}
+
+/// An OpenCV `FileStorage` YAML file holding camera intrinsics.
+#[derive(Deserialize)]
+struct OpenCvCalibration {
+    image_width: usize,
+    image_height: usize,
+    camera_matrix: OpenCvMatrix,
+    distortion_coefficients: OpenCvMatrix,
+    #[serde(default)]
+    distortion_model: Option<String>,
+}
+
+/// Format a row-major matrix as an OpenCV `FileStorage` YAML entry.
+fn opencv_matrix(name: &str, rows: usize, cols: usize, data: &[f32]) -> String {
+    let data: Vec<String> = data.iter().map(|v| format!("{:?}", v)).collect();
+    format!(
+        "{}: !!opencv-matrix\n   rows: {}\n   cols: {}\n   dt: f\n   data: [ {} ]\n",
+        name,
+        rows,
+        cols,
+        data.join(", ")
+    )
+}
+
+/// Write intrinsics as an OpenCV `FileStorage` YAML file, as read by `cv::FileStorage`.
+///
+/// The camera matrix and distortion coefficients are written as `camera_matrix` and
+/// `distortion_coefficients`, matching OpenCV's calibration samples. Since OpenCV files do not
+/// describe which distortion model they use, the model is written alongside as
+/// `distortion_model`, using the same names as ROS.
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::UnsupportedDistortion`] if the intrinsics do not use the
+/// [`None`](Rs2DistortionModel::None), [`BrownConrady`](Rs2DistortionModel::BrownConrady) or
+/// [`KannalaBrandt`](Rs2DistortionModel::KannalaBrandt) distortion models.
+pub fn to_opencv_yaml(intrinsics: &Rs2Intrinsics) -> Result<String, CalibrationError> {
+    let (distortion_model, coeffs) =
+        plumb_bob_or_equidistant(&intrinsics.distortion(), "OpenCV FileStorage")?;
+
+    Ok(format!(
+        "%YAML:1.0\n---\nimage_width: {}\nimage_height: {}\n{}distortion_model: {}\n{}",
+        intrinsics.width(),
+        intrinsics.height(),
+        opencv_matrix("camera_matrix", 3, 3, &camera_matrix(intrinsics)),
+        distortion_model,
+        opencv_matrix("distortion_coefficients", 1, coeffs.len(), &coeffs),
+    ))
+}
+
+/// Read intrinsics from an OpenCV `FileStorage` YAML file.
+///
+/// The file must hold `image_width`, `image_height`, `camera_matrix` and
+/// `distortion_coefficients` entries. If the file does not hold a `distortion_model` entry, the
+/// coefficients are read as OpenCV's default `(k1, k2, p1, p2[, k3])` distortion model, i.e.
+/// [`BrownConrady`](Rs2DistortionModel::BrownConrady).
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::CouldNotParse`] if the YAML is not a valid `FileStorage` file.
+///
+/// Returns [`CalibrationError::UnsupportedModel`] if the file uses an unsupported distortion model.
+///
+/// Returns [`CalibrationError::InvalidSize`] if the camera matrix or distortion coefficients have
+/// the wrong number of elements.
+pub fn from_opencv_yaml(yaml: &str) -> Result<Rs2Intrinsics, CalibrationError> {
+    // OpenCV writes a non-standard YAML directive and type tags that YAML parsers reject, and
+    // neither carries any information we need.
+    let yaml = yaml
+        .lines()
+        .filter(|line| !line.starts_with('%'))
+        .map(|line| line.replace("!!opencv-matrix", ""))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let calibration: OpenCvCalibration =
+        serde_yaml::from_str(&yaml).map_err(|e| CalibrationError::CouldNotParse(e.to_string()))?;
+
+    let distortion = distortion_from_model(
+        calibration.distortion_model.as_deref().unwrap_or(PLUMB_BOB),
+        &calibration.distortion_coefficients.data,
+    )?;
+    intrinsics_from_camera_matrix(
+        calibration.image_width,
+        calibration.image_height,
+        &calibration.camera_matrix.data,
+        distortion,
+    )
+}
+
+/// A single camera in a Kalibr camchain file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KalibrCamera {
+    /// The intrinsics of the camera.
+    pub intrinsics: Rs2Intrinsics,
+    /// The extrinsics from the previous camera in the chain to this camera.
+    ///
+    /// This is `None` for the first camera in the chain.
+    pub from_previous: Option<Rs2Extrinsics>,
+    /// The ROS topic the camera's images were recorded on, if known.
+    pub rostopic: Option<String>,
+}
+
+/// A single camera in a Kalibr camchain YAML file.
+#[derive(Serialize, Deserialize)]
+struct KalibrCameraDef {
+    #[serde(rename = "T_cn_cnm1", default, skip_serializing_if = "Option::is_none")]
+    from_previous: Option<[[f32; 4]; 4]>,
+    camera_model: String,
+    intrinsics: Vec<f32>,
+    distortion_model: String,
+    distortion_coeffs: Vec<f32>,
+    resolution: [usize; 2],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rostopic: Option<String>,
+}
+
+impl KalibrCameraDef {
+    /// Convert a camera in the chain into its Kalibr representation.
+    fn from_camera(camera: &KalibrCamera) -> Result<Self, CalibrationError> {
+        let intrinsics = &camera.intrinsics;
+        let distortion = intrinsics.distortion();
+        let (distortion_model, distortion_coeffs) = match distortion.model {
+            Rs2DistortionModel::None => ("none", vec![]),
+            // Kalibr's radtan model has no k3 term.
+            Rs2DistortionModel::BrownConrady if distortion.coeffs[4] == 0.0 => {
+                ("radtan", distortion.coeffs[..4].to_vec())
+            }
+            Rs2DistortionModel::KannalaBrandt => (EQUIDISTANT, distortion.coeffs[..4].to_vec()),
+            model => return Err(CalibrationError::UnsupportedDistortion(model, "Kalibr")),
+        };
+
+        Ok(KalibrCameraDef {
+            from_previous: camera.from_previous.as_ref().map(Rs2Extrinsics::to_matrix),
+            camera_model: "pinhole".to_string(),
+            intrinsics: vec![
+                intrinsics.fx(),
+                intrinsics.fy(),
+                intrinsics.ppx(),
+                intrinsics.ppy(),
+            ],
+            distortion_model: distortion_model.to_string(),
+            distortion_coeffs,
+            resolution: [intrinsics.width(), intrinsics.height()],
+            rostopic: camera.rostopic.clone(),
+        })
+    }
+
+    /// Convert the Kalibr representation of a camera into a camera in the chain.
+    fn into_camera(self) -> Result<KalibrCamera, CalibrationError> {
+        if self.camera_model != "pinhole" {
+            return Err(CalibrationError::UnsupportedModel(self.camera_model));
+        }
+        if self.intrinsics.len() != 4 {
+            return Err(CalibrationError::InvalidSize(
+                "intrinsics",
+                4,
+                self.intrinsics.len(),
+            ));
+        }
+
+        let model = match self.distortion_model.as_str() {
+            "none" => Rs2DistortionModel::None,
+            "radtan" => Rs2DistortionModel::BrownConrady,
+            EQUIDISTANT => Rs2DistortionModel::KannalaBrandt,
+            other => return Err(CalibrationError::UnsupportedModel(other.to_string())),
+        };
+        let expected = if model == Rs2DistortionModel::None {
+            0
+        } else {
+            4
+        };
+        if self.distortion_coeffs.len() != expected {
+            return Err(CalibrationError::InvalidSize(
+                "distortion_coeffs",
+                expected,
+                self.distortion_coeffs.len(),
+            ));
+        }
+        let mut coeffs = [0.0; 5];
+        coeffs[..expected].copy_from_slice(&self.distortion_coeffs);
+
+        let from_previous = self.from_previous.map(|m| {
+            Rs2Extrinsics::new(
+                [
+                    m[0][0], m[1][0], m[2][0], m[0][1], m[1][1], m[2][1], m[0][2], m[1][2], m[2][2],
+                ],
+                [m[0][3], m[1][3], m[2][3]],
+            )
+        });
+
+        let [fx, fy, ppx, ppy] = [
+            self.intrinsics[0],
+            self.intrinsics[1],
+            self.intrinsics[2],
+            self.intrinsics[3],
+        ];
+        Ok(KalibrCamera {
+            intrinsics: Rs2Intrinsics::new(
+                self.resolution[0],
+                self.resolution[1],
+                [ppx, ppy],
+                [fx, fy],
+                Rs2Distortion { model, coeffs },
+            ),
+            from_previous,
+            rostopic: self.rostopic,
+        })
+    }
+}
+
+/// Write a chain of cameras as a Kalibr camchain YAML file.
+///
+/// Cameras are written as `cam0`, `cam1`, etc. in the order given.
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::UnsupportedDistortion`] if any camera does not use the
+/// [`None`](Rs2DistortionModel::None), [`BrownConrady`](Rs2DistortionModel::BrownConrady) or
+/// [`KannalaBrandt`](Rs2DistortionModel::KannalaBrandt) distortion models. Kalibr's `radtan`
+/// model has no `k3` coefficient, so Brown-Conrady distortion is only supported if `k3` is zero.
+///
+/// Returns [`CalibrationError::CouldNotWrite`] if the YAML cannot be generated.
+pub fn to_kalibr_camchain(cameras: &[KalibrCamera]) -> Result<String, CalibrationError> {
+    let mut chain = serde_yaml::Mapping::new();
+    for (index, camera) in cameras.iter().enumerate() {
+        let def = serde_yaml::to_value(KalibrCameraDef::from_camera(camera)?)
+            .map_err(|e| CalibrationError::CouldNotWrite(e.to_string()))?;
+        chain.insert(format!("cam{}", index).into(), def);
+    }
+
+    serde_yaml::to_string(&chain).map_err(|e| CalibrationError::CouldNotWrite(e.to_string()))
+}
+
+/// Read a chain of cameras from a Kalibr camchain YAML file.
+///
+/// Cameras are returned in the order of their `camN` keys. Only pinhole cameras with the `none`,
+/// `radtan` or `equidistant` distortion models are supported. Fields that have no RealSense
+/// equivalent (such as `cam_overlaps` or IMU extrinsics) are ignored.
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::CouldNotParse`] if the YAML is not a valid camchain file.
+///
+/// Returns [`CalibrationError::UnsupportedModel`] if any camera uses an unsupported camera or
+/// distortion model.
+///
+/// Returns [`CalibrationError::InvalidSize`] if the intrinsics or distortion coefficients of any
+/// camera have the wrong number of elements.
+pub fn from_kalibr_camchain(yaml: &str) -> Result<Vec<KalibrCamera>, CalibrationError> {
+    let chain: BTreeMap<String, KalibrCameraDef> =
+        serde_yaml::from_str(yaml).map_err(|e| CalibrationError::CouldNotParse(e.to_string()))?;
+
+    let mut cameras = Vec::with_capacity(chain.len());
+    for (key, def) in chain {
+        let index = key
+            .strip_prefix("cam")
+            .and_then(|index| index.parse::<usize>().ok())
+            .ok_or_else(|| {
+                CalibrationError::CouldNotParse(format!("Unexpected camera key: {}", key))
+            })?;
+        cameras.push((index, def.into_camera()?));
+    }
+
+    // Keys sort lexicographically, so `cam10` would otherwise come before `cam2`.
+    cameras.sort_by_key(|(index, _)| *index);
+    Ok(cameras.into_iter().map(|(_, camera)| camera).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intrinsics(model: Rs2DistortionModel, coeffs: [f32; 5]) -> Rs2Intrinsics {
+        Rs2Intrinsics::new(
+            640,
+            480,
+            [321.5, 242.25],
+            [383.7, 384.1],
+            Rs2Distortion { model, coeffs },
+        )
+    }
+
+    #[test]
+    fn serde_round_trips() {
+        let intrinsics = intrinsics(
+            Rs2DistortionModel::BrownConradyInverse,
+            [0.1, -0.2, 0.001, 0.002, 0.05],
+        );
+        let yaml = serde_yaml::to_string(&intrinsics).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Rs2Intrinsics>(&yaml).unwrap(),
+            intrinsics
+        );
+
+        let extrinsics = Rs2Extrinsics::new(
+            [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+            [0.015, 0.0, -0.002],
+        );
+        let yaml = serde_yaml::to_string(&extrinsics).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Rs2Extrinsics>(&yaml).unwrap(),
+            extrinsics
+        );
+
+        let motion = Rs2MotionDeviceIntrinsics(realsense_sys::rs2_motion_device_intrinsic {
+            data: [
+                [1.0, 0.0, 0.0, 0.01],
+                [0.0, 1.0, 0.0, -0.02],
+                [0.0, 0.0, 1.0, 0.03],
+            ],
+            noise_variances: [1e-4, 2e-4, 3e-4],
+            bias_variances: [1e-6, 2e-6, 3e-6],
+        });
+        let yaml = serde_yaml::to_string(&motion).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Rs2MotionDeviceIntrinsics>(&yaml).unwrap(),
+            motion
+        );
+    }
+
+    #[test]
+    fn reads_ros_camera_info() {
+        let yaml = "\
+image_width: 640
+image_height: 480
+camera_name: narrow_stereo
+camera_matrix:
+  rows: 3
+  cols: 3
+  data: [383.7, 0, 321.5, 0, 384.1, 242.25, 0, 0, 1]
+distortion_model: plumb_bob
+distortion_coefficients:
+  rows: 1
+  cols: 5
+  data: [0.1, -0.2, 0.001, 0.002, 0]
+rectification_matrix:
+  rows: 3
+  cols: 3
+  data: [1, 0, 0, 0, 1, 0, 0, 0, 1]
+projection_matrix:
+  rows: 3
+  cols: 4
+  data: [383.7, 0, 321.5, 0, 0, 384.1, 242.25, 0, 0, 0, 1, 0]
+";
+        assert_eq!(
+            from_ros_camera_info(yaml).unwrap(),
+            intrinsics(
+                Rs2DistortionModel::BrownConrady,
+                [0.1, -0.2, 0.001, 0.002, 0.0]
+            )
+        );
+    }
+
+    #[test]
+    fn ros_camera_info_round_trips() {
+        for intrinsics in [
+            intrinsics(Rs2DistortionModel::None, [0.0; 5]),
+            intrinsics(
+                Rs2DistortionModel::BrownConrady,
+                [0.1, -0.2, 0.001, 0.002, 0.05],
+            ),
+            intrinsics(
+                Rs2DistortionModel::KannalaBrandt,
+                [-0.0066, 0.0432, -0.0409, 0.0077, 0.0],
+            ),
+        ]
+        .iter()
+        {
+            let yaml = to_ros_camera_info(intrinsics, "camera").unwrap();
+            assert_eq!(&from_ros_camera_info(&yaml).unwrap(), intrinsics);
+        }
+
+        assert!(matches!(
+            to_ros_camera_info(
+                &intrinsics(Rs2DistortionModel::BrownConradyModified, [0.0; 5]),
+                "camera"
+            ),
+            Err(CalibrationError::UnsupportedDistortion(..))
+        ));
+    }
+
+    #[test]
+    fn reads_opencv_file_storage() {
+        let yaml = "\
+%YAML:1.0
+---
+image_width: 640
+image_height: 480
+camera_matrix: !!opencv-matrix
+   rows: 3
+   cols: 3
+   dt: d
+   data: [ 3.8370e+02, 0., 3.2150e+02, 0., 3.8410e+02, 2.4225e+02, 0., 0., 1. ]
+distortion_coefficients: !!opencv-matrix
+   rows: 1
+   cols: 4
+   dt: d
+   data: [ 1.0e-01, -2.0e-01, 1.0e-03, 2.0e-03 ]
+";
+        assert_eq!(
+            from_opencv_yaml(yaml).unwrap(),
+            intrinsics(
+                Rs2DistortionModel::BrownConrady,
+                [0.1, -0.2, 0.001, 0.002, 0.0]
+            )
+        );
+
+        let fisheye = intrinsics(
+            Rs2DistortionModel::KannalaBrandt,
+            [-0.0066, 0.0432, -0.0409, 0.0077, 0.0],
+        );
+        let yaml = to_opencv_yaml(&fisheye).unwrap();
+        assert!(yaml.starts_with("%YAML:1.0\n"));
+        assert_eq!(from_opencv_yaml(&yaml).unwrap(), fisheye);
+    }
+
+    #[test]
+    fn reads_kalibr_camchain() {
+        let yaml = "\
+cam0:
+  cam_overlaps: [1]
+  camera_model: pinhole
+  distortion_coeffs: [0.1, -0.2, 0.001, 0.002]
+  distortion_model: radtan
+  intrinsics: [383.7, 384.1, 321.5, 242.25]
+  resolution: [640, 480]
+  rostopic: /camera/infra1/image_rect_raw
+cam1:
+  T_cn_cnm1:
+  - [1.0, 0.0, 0.0, -0.05]
+  - [0.0, 1.0, 0.0, 0.0]
+  - [0.0, 0.0, 1.0, 0.0]
+  - [0.0, 0.0, 0.0, 1.0]
+  cam_overlaps: [0]
+  camera_model: pinhole
+  distortion_coeffs: []
+  distortion_model: none
+  intrinsics: [383.7, 384.1, 321.5, 242.25]
+  resolution: [640, 480]
+  rostopic: /camera/infra2/image_rect_raw
+";
+        let cameras = from_kalibr_camchain(yaml).unwrap();
+        assert_eq!(cameras.len(), 2);
+        assert_eq!(
+            cameras[0].intrinsics,
+            intrinsics(
+                Rs2DistortionModel::BrownConrady,
+                [0.1, -0.2, 0.001, 0.002, 0.0]
+            )
+        );
+        assert_eq!(cameras[0].from_previous, None);
+        assert_eq!(
+            cameras[1].from_previous,
+            Some(Rs2Extrinsics::new(
+                Rs2Extrinsics::identity().rotation(),
+                [-0.05, 0.0, 0.0]
+            ))
+        );
+        assert_eq!(
+            cameras[1].rostopic.as_deref(),
+            Some("/camera/infra2/image_rect_raw")
+        );
+
+        let yaml = to_kalibr_camchain(&cameras).unwrap();
+        assert_eq!(from_kalibr_camchain(&yaml).unwrap(), cameras);
+    }
+}
@@ -1,20 +1,61 @@
 //! Defines the frame type including sensor data.
 
+mod cloud;
 mod composite;
+mod confidence;
+mod correspondence;
+mod deprojection;
+mod disparity;
+mod export;
+mod fusion;
 mod image;
+mod imu;
+mod imu_calibration;
+#[cfg(feature = "jpeg")]
+mod mjpeg;
 mod motion;
+mod orientation;
 mod pixel;
 mod points;
 mod pose;
 mod prelude;
+mod registration;
+mod sampling;
+mod statistics;
+mod trajectory;
+mod tsdf;
+mod undistortion;
 
 pub use self::image::{
     ColorFrame, ConfidenceFrame, DepthFrame, DisparityFrame, FisheyeFrame, ImageFrame,
     InfraredFrame,
 };
+#[cfg(feature = "jpeg")]
+pub use self::mjpeg::{DecodedColorFrame, MjpegDecodeError};
 pub use self::motion::{AccelFrame, GyroFrame, MotionFrame};
 pub use self::points::PointsFrame;
+pub use cloud::{AxisAlignedBox, CloudError, CropRegion, OrientedBox, UnorganizedPointCloud};
 pub use composite::CompositeFrame;
+pub use confidence::ConfidenceLevel;
+pub use correspondence::ColorDepthCorrespondence;
+pub use deprojection::{DeprojectionTable, OrganizedPointCloud};
+pub use disparity::{DepthImage, DisparityImage, StereoParameters};
+pub use export::{ExportError, PcdFormat, PlyFormat, PlyOptions};
+pub use fusion::{CloudFusion, FusedPointCloud, FusionError};
+pub use imu::{ImuGap, ImuInterpolation, ImuSample, ImuStream};
+pub use imu_calibration::{
+    CalibrationPose, ImuCalibration, ImuCalibrationError, ImuCalibrator, STANDARD_GRAVITY,
+};
+pub use orientation::{
+    gravity_alignment, ComplementaryFilter, MadgwickFilter, OrientationEstimator,
+    OrientationFilter, D400_UP, T265_UP,
+};
 pub use pixel::PixelKind;
 pub use pose::{Confidence, PoseFrame};
-pub use prelude::{FrameCategory, FrameConstructionError, FrameEx};
+pub use prelude::{DepthError, DisparityError, FrameCategory, FrameConstructionError, FrameEx};
+pub use registration::RegisteredImage;
+pub use sampling::{DepthSample, DepthSampling};
+pub use statistics::{DepthHistogram, DepthStatistics, HistogramError};
+pub use trajectory::{Pose, PoseBuffer};
+pub use tsdf::{TriangleMesh, TsdfError, TsdfVolume};
+pub use undistortion::{Interpolate, StereoRectification, UndistortError, UndistortionMap};
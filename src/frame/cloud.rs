@@ -0,0 +1,596 @@
+//! Types for processing unorganized point clouds.
+//!
+//! These are the usual first steps after calculating a point cloud: cropping to a region of
+//! interest, downsampling to a voxel grid, removing outliers, and moving the cloud into another
+//! coordinate frame. Every operation returns a new cloud and keeps colors attached to their
+//! points.
+//!
+//! Clouds can be built from a [`PointsFrame`](crate::frame::PointsFrame) with
+//! [`PointsFrame::to_cloud`](crate::frame::PointsFrame::to_cloud), from an
+//! [`OrganizedPointCloud`], or directly from points.
+
+use super::deprojection::OrganizedPointCloud;
+use crate::base::Rs2Extrinsics;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Occurs when a point cloud operation is given an invalid parameter.
+#[derive(Error, Debug)]
+pub enum CloudError {
+    /// The voxel size is not positive and finite.
+    #[error("Voxel size must be positive and finite, got {0}.")]
+    InvalidVoxelSize(f32),
+    /// The neighbour search radius is negative or NaN.
+    #[error("Neighbour search radius must not be negative or NaN, got {0}.")]
+    InvalidRadius(f32),
+}
+
+/// A region of space that a point cloud can be cropped to.
+pub trait CropRegion {
+    /// Whether or not a point lies inside the region.
+    fn contains(&self, point: [f32; 3]) -> bool;
+}
+
+/// A box aligned with the axes of the point cloud's coordinate frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisAlignedBox {
+    /// The corner of the box with the smallest coordinates, in meters.
+    pub min: [f32; 3],
+    /// The corner of the box with the largest coordinates, in meters.
+    pub max: [f32; 3],
+}
+
+impl AxisAlignedBox {
+    /// Construct a box from two opposite corners, in meters.
+    pub fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        AxisAlignedBox { min, max }
+    }
+}
+
+impl CropRegion for AxisAlignedBox {
+    fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+}
+
+/// A box with an arbitrary position and orientation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientedBox {
+    /// The extrinsics from the coordinate frame of the box (centered on the box, with axes along
+    /// its edges) to the coordinate frame of the point cloud.
+    box_to_cloud: Rs2Extrinsics,
+    /// The inverse of `box_to_cloud`.
+    cloud_to_box: Rs2Extrinsics,
+    /// Half the size of the box along each of its axes, in meters.
+    half_extents: [f32; 3],
+}
+
+impl OrientedBox {
+    /// Construct a box from its pose in the point cloud's coordinate frame and its size.
+    ///
+    /// `box_to_cloud` are the extrinsics from the coordinate frame of the box, which is centered
+    /// on the box with axes along its edges, to that of the point cloud. `size` is the length of
+    /// the box along each of its axes, in meters.
+    pub fn new(box_to_cloud: &Rs2Extrinsics, size: [f32; 3]) -> Self {
+        OrientedBox {
+            box_to_cloud: *box_to_cloud,
+            cloud_to_box: box_to_cloud.inverse(),
+            half_extents: [size[0] / 2.0, size[1] / 2.0, size[2] / 2.0],
+        }
+    }
+
+    /// Get the extrinsics from the coordinate frame of the box to that of the point cloud.
+    pub fn box_to_cloud(&self) -> &Rs2Extrinsics {
+        &self.box_to_cloud
+    }
+
+    /// Get the length of the box along each of its axes, in meters.
+    pub fn size(&self) -> [f32; 3] {
+        [
+            self.half_extents[0] * 2.0,
+            self.half_extents[1] * 2.0,
+            self.half_extents[2] * 2.0,
+        ]
+    }
+}
+
+impl CropRegion for OrientedBox {
+    fn contains(&self, point: [f32; 3]) -> bool {
+        let local = self.cloud_to_box.transform_point(point);
+        (0..3).all(|axis| local[axis].abs() <= self.half_extents[axis])
+    }
+}
+
+/// An owned point cloud without any grid structure, with optional per-point colors.
+///
+/// Points are in meters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnorganizedPointCloud {
+    /// The points of the cloud.
+    pub(crate) points: Vec<[f32; 3]>,
+    /// The color of each point, if the cloud is colored.
+    pub(crate) colors: Option<Vec<[u8; 3]>>,
+}
+
+impl UnorganizedPointCloud {
+    /// Construct a cloud from points, leaving out points that are not finite.
+    pub fn new(points: Vec<[f32; 3]>) -> Self {
+        let points = points.into_iter().filter(is_finite).collect();
+        UnorganizedPointCloud {
+            points,
+            colors: None,
+        }
+    }
+
+    /// Construct a colored cloud from points and their colors, leaving out points that are not
+    /// finite.
+    ///
+    /// Returns `None` if there is not exactly one color per point.
+    pub fn with_colors(points: Vec<[f32; 3]>, colors: Vec<[u8; 3]>) -> Option<Self> {
+        if points.len() != colors.len() {
+            return None;
+        }
+
+        let (points, colors) = points
+            .into_iter()
+            .zip(colors)
+            .filter(|(p, _)| is_finite(p))
+            .unzip();
+        Some(UnorganizedPointCloud {
+            points,
+            colors: Some(colors),
+        })
+    }
+
+    /// Get the points of the cloud.
+    pub fn points(&self) -> &[[f32; 3]] {
+        &self.points
+    }
+
+    /// Get the color of each point, if the cloud is colored.
+    pub fn colors(&self) -> Option<&[[u8; 3]]> {
+        self.colors.as_deref()
+    }
+
+    /// Take ownership of the points and colors of the cloud.
+    pub fn into_parts(self) -> (Vec<[f32; 3]>, Option<Vec<[u8; 3]>>) {
+        (self.points, self.colors)
+    }
+
+    /// Get the number of points in the cloud.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Whether or not the cloud has no points.
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Move the cloud into another coordinate frame.
+    pub fn transform(&self, extrinsics: &Rs2Extrinsics) -> Self {
+        UnorganizedPointCloud {
+            points: self
+                .points
+                .iter()
+                .map(|p| extrinsics.transform_point(*p))
+                .collect(),
+            colors: self.colors.clone(),
+        }
+    }
+
+    /// Keep only the points inside a region, such as an [`AxisAlignedBox`] or an
+    /// [`OrientedBox`].
+    pub fn crop<R: CropRegion>(&self, region: &R) -> Self {
+        self.select(|i| region.contains(self.points[i]))
+    }
+
+    /// Downsample the cloud to at most one point per cubic voxel of `voxel_size` meters.
+    ///
+    /// Each voxel is replaced by the centroid (and average color) of the points inside it. Voxels
+    /// are ordered by the first point that falls inside them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CloudError::InvalidVoxelSize`] if `voxel_size` is not positive and finite.
+    pub fn voxel_downsample(&self, voxel_size: f32) -> Result<Self, CloudError> {
+        if !(voxel_size > 0.0 && voxel_size.is_finite()) {
+            return Err(CloudError::InvalidVoxelSize(voxel_size));
+        }
+
+        // Sums of the positions and colors of each voxel, and the number of points in it.
+        let mut voxels: Vec<([f64; 3], [u32; 3], u32)> = Vec::new();
+        let mut indices: HashMap<[i64; 3], usize> = HashMap::new();
+
+        for (i, point) in self.points.iter().enumerate() {
+            let index = *indices
+                .entry(voxel_index(point, voxel_size))
+                .or_insert_with(|| {
+                    voxels.push(([0.0; 3], [0; 3], 0));
+                    voxels.len() - 1
+                });
+
+            let (sum, color_sum, count) = &mut voxels[index];
+            for axis in 0..3 {
+                sum[axis] += point[axis] as f64;
+            }
+            if let Some(colors) = &self.colors {
+                for channel in 0..3 {
+                    color_sum[channel] += colors[i][channel] as u32;
+                }
+            }
+            *count += 1;
+        }
+
+        let points = voxels
+            .iter()
+            .map(|(sum, _, count)| {
+                let n = *count as f64;
+                [
+                    (sum[0] / n) as f32,
+                    (sum[1] / n) as f32,
+                    (sum[2] / n) as f32,
+                ]
+            })
+            .collect();
+        let colors = self.colors.as_ref().map(|_| {
+            voxels
+                .iter()
+                .map(|(_, sum, count)| {
+                    [
+                        ((sum[0] + count / 2) / count) as u8,
+                        ((sum[1] + count / 2) / count) as u8,
+                        ((sum[2] + count / 2) / count) as u8,
+                    ]
+                })
+                .collect()
+        });
+
+        Ok(UnorganizedPointCloud { points, colors })
+    }
+
+    /// Remove points with fewer than `min_neighbours` other points within `radius` meters.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CloudError::InvalidRadius`] if `radius` is negative or NaN.
+    pub fn remove_radius_outliers(
+        &self,
+        radius: f32,
+        min_neighbours: usize,
+    ) -> Result<Self, CloudError> {
+        if radius.is_nan() || radius < 0.0 {
+            return Err(CloudError::InvalidRadius(radius));
+        }
+
+        let tree = KdTree::new(&self.points);
+        let radius_squared = radius * radius;
+        Ok(self.select(|i| {
+            // The point itself is always within the radius.
+            tree.count_within(
+                &self.points,
+                self.points[i],
+                radius_squared,
+                min_neighbours + 1,
+            ) > min_neighbours
+        }))
+    }
+
+    /// Remove points whose mean distance to their `k` nearest neighbours is more than
+    /// `std_ratio` standard deviations above the mean over the whole cloud.
+    ///
+    /// This is the statistical outlier removal filter of PCL and Open3D.
+    pub fn remove_statistical_outliers(&self, k: usize, std_ratio: f32) -> Self {
+        if self.points.len() <= 1 || k == 0 {
+            return self.clone();
+        }
+
+        let tree = KdTree::new(&self.points);
+        let mean_distances: Vec<f64> = self
+            .points
+            .iter()
+            .map(|point| {
+                // The nearest neighbour of every point is itself, at a distance of zero.
+                let neighbours = tree.nearest(&self.points, *point, k + 1);
+                let total: f64 = neighbours.iter().map(|d| (*d as f64).sqrt()).sum();
+                total / (neighbours.len() - 1) as f64
+            })
+            .collect();
+
+        let n = mean_distances.len() as f64;
+        let mean = mean_distances.iter().sum::<f64>() / n;
+        let variance = mean_distances
+            .iter()
+            .map(|d| (d - mean) * (d - mean))
+            .sum::<f64>()
+            / (n - 1.0);
+        let threshold = mean + std_ratio as f64 * variance.sqrt();
+
+        self.select(|i| mean_distances[i] <= threshold)
+    }
+
+    /// Keep only the points (and colors) for which `keep` returns true.
+    fn select<F: Fn(usize) -> bool>(&self, keep: F) -> Self {
+        let kept: Vec<usize> = (0..self.points.len()).filter(|&i| keep(i)).collect();
+        UnorganizedPointCloud {
+            points: kept.iter().map(|&i| self.points[i]).collect(),
+            colors: self
+                .colors
+                .as_ref()
+                .map(|colors| kept.iter().map(|&i| colors[i]).collect()),
+        }
+    }
+}
+
+impl From<OrganizedPointCloud> for UnorganizedPointCloud {
+    /// Take the valid points of an organized cloud, dropping its grid structure.
+    fn from(cloud: OrganizedPointCloud) -> Self {
+        UnorganizedPointCloud::new(cloud.points)
+    }
+}
+
+/// Whether every coordinate of a point is finite.
+#[inline]
+fn is_finite(point: &[f32; 3]) -> bool {
+    point.iter().all(|c| c.is_finite())
+}
+
+/// The index of the voxel a point falls in.
+#[inline]
+fn voxel_index(point: &[f32; 3], voxel_size: f32) -> [i64; 3] {
+    [
+        (point[0] / voxel_size).floor() as i64,
+        (point[1] / voxel_size).floor() as i64,
+        (point[2] / voxel_size).floor() as i64,
+    ]
+}
+
+/// The squared distance between two points.
+#[inline]
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3)
+        .map(|axis| (a[axis] - b[axis]) * (a[axis] - b[axis]))
+        .sum()
+}
+
+/// A balanced k-d tree over the indices of a set of points, for neighbour searches.
+///
+/// The tree is stored implicitly: the median of each range of `indices` splits it along the axis
+/// of its depth in the tree.
+struct KdTree {
+    /// Point indices, ordered so that each range is split by its median.
+    indices: Vec<usize>,
+}
+
+impl KdTree {
+    /// Build a tree over every point.
+    fn new(points: &[[f32; 3]]) -> Self {
+        let mut indices: Vec<usize> = (0..points.len()).collect();
+        Self::build(points, &mut indices, 0);
+        KdTree { indices }
+    }
+
+    fn build(points: &[[f32; 3]], indices: &mut [usize], depth: usize) {
+        if indices.len() <= 1 {
+            return;
+        }
+
+        let axis = depth % 3;
+        let median = indices.len() / 2;
+        indices
+            .select_nth_unstable_by(median, |&a, &b| points[a][axis].total_cmp(&points[b][axis]));
+
+        let (left, right) = indices.split_at_mut(median);
+        Self::build(points, left, depth + 1);
+        Self::build(points, &mut right[1..], depth + 1);
+    }
+
+    /// Count the points within a squared distance of `query`, stopping early at `limit`.
+    fn count_within(
+        &self,
+        points: &[[f32; 3]],
+        query: [f32; 3],
+        radius_squared: f32,
+        limit: usize,
+    ) -> usize {
+        let mut count = 0;
+        let mut stack = vec![(0, self.indices.len(), 0)];
+        while let Some((start, end, depth)) = stack.pop() {
+            if start >= end || count >= limit {
+                continue;
+            }
+
+            let median = start + (end - start) / 2;
+            let point = points[self.indices[median]];
+            if distance_squared(point, query) <= radius_squared {
+                count += 1;
+            }
+
+            let axis = depth % 3;
+            let offset = query[axis] - point[axis];
+            if offset <= 0.0 || offset * offset <= radius_squared {
+                stack.push((start, median, depth + 1));
+            }
+            if offset >= 0.0 || offset * offset <= radius_squared {
+                stack.push((median + 1, end, depth + 1));
+            }
+        }
+        count.min(limit)
+    }
+
+    /// Find the squared distances to the `k` nearest points to `query`, in increasing order.
+    fn nearest(&self, points: &[[f32; 3]], query: [f32; 3], k: usize) -> Vec<f32> {
+        let mut nearest: Vec<f32> = Vec::with_capacity(k + 1);
+        self.search(points, query, k, 0, self.indices.len(), 0, &mut nearest);
+        nearest
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        points: &[[f32; 3]],
+        query: [f32; 3],
+        k: usize,
+        start: usize,
+        end: usize,
+        depth: usize,
+        nearest: &mut Vec<f32>,
+    ) {
+        if start >= end {
+            return;
+        }
+
+        let median = start + (end - start) / 2;
+        let point = points[self.indices[median]];
+        let distance = distance_squared(point, query);
+        if nearest.len() < k || distance < nearest[nearest.len() - 1] {
+            let position = nearest.partition_point(|d| *d <= distance);
+            nearest.insert(position, distance);
+            nearest.truncate(k);
+        }
+
+        // Search the side of the split containing the query first, then the other side only if
+        // it could hold a closer point.
+        let axis = depth % 3;
+        let offset = query[axis] - point[axis];
+        let (near, far) = if offset <= 0.0 {
+            ((start, median), (median + 1, end))
+        } else {
+            ((median + 1, end), (start, median))
+        };
+
+        self.search(points, query, k, near.0, near.1, depth + 1, nearest);
+        if nearest.len() < k || offset * offset < nearest[nearest.len() - 1] {
+            self.search(points, query, k, far.0, far.1, depth + 1, nearest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5x5x5 lattice of points 1cm apart, with one far away outlier.
+    fn lattice() -> UnorganizedPointCloud {
+        let mut points = Vec::new();
+        for x in 0..5 {
+            for y in 0..5 {
+                for z in 0..5 {
+                    points.push([x as f32 * 0.01, y as f32 * 0.01, 1.0 + z as f32 * 0.01]);
+                }
+            }
+        }
+        points.push([0.5, 0.5, 2.0]);
+        let colors = (0..points.len()).map(|i| [i as u8, 0, 255]).collect();
+        UnorganizedPointCloud::with_colors(points, colors).unwrap()
+    }
+
+    #[test]
+    fn drops_points_that_are_not_finite() {
+        let cloud = UnorganizedPointCloud::new(vec![[0.0, 0.0, 1.0], [f32::NAN; 3]]);
+        assert_eq!(cloud.len(), 1);
+        assert!(UnorganizedPointCloud::with_colors(vec![[0.0; 3]], vec![]).is_none());
+    }
+
+    #[test]
+    fn crops_to_boxes() {
+        let cloud = lattice();
+
+        let aligned = cloud.crop(&AxisAlignedBox::new([-1.0, -1.0, 0.0], [0.015, 0.015, 1.5]));
+        assert_eq!(aligned.len(), 2 * 2 * 5);
+        assert_eq!(aligned.colors().unwrap().len(), aligned.len());
+
+        // A box rotated 90 degrees about Z, long along its own X axis (i.e. the cloud's Y axis).
+        let pose = Rs2Extrinsics::new(
+            [0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+            [0.0, 0.02, 1.02],
+        );
+        let oriented = cloud.crop(&OrientedBox::new(&pose, [0.1, 0.005, 0.1]));
+        assert_eq!(oriented.len(), 5 * 5);
+        assert!(oriented.points().iter().all(|p| p[0].abs() < 1e-6));
+    }
+
+    #[test]
+    fn downsamples_to_voxel_centroids() {
+        let cloud = lattice().voxel_downsample(0.025).unwrap();
+
+        // Voxels of 2.5cm hold 3, 2 and 0 of every 5 lattice coordinates along each axis, so the
+        // lattice covers 2x2x2 voxels (plus the outlier).
+        assert_eq!(cloud.len(), 2 * 2 * 2 + 1);
+        let first = cloud.points()[0];
+        assert!((first[0] - 0.01).abs() < 1e-6);
+        assert!((first[2] - 1.01).abs() < 1e-6);
+        assert_eq!(cloud.colors().unwrap()[0][2], 255);
+    }
+
+    #[test]
+    fn rejects_invalid_voxel_sizes() {
+        for &size in [0.0, -0.01, f32::NAN, f32::INFINITY].iter() {
+            assert!(matches!(
+                lattice().voxel_downsample(size),
+                Err(CloudError::InvalidVoxelSize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn removes_radius_outliers() {
+        let cloud = lattice().remove_radius_outliers(0.011, 3).unwrap();
+
+        // Corners of the lattice have exactly three neighbours within 1.1cm.
+        assert_eq!(cloud.len(), 125);
+        assert!(cloud.points().iter().all(|p| p[2] < 1.5));
+        assert_eq!(
+            lattice().remove_radius_outliers(0.011, 4).unwrap().len(),
+            125 - 8
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_radius() {
+        for &radius in [-0.01, f32::NAN].iter() {
+            assert!(matches!(
+                lattice().remove_radius_outliers(radius, 1),
+                Err(CloudError::InvalidRadius(_))
+            ));
+        }
+        assert_eq!(lattice().remove_radius_outliers(0.0, 0).unwrap().len(), 126);
+    }
+
+    #[test]
+    fn removes_statistical_outliers() {
+        let cloud = lattice().remove_statistical_outliers(6, 1.0);
+
+        assert!(cloud.len() >= 100);
+        assert!(cloud.points().iter().all(|p| p[2] < 1.5));
+    }
+
+    #[test]
+    fn finds_nearest_neighbours() {
+        let cloud = lattice();
+        let tree = KdTree::new(cloud.points());
+
+        // Brute force the nearest neighbours of a point in the middle of the lattice.
+        let query = [0.02, 0.021, 1.02];
+        let mut expected: Vec<f32> = cloud
+            .points()
+            .iter()
+            .map(|p| distance_squared(*p, query))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.truncate(10);
+
+        assert_eq!(tree.nearest(cloud.points(), query, 10), expected);
+    }
+
+    #[test]
+    fn transforms_points() {
+        let translation = Rs2Extrinsics::new(
+            [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            [1.0, 2.0, 3.0],
+        );
+        let cloud = lattice().transform(&translation);
+        assert_eq!(cloud.points()[0], [1.0, 2.0, 4.0]);
+        assert_eq!(cloud.colors(), lattice().colors());
+    }
+}
@@ -0,0 +1,181 @@
+//! Types for interpreting the data held in a confidence frame.
+//!
+//! Devices in the L500 series (e.g. the L515) can stream a per-pixel confidence alongside depth.
+//! Depending on the device configuration, this stream is delivered either as one 8-bit value per
+//! pixel, or as 4-bit values packed two pixels to a byte. Both representations are exposed through
+//! the common [`ConfidenceLevel`] type so that thresholds can be written once regardless of which
+//! representation is being streamed.
+
+use std::{os::raw::c_void, slice};
+
+/// The confidence of a single pixel, on an 8-bit scale.
+///
+/// 4-bit confidence values are shifted into the high nibble (i.e. `0xF` becomes `0xF0`), which is
+/// how librealsense2 delivers confidence on an 8-bit scale, so that levels taken from either
+/// representation can be compared directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConfidenceLevel(u8);
+
+impl ConfidenceLevel {
+    /// The lowest possible confidence.
+    pub const MIN: ConfidenceLevel = ConfidenceLevel(u8::MIN);
+    /// The highest possible confidence.
+    pub const MAX: ConfidenceLevel = ConfidenceLevel(u8::MAX);
+
+    /// Construct a confidence level from an 8-bit confidence value.
+    pub const fn new(value: u8) -> Self {
+        ConfidenceLevel(value)
+    }
+
+    /// Construct a confidence level from a 4-bit confidence value.
+    ///
+    /// Only the lower four bits of `value` are used.
+    pub const fn from_4bit(value: u8) -> Self {
+        ConfidenceLevel((value & 0x0F) << 4)
+    }
+
+    /// The confidence on an 8-bit scale.
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    /// The confidence on a 4-bit scale.
+    ///
+    /// This drops the low nibble of levels constructed from an 8-bit value.
+    pub fn as_4bit(&self) -> u8 {
+        self.0 >> 4
+    }
+
+    /// The confidence normalized to the range `[0, 1]`.
+    pub fn normalized(&self) -> f32 {
+        self.0 as f32 / u8::MAX as f32
+    }
+}
+
+/// Method to retrieve the confidence of a pixel from raw confidence frame data.
+///
+/// Frames with 4 bits per pixel are read as packed data, where the even column of each pair is
+/// held in the low nibble of a byte and the odd column in the high nibble. Any other pixel size is
+/// read as one 8-bit confidence value per pixel.
+///
+/// # Safety
+///
+/// This method should only be called from the ImageFrame types themselves (or on a slice of
+/// `data_size_in_bytes` bytes), as this is the only place where proper pointer management happens.
+#[inline]
+pub(crate) unsafe fn get_confidence(
+    data: *const c_void,
+    data_size_in_bytes: usize,
+    stride_in_bytes: usize,
+    bits_per_pixel: usize,
+    col: usize,
+    row: usize,
+) -> ConfidenceLevel {
+    let slice = slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes);
+
+    if bits_per_pixel == 4 {
+        let byte = *slice.get_unchecked((row * stride_in_bytes) + (col / 2));
+
+        if col % 2 == 1 {
+            ConfidenceLevel::from_4bit(byte >> 4)
+        } else {
+            ConfidenceLevel::from_4bit(byte)
+        }
+    } else {
+        ConfidenceLevel::new(*slice.get_unchecked((row * stride_in_bytes) + col))
+    }
+}
+
+/// Replace every depth whose confidence is below `threshold` with zero (i.e. invalid depth).
+///
+/// Depths and confidences are paired up in order; any depths left over once the confidences are
+/// exhausted are dropped.
+pub(crate) fn mask_depths<D, C>(depths: D, confidences: C, threshold: ConfidenceLevel) -> Vec<f32>
+where
+    D: IntoIterator<Item = f32>,
+    C: IntoIterator<Item = ConfidenceLevel>,
+{
+    depths
+        .into_iter()
+        .zip(confidences)
+        .map(|(depth, confidence)| if confidence >= threshold { depth } else { 0.0 })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read every confidence out of a synthetic frame buffer in row-major order.
+    fn read_all(
+        data: &[u8],
+        stride: usize,
+        bits_per_pixel: usize,
+        width: usize,
+        height: usize,
+    ) -> Vec<u8> {
+        let mut levels = Vec::new();
+        for row in 0..height {
+            for col in 0..width {
+                let level = unsafe {
+                    get_confidence(
+                        data.as_ptr().cast::<c_void>(),
+                        data.len(),
+                        stride,
+                        bits_per_pixel,
+                        col,
+                        row,
+                    )
+                };
+                levels.push(level.value());
+            }
+        }
+        levels
+    }
+
+    #[test]
+    fn shifts_4bit_levels() {
+        assert_eq!(ConfidenceLevel::from_4bit(0x0), ConfidenceLevel::MIN);
+        assert_eq!(ConfidenceLevel::from_4bit(0xF).value(), 0xF0);
+        assert_eq!(ConfidenceLevel::from_4bit(0xF8).value(), 0x80);
+        assert_eq!(ConfidenceLevel::from_4bit(0x7).as_4bit(), 0x7);
+        assert_eq!(ConfidenceLevel::from_4bit(0x8), ConfidenceLevel::new(0x80));
+        assert!(ConfidenceLevel::from_4bit(0x8) > ConfidenceLevel::new(0x7F));
+        assert_eq!(ConfidenceLevel::MAX.normalized(), 1.0);
+    }
+
+    #[test]
+    fn reads_8bit_confidence_frame() {
+        // A 3x2 frame with one byte of row padding.
+        let data = [0x00, 0x10, 0xFF, 0xAA, 0x20, 0x30, 0x40, 0xAA];
+
+        assert_eq!(
+            read_all(&data, 4, 8, 3, 2),
+            vec![0x00, 0x10, 0xFF, 0x20, 0x30, 0x40]
+        );
+    }
+
+    #[test]
+    fn reads_4bit_confidence_frame() {
+        // A 4x2 frame, two pixels per byte with the even column in the low nibble.
+        let data = [0x10, 0xF2, 0x54, 0x76];
+
+        assert_eq!(
+            read_all(&data, 2, 4, 4, 2),
+            vec![0x00, 0x10, 0x20, 0xF0, 0x40, 0x50, 0x60, 0x70]
+        );
+    }
+
+    #[test]
+    fn masks_low_confidence_depth() {
+        let depths = vec![1.0, 2.0, 3.0, 4.0];
+        let confidences = [0x0, 0x8, 0xF, 0x7]
+            .iter()
+            .map(|&c| ConfidenceLevel::from_4bit(c));
+
+        assert_eq!(
+            mask_depths(depths, confidences, ConfidenceLevel::from_4bit(0x8)),
+            vec![0.0, 2.0, 3.0, 0.0]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Type for finding the depth pixel that corresponds to a pixel in a color image.
+//!
+//! Mapping a color pixel back into a depth image is not a simple projection, since the depth at
+//! the color pixel is not known up front. Instead, the depth pixels that the color pixel could
+//! correspond to are walked along the epipolar line between the nearest and furthest depths of
+//! interest, and the depth pixel that projects closest to the color pixel is chosen. This is the
+//! approach taken by `rs2_project_color_pixel_to_depth_pixel` in librealsense2's `rsutil.h`.
+//!
+//! See [`DepthFrame::color_pixel_to_depth_pixel`](crate::frame::DepthFrame::color_pixel_to_depth_pixel)
+//! for how to search a frame.
+
+use super::statistics::is_valid_depth;
+use crate::base::{Rs2Extrinsics, Rs2Intrinsics};
+
+/// The calibration between a depth stream and a color stream needed to search for corresponding
+/// pixels.
+///
+/// This is cheap to copy, and can be reused across frames for as long as the stream
+/// configuration stays the same.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorDepthCorrespondence {
+    /// The intrinsics of the depth stream.
+    pub depth_intrinsics: Rs2Intrinsics,
+    /// The intrinsics of the color stream.
+    pub color_intrinsics: Rs2Intrinsics,
+    /// The extrinsics from the color stream to the depth stream.
+    pub color_to_depth: Rs2Extrinsics,
+    /// The extrinsics from the depth stream to the color stream.
+    pub depth_to_color: Rs2Extrinsics,
+}
+
+impl ColorDepthCorrespondence {
+    /// Construct a correspondence from the intrinsics of both streams and the extrinsics between
+    /// them in both directions.
+    pub fn new(
+        depth_intrinsics: Rs2Intrinsics,
+        color_intrinsics: Rs2Intrinsics,
+        color_to_depth: Rs2Extrinsics,
+        depth_to_color: Rs2Extrinsics,
+    ) -> Self {
+        ColorDepthCorrespondence {
+            depth_intrinsics,
+            color_intrinsics,
+            color_to_depth,
+            depth_to_color,
+        }
+    }
+
+    /// Find the depth pixel corresponding to the color pixel `pixel`, searching depths between
+    /// `min_depth` and `max_depth` meters.
+    ///
+    /// `depth_at` returns the depth (in meters) of the pixel at a given column and row of a
+    /// `width` x `height` depth image, and is only called with coordinates inside the image.
+    ///
+    /// Returns `None` if no depth pixel along the search line holds a valid depth.
+    pub(crate) fn depth_pixel<F>(
+        &self,
+        width: usize,
+        height: usize,
+        depth_at: F,
+        pixel: [f32; 2],
+        min_depth: f32,
+        max_depth: f32,
+    ) -> Option<[f32; 2]>
+    where
+        F: Fn(usize, usize) -> f32,
+    {
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        // Find where the color pixel lands in the depth image at either end of the depth range.
+        let line_end = |depth: f32| {
+            let point = self.color_intrinsics.deproject(pixel, depth);
+            let point = self.color_to_depth.transform_point(point);
+            let [x, y] = self.depth_intrinsics.project(point);
+            [
+                x.max(0.0).min((width - 1) as f32),
+                y.max(0.0).min((height - 1) as f32),
+            ]
+        };
+        let start = line_end(min_depth);
+        let end = line_end(max_depth);
+
+        if !start.iter().chain(end.iter()).all(|v| v.is_finite()) {
+            return None;
+        }
+
+        let mut best = None;
+        let mut min_distance = f32::INFINITY;
+
+        let mut current = start;
+        while is_pixel_in_line(current, start, end) {
+            let depth = depth_at(current[0] as usize, current[1] as usize);
+
+            if is_valid_depth(depth) {
+                let point = self.depth_intrinsics.deproject(current, depth);
+                let point = self.depth_to_color.transform_point(point);
+                let projected = self.color_intrinsics.project(point);
+
+                let dx = projected[0] - pixel[0];
+                let dy = projected[1] - pixel[1];
+                let distance = dx * dx + dy * dy;
+                if distance < min_distance {
+                    min_distance = distance;
+                    best = Some(current);
+                }
+            }
+
+            current = next_pixel_in_line(current, start, end);
+        }
+
+        best
+    }
+}
+
+/// Whether `current` lies within the bounding box of the line from `start` to `end`.
+fn is_pixel_in_line(current: [f32; 2], start: [f32; 2], end: [f32; 2]) -> bool {
+    (0..2).all(|i| {
+        let (low, high) = if start[i] <= end[i] {
+            (start[i], end[i])
+        } else {
+            (end[i], start[i])
+        };
+        low <= current[i] && current[i] <= high
+    })
+}
+
+/// Step one pixel from `current` along the line from `start` to `end`.
+///
+/// Steps are always taken along the axis in which the line is longest, and the other coordinate is
+/// placed back onto the line. Unlike `rsutil.h`, the step direction is fixed by the line rather
+/// than by the current position, so that the walk cannot oscillate around `end`.
+fn next_pixel_in_line(current: [f32; 2], start: [f32; 2], end: [f32; 2]) -> [f32; 2] {
+    let slope = (end[1] - start[1]) / (end[0] - start[0]);
+    let step = |axis: usize| {
+        if end[axis] >= start[axis] {
+            1.0
+        } else {
+            -1.0
+        }
+    };
+
+    if (end[0] - start[0]).abs() > (end[1] - start[1]).abs() {
+        let x = current[0] + step(0);
+        [x, start[1] + slope * (x - start[0])]
+    } else {
+        let y = current[1] + step(1);
+        [start[0] + (y - start[1]) / slope, y]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kind::Rs2DistortionModel;
+    use num_traits::ToPrimitive;
+    use realsense_sys as sys;
+
+    const WIDTH: usize = 40;
+    const HEIGHT: usize = 30;
+
+    fn intrinsics() -> Rs2Intrinsics {
+        Rs2Intrinsics(sys::rs2_intrinsics {
+            width: WIDTH as i32,
+            height: HEIGHT as i32,
+            ppx: 20.0,
+            ppy: 15.0,
+            fx: 100.0,
+            fy: 100.0,
+            model: Rs2DistortionModel::None.to_u32().unwrap(),
+            coeffs: [0.0; 5],
+        })
+    }
+
+    /// Color camera offset 5cm along the X axis from the depth camera.
+    fn correspondence() -> ColorDepthCorrespondence {
+        let depth_to_color =
+            Rs2Extrinsics::new(Rs2Extrinsics::identity().rotation(), [-0.05, 0.0, 0.0]);
+        ColorDepthCorrespondence::new(
+            intrinsics(),
+            intrinsics(),
+            depth_to_color.inverse(),
+            depth_to_color,
+        )
+    }
+
+    #[test]
+    fn finds_pixel_on_flat_wall() {
+        // At 1m, the 5cm baseline shifts pixels by 5 columns.
+        let pixel = correspondence().depth_pixel(WIDTH, HEIGHT, |_, _| 1.0, [25.0, 15.0], 0.5, 2.0);
+        assert_eq!(pixel, Some([30.0, 15.0]));
+
+        // At 2.5m the shift is 2 columns, and the far end of the search line is clamped to the
+        // edge of the image.
+        let pixel = correspondence().depth_pixel(WIDTH, HEIGHT, |_, _| 2.5, [10.0, 7.0], 0.5, 4.0);
+        assert_eq!(pixel, Some([12.0, 7.0]));
+    }
+
+    #[test]
+    fn ignores_invalid_depth() {
+        let pixel = correspondence().depth_pixel(WIDTH, HEIGHT, |_, _| 0.0, [25.0, 15.0], 0.5, 2.0);
+        assert_eq!(pixel, None);
+
+        // Only one pixel on the search line holds valid depth.
+        let depth_at = |col, _| if col == 28 { 1.0 } else { 0.0 };
+        let pixel = correspondence().depth_pixel(WIDTH, HEIGHT, depth_at, [25.0, 15.0], 0.5, 2.0);
+        assert_eq!(pixel, Some([28.0, 15.0]));
+    }
+
+    #[test]
+    fn steps_along_steep_lines() {
+        let start = [2.0, 1.0];
+        let end = [4.0, 9.0];
+
+        let mut current = start;
+        let mut steps = 0;
+        while is_pixel_in_line(current, start, end) {
+            assert!((current[0] - (2.0 + (current[1] - 1.0) / 4.0)).abs() < 1e-6);
+            current = next_pixel_in_line(current, start, end);
+            steps += 1;
+        }
+        assert_eq!(steps, 9);
+    }
+}
@@ -0,0 +1,396 @@
+//! Types for deprojecting an entire depth frame into an organized point cloud.
+//!
+//! Deprojecting a pixel through [`Rs2Intrinsics::deproject`] involves undistorting the pixel, which
+//! for most distortion models is an iterative process. Since the undistorted ray through each
+//! pixel only depends on the intrinsics, a [`DeprojectionTable`] computes these rays once so that
+//! deprojecting a frame is reduced to scaling each ray by the depth at its pixel.
+//!
+//! [`DepthFrame::to_points`](crate::frame::DepthFrame::to_points) keeps a small cache of tables so
+//! that the rays are only computed the first time a given set of intrinsics is seen.
+
+use super::export::{write_las, write_pcd, ExportError, PcdFormat};
+use super::statistics::is_valid_depth;
+use crate::base::Rs2Intrinsics;
+use std::{
+    io::Write,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The number of tables kept by [`cached_table`].
+///
+/// This is enough for every depth stream of a few devices streaming at once.
+const TABLE_CACHE_SIZE: usize = 8;
+
+/// The cache shared by every frame.
+static TABLE_CACHE: TableCache = TableCache::new(TABLE_CACHE_SIZE);
+
+/// Get a deprojection table for `intrinsics`, computing it only if it is not already cached.
+pub(crate) fn cached_table(intrinsics: &Rs2Intrinsics) -> Arc<DeprojectionTable> {
+    TABLE_CACHE.get(intrinsics)
+}
+
+/// A cache of the most recently used deprojection tables.
+pub(crate) struct TableCache {
+    /// The maximum number of tables kept.
+    capacity: usize,
+    /// Recently used tables, ordered from least to most recently used.
+    tables: Mutex<Vec<Arc<DeprojectionTable>>>,
+}
+
+impl TableCache {
+    /// Create an empty cache holding at most `capacity` tables.
+    pub(crate) const fn new(capacity: usize) -> Self {
+        TableCache {
+            capacity,
+            tables: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get a deprojection table for `intrinsics`, computing it only if it is not already cached.
+    ///
+    /// The table is computed without holding the lock, so that other threads can still get the
+    /// tables already in the cache. If two threads compute the same table at once, the table of
+    /// the first thread to finish is kept.
+    pub(crate) fn get(&self, intrinsics: &Rs2Intrinsics) -> Arc<DeprojectionTable> {
+        if let Some(table) = self.lookup(intrinsics) {
+            return table;
+        }
+
+        let computed = Arc::new(DeprojectionTable::new(intrinsics));
+
+        let mut tables = self.lock();
+        if let Some(table) = Self::touch(&mut tables, intrinsics) {
+            return table;
+        }
+        if tables.len() >= self.capacity {
+            tables.remove(0);
+        }
+        tables.push(Arc::clone(&computed));
+        computed
+    }
+
+    /// Get the cached table for `intrinsics`, if there is one.
+    fn lookup(&self, intrinsics: &Rs2Intrinsics) -> Option<Arc<DeprojectionTable>> {
+        Self::touch(&mut self.lock(), intrinsics)
+    }
+
+    /// Find the table for `intrinsics` and mark it as the most recently used.
+    fn touch(
+        tables: &mut Vec<Arc<DeprojectionTable>>,
+        intrinsics: &Rs2Intrinsics,
+    ) -> Option<Arc<DeprojectionTable>> {
+        let index = tables.iter().position(|table| table.matches(intrinsics))?;
+        let table = tables.remove(index);
+        tables.push(Arc::clone(&table));
+        Some(table)
+    }
+
+    /// Lock the tables.
+    fn lock(&self) -> MutexGuard<'_, Vec<Arc<DeprojectionTable>>> {
+        // A poisoned cache only means another thread panicked while holding the lock; the tables
+        // in it are still valid.
+        self.tables
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Bit-for-bit comparison of two sets of intrinsics.
+fn intrinsics_eq(a: &Rs2Intrinsics, b: &Rs2Intrinsics) -> bool {
+    let (a, b) = (&a.0, &b.0);
+    a.width == b.width
+        && a.height == b.height
+        && a.model == b.model
+        && [a.ppx, a.ppy, a.fx, a.fy]
+            .iter()
+            .chain(a.coeffs.iter())
+            .zip([b.ppx, b.ppy, b.fx, b.fy].iter().chain(b.coeffs.iter()))
+            .all(|(x, y)| x.to_bits() == y.to_bits())
+}
+
+/// A precomputed table of the undistorted ray through every pixel of an image.
+///
+/// Each ray is stored as the `[x, y]` coordinates of the point on the ray with a depth of 1 meter,
+/// so that the point at any depth `z` is `[x * z, y * z, z]`.
+#[derive(Debug, Clone)]
+pub struct DeprojectionTable {
+    /// The intrinsics the table was computed from.
+    intrinsics: Rs2Intrinsics,
+    /// The width of the image in pixels.
+    width: usize,
+    /// The height of the image in pixels.
+    height: usize,
+    /// The ray through each pixel, in row-major order.
+    rays: Vec<[f32; 2]>,
+}
+
+impl DeprojectionTable {
+    /// Compute the ray through every pixel of an image with the given intrinsics.
+    pub fn new(intrinsics: &Rs2Intrinsics) -> Self {
+        let width = intrinsics.width();
+        let height = intrinsics.height();
+
+        let mut rays = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for col in 0..width {
+                let [x, y, _] = intrinsics.deproject([col as f32, row as f32], 1.0);
+                rays.push([x, y]);
+            }
+        }
+
+        DeprojectionTable {
+            intrinsics: *intrinsics,
+            width,
+            height,
+            rays,
+        }
+    }
+
+    /// Get the width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether this table was computed from `intrinsics`.
+    pub fn matches(&self, intrinsics: &Rs2Intrinsics) -> bool {
+        intrinsics_eq(&self.intrinsics, intrinsics)
+    }
+
+    /// Get the ray through the pixel at a given column and row, as the `[x, y]` coordinates of the
+    /// point on the ray with a depth of 1 meter.
+    ///
+    /// Returns `None` if the pixel is outside of the image.
+    pub fn ray(&self, col: usize, row: usize) -> Option<[f32; 2]> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        Some(self.rays[row * self.width + col])
+    }
+
+    /// Deproject every pixel of a depth image into an organized point cloud.
+    ///
+    /// `depth_at` returns the depth (in meters) of the pixel at a given column and row, and is only
+    /// called with coordinates inside the image.
+    pub(crate) fn deproject<F>(&self, depth_at: F) -> OrganizedPointCloud
+    where
+        F: Fn(usize, usize) -> f32,
+    {
+        let mut points = Vec::with_capacity(self.rays.len());
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let depth = depth_at(col, row);
+                if is_valid_depth(depth) {
+                    let [x, y] = self.rays[row * self.width + col];
+                    points.push([x * depth, y * depth, depth]);
+                } else {
+                    points.push([f32::NAN; 3]);
+                }
+            }
+        }
+
+        OrganizedPointCloud {
+            width: self.width,
+            height: self.height,
+            points,
+        }
+    }
+}
+
+/// A point cloud with one point per pixel of the depth image it was deprojected from.
+///
+/// Points are in meters, in the coordinate frame of the depth stream. Pixels without a valid depth
+/// produce a point whose coordinates are all NaN, so that every pixel keeps its place in the grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrganizedPointCloud {
+    /// The width of the grid in points.
+    pub(crate) width: usize,
+    /// The height of the grid in points.
+    pub(crate) height: usize,
+    /// The points, in row-major order.
+    pub(crate) points: Vec<[f32; 3]>,
+}
+
+impl OrganizedPointCloud {
+    /// Get the width of the grid in points.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the grid in points.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get every point in the grid, in row-major order.
+    pub fn points(&self) -> &[[f32; 3]] {
+        &self.points
+    }
+
+    /// Take ownership of the points, in row-major order.
+    pub fn into_points(self) -> Vec<[f32; 3]> {
+        self.points
+    }
+
+    /// Given a row and column index, get the point deprojected from that pixel.
+    ///
+    /// Returns `None` if the index is out of bounds. The point itself is NaN if the pixel did not
+    /// hold a valid depth.
+    pub fn get(&self, col: usize, row: usize) -> Option<[f32; 3]> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        Some(self.points[row * self.width + col])
+    }
+
+    /// Iterate over the points that were deprojected from a valid depth.
+    pub fn valid_points(&self) -> impl Iterator<Item = [f32; 3]> + '_ {
+        self.points.iter().copied().filter(|p| !p[2].is_nan())
+    }
+
+    /// Write the point cloud as an organized PCL `.pcd` file.
+    ///
+    /// `colors`, if given, must hold one color per point, e.g. the pixels of a color image
+    /// registered to the depth frame with
+    /// [`DepthFrame::register_image_to_depth`](crate::frame::DepthFrame::register_image_to_depth).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError::MismatchedColors`] if there is not exactly one color per point.
+    ///
+    /// Returns [`ExportError::CouldNotWrite`] if writing to `writer` fails.
+    pub fn write_pcd<W: Write>(
+        &self,
+        writer: W,
+        format: PcdFormat,
+        colors: Option<&[[u8; 3]]>,
+    ) -> Result<(), ExportError> {
+        self.check_colors(colors)?;
+        write_pcd(
+            writer,
+            self.width,
+            self.height,
+            &self.points,
+            colors,
+            format,
+        )
+    }
+
+    /// Write the valid points of the cloud as a LAS 1.4 file.
+    ///
+    /// Positions are in meters, with a resolution of 0.1mm. `colors`, if given, must hold one
+    /// color per point, including the points without a valid depth.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError::MismatchedColors`] if there is not exactly one color per point.
+    ///
+    /// Returns [`ExportError::CouldNotWrite`] if writing to `writer` fails.
+    pub fn write_las<W: Write>(
+        &self,
+        writer: W,
+        colors: Option<&[[u8; 3]]>,
+    ) -> Result<(), ExportError> {
+        self.check_colors(colors)?;
+        write_las(writer, &self.points, colors)
+    }
+
+    /// Check that there is one color per point.
+    fn check_colors(&self, colors: Option<&[[u8; 3]]>) -> Result<(), ExportError> {
+        match colors {
+            Some(colors) if colors.len() != self.points.len() => Err(
+                ExportError::MismatchedColors(colors.len(), self.points.len()),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kind::Rs2DistortionModel;
+    use num_traits::ToPrimitive;
+    use realsense_sys as sys;
+
+    fn intrinsics(model: Rs2DistortionModel, coeffs: [f32; 5]) -> Rs2Intrinsics {
+        Rs2Intrinsics(sys::rs2_intrinsics {
+            width: 4,
+            height: 3,
+            ppx: 1.5,
+            ppy: 1.0,
+            fx: 2.0,
+            fy: 2.0,
+            model: model.to_u32().unwrap(),
+            coeffs,
+        })
+    }
+
+    #[test]
+    fn deprojects_organized_grid() {
+        let table = DeprojectionTable::new(&intrinsics(Rs2DistortionModel::None, [0.0; 5]));
+        let cloud = table.deproject(|col, row| if col == 0 && row == 0 { 0.0 } else { 2.0 });
+
+        assert_eq!((cloud.width(), cloud.height()), (4, 3));
+        assert_eq!(cloud.points().len(), 12);
+        assert!(cloud.get(0, 0).unwrap().iter().all(|v| v.is_nan()));
+        assert_eq!(cloud.get(3, 2), Some([1.5, 1.0, 2.0]));
+        assert_eq!(cloud.get(4, 0), None);
+        assert_eq!(cloud.valid_points().count(), 11);
+    }
+
+    #[test]
+    fn table_matches_direct_deprojection() {
+        let intrinsics = intrinsics(
+            Rs2DistortionModel::BrownConrady,
+            [-0.055, 0.065, -0.0008, 0.0011, -0.02],
+        );
+        let cloud = DeprojectionTable::new(&intrinsics).deproject(|_, _| 1.25);
+
+        for row in 0..3 {
+            for col in 0..4 {
+                let expected = intrinsics.deproject([col as f32, row as f32], 1.25);
+                let point = cloud.get(col, row).unwrap();
+                for i in 0..3 {
+                    assert!((point[i] - expected[i]).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn caches_tables_per_intrinsics() {
+        let cache = TableCache::new(2);
+        let none = intrinsics(Rs2DistortionModel::None, [0.0; 5]);
+        let mut distorted = none;
+        distorted.0.coeffs[0] = 0.1;
+
+        let first = cache.get(&none);
+        assert!(Arc::ptr_eq(&first, &cache.get(&none)));
+        assert!(!Arc::ptr_eq(&first, &cache.get(&distorted)));
+        assert!(cache.get(&distorted).matches(&distorted));
+    }
+
+    #[test]
+    fn evicts_least_recently_used_table() {
+        let cache = TableCache::new(2);
+        let none = intrinsics(Rs2DistortionModel::None, [0.0; 5]);
+        let mut a = none;
+        a.0.coeffs[0] = 0.1;
+        let mut b = none;
+        b.0.coeffs[0] = 0.2;
+
+        let first = cache.get(&none);
+        let first_a = cache.get(&a);
+        // Using `none` again makes `a` the least recently used table.
+        cache.get(&none);
+        cache.get(&b);
+
+        assert!(Arc::ptr_eq(&first, &cache.get(&none)));
+        assert!(!Arc::ptr_eq(&first_a, &cache.get(&a)));
+    }
+}
@@ -0,0 +1,307 @@
+//! Types for converting between stereo depth and disparity.
+//!
+//! Stereo depth cameras measure disparity, the horizontal shift in pixels of a point between the
+//! left and right imagers, and convert it to depth with
+//!
+//! ```text
+//! depth = focal_length * baseline / (disparity + disparity_shift)
+//! ```
+//!
+//! where the disparity shift is the offset of the disparity search range (configured through the
+//! advanced mode of D400 devices, and `0` by default). The conversions here are the native
+//! counterpart to the SDK's `DisparityFilter`. See
+//! [`DepthFrame::to_disparity`](crate::frame::DepthFrame::to_disparity) and
+//! [`DisparityFrame::to_depth`](crate::frame::DisparityFrame::to_depth) for how to convert a frame.
+
+use super::statistics::is_valid_depth;
+
+/// The number of subpixel steps per pixel in [`Disparity16`](crate::kind::Rs2Format::Disparity16)
+/// frames.
+pub(crate) const DISPARITY16_SUBPIXELS: f32 = 32.0;
+
+/// The stereo geometry used to convert between depth and disparity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StereoParameters {
+    /// The horizontal focal length of the depth stream, in pixels.
+    pub focal_length: f32,
+    /// The distance between the two imagers, in meters.
+    pub baseline: f32,
+    /// The offset of the disparity search range, in pixels.
+    ///
+    /// Disparities are reported relative to this offset, i.e. the actual shift of a point between
+    /// the imagers is `disparity + disparity_shift`.
+    pub disparity_shift: f32,
+}
+
+impl StereoParameters {
+    /// Construct stereo parameters from a focal length in pixels, a baseline in meters, and a
+    /// disparity shift in pixels.
+    pub fn new(focal_length: f32, baseline: f32, disparity_shift: f32) -> Self {
+        StereoParameters {
+            focal_length,
+            baseline,
+            disparity_shift,
+        }
+    }
+
+    /// Convert a depth in meters to a disparity in pixels.
+    ///
+    /// Returns `0.0` for invalid depths, and for depths too far away to be measured with the
+    /// disparity shift (i.e. whose disparity would be negative).
+    pub fn depth_to_disparity(&self, depth: f32) -> f32 {
+        if !is_valid_depth(depth) {
+            return 0.0;
+        }
+
+        let disparity = self.focal_length * self.baseline / depth - self.disparity_shift;
+        if disparity.is_finite() && disparity > 0.0 {
+            disparity
+        } else {
+            0.0
+        }
+    }
+
+    /// Convert a disparity in pixels to a depth in meters.
+    ///
+    /// Returns `0.0` for disparities that do not correspond to a point in front of the camera.
+    pub fn disparity_to_depth(&self, disparity: f32) -> f32 {
+        let shifted = disparity + self.disparity_shift;
+        if disparity <= 0.0 || !shifted.is_finite() || shifted <= 0.0 {
+            return 0.0;
+        }
+
+        self.focal_length * self.baseline / shifted
+    }
+}
+
+/// An owned disparity image, in pixels.
+///
+/// Pixels without a valid disparity are `0.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisparityImage {
+    /// The width of the image in pixels.
+    pub(crate) width: usize,
+    /// The height of the image in pixels.
+    pub(crate) height: usize,
+    /// The disparity of each pixel, in row-major order.
+    pub(crate) disparities: Vec<f32>,
+    /// The stereo geometry the disparities were computed with.
+    pub(crate) parameters: StereoParameters,
+}
+
+impl DisparityImage {
+    /// Get the width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the disparity of every pixel in pixels, in row-major order.
+    pub fn disparities(&self) -> &[f32] {
+        &self.disparities
+    }
+
+    /// Take ownership of the disparities, in row-major order.
+    pub fn into_disparities(self) -> Vec<f32> {
+        self.disparities
+    }
+
+    /// Given a row and column index, get the disparity of a pixel in pixels.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    pub fn get(&self, col: usize, row: usize) -> Option<f32> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        self.disparities.get(row * self.width + col).copied()
+    }
+
+    /// Get the stereo geometry the disparities were computed with.
+    pub fn parameters(&self) -> &StereoParameters {
+        &self.parameters
+    }
+
+    /// Convert the disparities into a depth image, in meters.
+    pub fn to_depth(&self) -> DepthImage {
+        let depths = self
+            .disparities
+            .iter()
+            .map(|&disparity| self.parameters.disparity_to_depth(disparity))
+            .collect();
+
+        DepthImage {
+            width: self.width,
+            height: self.height,
+            depths,
+            parameters: self.parameters,
+        }
+    }
+
+    /// Quantize the disparities into the fixed-point representation of
+    /// [`Disparity16`](crate::kind::Rs2Format::Disparity16) frames.
+    ///
+    /// Disparities that do not fit in 16 bits are saturated.
+    pub fn to_disparity16(&self) -> Vec<u16> {
+        self.disparities
+            .iter()
+            .map(|disparity| {
+                (disparity * DISPARITY16_SUBPIXELS)
+                    .round()
+                    .min(u16::MAX as f32) as u16
+            })
+            .collect()
+    }
+}
+
+/// An owned depth image, in meters, converted from disparity.
+///
+/// Pixels without a valid depth are `0.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthImage {
+    /// The width of the image in pixels.
+    pub(crate) width: usize,
+    /// The height of the image in pixels.
+    pub(crate) height: usize,
+    /// The depth of each pixel, in row-major order.
+    pub(crate) depths: Vec<f32>,
+    /// The stereo geometry the depths were computed with.
+    pub(crate) parameters: StereoParameters,
+}
+
+impl DepthImage {
+    /// Get the width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the depth of every pixel in meters, in row-major order.
+    pub fn depths(&self) -> &[f32] {
+        &self.depths
+    }
+
+    /// Take ownership of the depths, in row-major order.
+    pub fn into_depths(self) -> Vec<f32> {
+        self.depths
+    }
+
+    /// Given a row and column index, get the depth of a pixel in meters.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    pub fn get(&self, col: usize, row: usize) -> Option<f32> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        self.depths.get(row * self.width + col).copied()
+    }
+
+    /// Get the stereo geometry the depths were computed with.
+    pub fn parameters(&self) -> &StereoParameters {
+        &self.parameters
+    }
+
+    /// Convert the depths into a disparity image, in pixels.
+    pub fn to_disparity(&self) -> DisparityImage {
+        disparity_image(
+            self.width,
+            self.height,
+            |col, row| self.depths[row * self.width + col],
+            self.parameters,
+        )
+    }
+
+    /// Quantize the depths into the representation of [`Z16`](crate::kind::Rs2Format::Z16)
+    /// frames, given the depth units (in meters) of the sensor.
+    ///
+    /// Depths that do not fit in 16 bits are saturated.
+    pub fn to_z16(&self, depth_units: f32) -> Vec<u16> {
+        self.depths
+            .iter()
+            .map(|depth| (depth / depth_units).round().min(u16::MAX as f32) as u16)
+            .collect()
+    }
+}
+
+/// Convert a `width` x `height` depth image into a disparity image.
+///
+/// `depth_at` returns the depth (in meters) of the pixel at a given column and row.
+pub(crate) fn disparity_image<F>(
+    width: usize,
+    height: usize,
+    depth_at: F,
+    parameters: StereoParameters,
+) -> DisparityImage
+where
+    F: Fn(usize, usize) -> f32,
+{
+    let mut disparities = Vec::with_capacity(width * height);
+    for row in 0..height {
+        for col in 0..width {
+            disparities.push(parameters.depth_to_disparity(depth_at(col, row)));
+        }
+    }
+
+    DisparityImage {
+        width,
+        height,
+        disparities,
+        parameters,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A D435-like depth stream: 50mm baseline, 640 pixels focal length.
+    const PARAMETERS: StereoParameters = StereoParameters {
+        focal_length: 640.0,
+        baseline: 0.05,
+        disparity_shift: 0.0,
+    };
+
+    #[test]
+    fn converts_depth_to_disparity() {
+        assert_eq!(PARAMETERS.depth_to_disparity(1.0), 32.0);
+        assert_eq!(PARAMETERS.disparity_to_depth(32.0), 1.0);
+        assert_eq!(PARAMETERS.depth_to_disparity(0.0), 0.0);
+        assert_eq!(PARAMETERS.depth_to_disparity(f32::NAN), 0.0);
+        assert_eq!(PARAMETERS.disparity_to_depth(0.0), 0.0);
+    }
+
+    #[test]
+    fn disparity_shift_offsets_disparities() {
+        let shifted = StereoParameters::new(640.0, 0.05, 16.0);
+        assert_eq!(shifted.depth_to_disparity(1.0), 16.0);
+        assert_eq!(shifted.disparity_to_depth(16.0), 1.0);
+
+        // Points beyond the shifted search range cannot be represented.
+        assert_eq!(shifted.depth_to_disparity(4.0), 0.0);
+    }
+
+    #[test]
+    fn images_round_trip() {
+        let depths = [0.5, 1.0, 0.0, 2.0, 4.0, 8.0];
+        let disparity = disparity_image(3, 2, |col, row| depths[row * 3 + col], PARAMETERS);
+
+        assert_eq!(disparity.get(1, 0), Some(32.0));
+        assert_eq!(disparity.get(2, 0), Some(0.0));
+        assert_eq!(disparity.get(3, 0), None);
+        assert_eq!(disparity.to_disparity16()[0], 64 * 32);
+
+        let depth = disparity.to_depth();
+        for (converted, original) in depth.depths().iter().zip(depths.iter()) {
+            assert!((converted - original).abs() < 1e-5);
+        }
+        assert_eq!(depth.to_z16(0.001)[3], 2000);
+        assert_eq!(depth.to_disparity(), disparity);
+    }
+}
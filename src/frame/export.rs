@@ -0,0 +1,872 @@
+//! Types for exporting point clouds to common file formats.
+//!
+//! Three formats are supported:
+//!
+//! - PLY, as written by the SDK's `export_to_ply`. See
+//!   [`PointsFrame::write_ply`](crate::frame::PointsFrame::write_ply).
+//! - PCD, as read by the Point Cloud Library. Organized clouds keep their width and height. See
+//!   [`PointsFrame::write_pcd`](crate::frame::PointsFrame::write_pcd) and
+//!   [`OrganizedPointCloud::write_pcd`](crate::frame::OrganizedPointCloud::write_pcd).
+//! - LAS 1.4, as read by survey and GIS tools. See
+//!   [`PointsFrame::write_las`](crate::frame::PointsFrame::write_las) and
+//!   [`OrganizedPointCloud::write_las`](crate::frame::OrganizedPointCloud::write_las). Compressed
+//!   LAZ output is not supported, but LAS files can be compressed with `laszip`.
+
+use super::{image::ColorFrame, prelude::FrameEx};
+use crate::kind::Rs2Format;
+use std::{
+    io::{BufWriter, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// Occurs when a point cloud cannot be exported.
+#[derive(Error, Debug)]
+pub enum ExportError {
+    /// The color frame is in a format that cannot be exported as RGB.
+    #[error("Color frame format cannot be exported: {0:?}")]
+    UnsupportedColorFormat(Rs2Format),
+    /// Normals were requested, but the point cloud is not organized into a grid.
+    #[error("Cannot compute normals for a point cloud of {0} points that is not organized")]
+    NotOrganized(usize),
+    /// The number of colors does not match the number of points.
+    #[error("Expected one color per point, but got {0} colors for {1} points")]
+    MismatchedColors(usize, usize),
+    /// The writer returned an error.
+    #[error("Could not write point cloud. Reason: {0}")]
+    CouldNotWrite(String),
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::CouldNotWrite(e.to_string())
+    }
+}
+
+/// The encoding of a PLY file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    /// Human-readable text, one point per line.
+    Ascii,
+    /// Packed little-endian binary, which is much smaller and faster to read and write.
+    BinaryLittleEndian,
+}
+
+/// Options for exporting a point cloud to PLY.
+#[derive(Debug, Clone, Copy)]
+pub struct PlyOptions<'a> {
+    /// The encoding of the file.
+    pub format: PlyFormat,
+    /// The color frame to texture the points with, if any.
+    ///
+    /// Colors are sampled at the texture coordinates of each point, so this should be the frame
+    /// the point cloud was mapped to with `PointCloud::map_to`.
+    pub color: Option<&'a ColorFrame>,
+    /// Whether or not to compute a normal for every point from its neighbours in the grid.
+    pub normals: bool,
+    /// Whether or not to leave out points at the origin, which librealsense2 produces for pixels
+    /// without a valid depth.
+    pub remove_zero_points: bool,
+}
+
+impl Default for PlyOptions<'_> {
+    /// Binary output without colors or normals, keeping every point.
+    fn default() -> Self {
+        PlyOptions {
+            format: PlyFormat::BinaryLittleEndian,
+            color: None,
+            normals: false,
+            remove_zero_points: false,
+        }
+    }
+}
+
+/// Whether a point is at the origin, i.e. has no valid depth.
+#[inline]
+pub(crate) fn is_zero_point(point: &[f32; 3]) -> bool {
+    point[0] == 0.0 && point[1] == 0.0 && point[2] == 0.0
+}
+
+/// Whether a point has a valid position.
+#[inline]
+fn is_valid_point(point: &[f32; 3]) -> bool {
+    point.iter().all(|c| c.is_finite()) && !is_zero_point(point)
+}
+
+fn sub(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Compute a normal for every point of an organized point cloud, `width` points wide.
+///
+/// Normals are the cross product of the horizontal and vertical gradients of the grid, using
+/// central differences where both neighbours are valid and one-sided differences otherwise. They
+/// are oriented towards the camera. Points without enough valid neighbours get a zero normal.
+pub(crate) fn grid_normals(points: &[[f32; 3]], width: usize) -> Vec<[f32; 3]> {
+    let height = points.len().checked_div(width).unwrap_or(0);
+    let valid_at = |col: usize, row: usize| {
+        let point = &points[row * width + col];
+        if is_valid_point(point) {
+            Some(point)
+        } else {
+            None
+        }
+    };
+
+    // Gradient between the valid neighbours of a point along one axis.
+    let gradient = |before: Option<&[f32; 3]>, center: &[f32; 3], after: Option<&[f32; 3]>| match (
+        before, after,
+    ) {
+        (Some(before), Some(after)) => Some(sub(after, before)),
+        (None, Some(after)) => Some(sub(after, center)),
+        (Some(before), None) => Some(sub(center, before)),
+        (None, None) => None,
+    };
+
+    let mut normals = Vec::with_capacity(points.len());
+    for row in 0..height {
+        for col in 0..width {
+            let normal = valid_at(col, row).and_then(|center| {
+                let left = col.checked_sub(1).and_then(|c| valid_at(c, row));
+                let right = if col + 1 < width {
+                    valid_at(col + 1, row)
+                } else {
+                    None
+                };
+                let up = row.checked_sub(1).and_then(|r| valid_at(col, r));
+                let down = if row + 1 < height {
+                    valid_at(col, row + 1)
+                } else {
+                    None
+                };
+
+                let du = gradient(left, center, right)?;
+                let dv = gradient(up, center, down)?;
+                let normal = cross(&du, &dv);
+                let norm = normal.iter().map(|c| c * c).sum::<f32>().sqrt();
+                if norm == 0.0 || !norm.is_finite() {
+                    return None;
+                }
+
+                // Orient the normal towards the camera at the origin.
+                let sign = if normal
+                    .iter()
+                    .zip(center.iter())
+                    .map(|(n, p)| n * p)
+                    .sum::<f32>()
+                    > 0.0
+                {
+                    -1.0
+                } else {
+                    1.0
+                };
+                Some([
+                    sign * normal[0] / norm,
+                    sign * normal[1] / norm,
+                    sign * normal[2] / norm,
+                ])
+            });
+            normals.push(normal.unwrap_or([0.0; 3]));
+        }
+    }
+
+    // Any points past the last full row cannot have neighbours below them.
+    normals.resize(points.len(), [0.0; 3]);
+    normals
+}
+
+/// Read the color of a frame as RGB at every texture coordinate.
+///
+/// Texture coordinates are normalized to `[0, 1]` across the frame, and are clamped to the frame
+/// as in librealsense2's own PLY export.
+pub(crate) fn sample_colors(
+    color: &ColorFrame,
+    texture_coordinates: &[[f32; 2]],
+) -> Result<Vec<[u8; 3]>, ExportError> {
+    let rgb_at = color
+        .rgb_reader()
+        .ok_or_else(|| ExportError::UnsupportedColorFormat(color.stream_profile().format()))?;
+
+    let (width, height) = (color.width(), color.height());
+    Ok(texture_coordinates
+        .iter()
+        .map(|[u, v]| {
+            let col = texture_index(*u, width);
+            let row = texture_index(*v, height);
+            rgb_at(col, row)
+        })
+        .collect())
+}
+
+/// Convert a normalized texture coordinate to a pixel index in `[0, size)`.
+#[inline]
+fn texture_index(coordinate: f32, size: usize) -> usize {
+    let index = (coordinate * size as f32 + 0.5) as i64;
+    index.max(0).min(size as i64 - 1) as usize
+}
+
+/// Write points (with optional colors and normals) as a PLY file.
+///
+/// `colors` and `normals` must have one entry per point when given. Points at the origin are left
+/// out if `remove_zero_points` is set.
+pub(crate) fn write_ply<W: Write>(
+    writer: W,
+    points: &[[f32; 3]],
+    colors: Option<&[[u8; 3]]>,
+    normals: Option<&[[f32; 3]]>,
+    format: PlyFormat,
+    remove_zero_points: bool,
+) -> Result<(), ExportError> {
+    let mut writer = BufWriter::new(writer);
+    let kept: Vec<usize> = (0..points.len())
+        .filter(|&i| !remove_zero_points || !is_zero_point(&points[i]))
+        .collect();
+
+    writeln!(writer, "ply")?;
+    match format {
+        PlyFormat::Ascii => writeln!(writer, "format ascii 1.0")?,
+        PlyFormat::BinaryLittleEndian => writeln!(writer, "format binary_little_endian 1.0")?,
+    }
+    writeln!(writer, "comment Created by realsense-rust")?;
+    writeln!(writer, "element vertex {}", kept.len())?;
+    writeln!(writer, "property float x")?;
+    writeln!(writer, "property float y")?;
+    writeln!(writer, "property float z")?;
+    if normals.is_some() {
+        writeln!(writer, "property float nx")?;
+        writeln!(writer, "property float ny")?;
+        writeln!(writer, "property float nz")?;
+    }
+    if colors.is_some() {
+        writeln!(writer, "property uchar red")?;
+        writeln!(writer, "property uchar green")?;
+        writeln!(writer, "property uchar blue")?;
+    }
+    writeln!(writer, "end_header")?;
+
+    for &i in kept.iter() {
+        let normal = normals.map(|normals| normals[i]);
+        let color = colors.map(|colors| colors[i]);
+
+        match format {
+            PlyFormat::Ascii => {
+                let [x, y, z] = points[i];
+                write!(writer, "{} {} {}", x, y, z)?;
+                if let Some([nx, ny, nz]) = normal {
+                    write!(writer, " {} {} {}", nx, ny, nz)?;
+                }
+                if let Some([r, g, b]) = color {
+                    write!(writer, " {} {} {}", r, g, b)?;
+                }
+                writeln!(writer)?;
+            }
+            PlyFormat::BinaryLittleEndian => {
+                for c in points[i].iter().chain(normal.iter().flatten()) {
+                    writer.write_all(&c.to_le_bytes())?;
+                }
+                if let Some(color) = color {
+                    writer.write_all(&color)?;
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write a triangle mesh (with optional per-vertex colors) as a PLY file.
+///
+/// `colors` must have one entry per vertex when given, and every triangle indexes into
+/// `vertices`.
+pub(crate) fn write_mesh_ply<W: Write>(
+    writer: W,
+    vertices: &[[f32; 3]],
+    colors: Option<&[[u8; 3]]>,
+    triangles: &[[u32; 3]],
+    format: PlyFormat,
+) -> Result<(), ExportError> {
+    let mut writer = BufWriter::new(writer);
+
+    writeln!(writer, "ply")?;
+    match format {
+        PlyFormat::Ascii => writeln!(writer, "format ascii 1.0")?,
+        PlyFormat::BinaryLittleEndian => writeln!(writer, "format binary_little_endian 1.0")?,
+    }
+    writeln!(writer, "comment Created by realsense-rust")?;
+    writeln!(writer, "element vertex {}", vertices.len())?;
+    writeln!(writer, "property float x")?;
+    writeln!(writer, "property float y")?;
+    writeln!(writer, "property float z")?;
+    if colors.is_some() {
+        writeln!(writer, "property uchar red")?;
+        writeln!(writer, "property uchar green")?;
+        writeln!(writer, "property uchar blue")?;
+    }
+    writeln!(writer, "element face {}", triangles.len())?;
+    writeln!(writer, "property list uchar int vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for (i, vertex) in vertices.iter().enumerate() {
+        let color = colors.map(|colors| colors[i]);
+
+        match format {
+            PlyFormat::Ascii => {
+                let [x, y, z] = vertex;
+                write!(writer, "{} {} {}", x, y, z)?;
+                if let Some([r, g, b]) = color {
+                    write!(writer, " {} {} {}", r, g, b)?;
+                }
+                writeln!(writer)?;
+            }
+            PlyFormat::BinaryLittleEndian => {
+                for c in vertex.iter() {
+                    writer.write_all(&c.to_le_bytes())?;
+                }
+                if let Some(color) = color {
+                    writer.write_all(&color)?;
+                }
+            }
+        }
+    }
+
+    for [a, b, c] in triangles.iter() {
+        match format {
+            PlyFormat::Ascii => writeln!(writer, "3 {} {} {}", a, b, c)?,
+            PlyFormat::BinaryLittleEndian => {
+                writer.write_all(&[3])?;
+                for index in [a, b, c].iter() {
+                    writer.write_all(&(**index as i32).to_le_bytes())?;
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// The encoding of the data in a PCD file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcdFormat {
+    /// Human-readable text, one point per line.
+    Ascii,
+    /// Packed binary, one point after another.
+    Binary,
+    /// Packed binary, one field after another, compressed with LZF.
+    BinaryCompressed,
+}
+
+/// Write points (with optional colors) as a PCL `.pcd` file, organized `width` x `height`.
+///
+/// `colors` must have one entry per point when given, and are written as the packed `rgb` field
+/// used by `pcl::PointXYZRGB`. Points without a valid position are written as NaN, as PCL expects
+/// for organized clouds.
+pub(crate) fn write_pcd<W: Write>(
+    writer: W,
+    width: usize,
+    height: usize,
+    points: &[[f32; 3]],
+    colors: Option<&[[u8; 3]]>,
+    format: PcdFormat,
+) -> Result<(), ExportError> {
+    let mut writer = BufWriter::new(writer);
+    let points: Vec<[f32; 3]> = points
+        .iter()
+        .map(|point| {
+            if is_valid_point(point) {
+                *point
+            } else {
+                [f32::NAN; 3]
+            }
+        })
+        .collect();
+    let rgb: Option<Vec<u32>> = colors.map(|colors| {
+        colors
+            .iter()
+            .map(|[r, g, b]| (*r as u32) << 16 | (*g as u32) << 8 | *b as u32)
+            .collect()
+    });
+
+    writeln!(writer, "# .PCD v0.7 - Point Cloud Data file format")?;
+    writeln!(writer, "VERSION 0.7")?;
+    if rgb.is_some() {
+        writeln!(writer, "FIELDS x y z rgb")?;
+        writeln!(writer, "SIZE 4 4 4 4")?;
+        writeln!(writer, "TYPE F F F F")?;
+        writeln!(writer, "COUNT 1 1 1 1")?;
+    } else {
+        writeln!(writer, "FIELDS x y z")?;
+        writeln!(writer, "SIZE 4 4 4")?;
+        writeln!(writer, "TYPE F F F")?;
+        writeln!(writer, "COUNT 1 1 1")?;
+    }
+    writeln!(writer, "WIDTH {}", width)?;
+    writeln!(writer, "HEIGHT {}", height)?;
+    writeln!(writer, "VIEWPOINT 0 0 0 1 0 0 0")?;
+    writeln!(writer, "POINTS {}", points.len())?;
+    match format {
+        PcdFormat::Ascii => writeln!(writer, "DATA ascii")?,
+        PcdFormat::Binary => writeln!(writer, "DATA binary")?,
+        PcdFormat::BinaryCompressed => writeln!(writer, "DATA binary_compressed")?,
+    }
+
+    match format {
+        PcdFormat::Ascii => {
+            for (i, [x, y, z]) in points.iter().enumerate() {
+                write!(writer, "{} {} {}", x, y, z)?;
+                // PCL writes the packed color as an integer, even though its type is float.
+                if let Some(rgb) = &rgb {
+                    write!(writer, " {}", rgb[i])?;
+                }
+                writeln!(writer)?;
+            }
+        }
+        PcdFormat::Binary => {
+            for (i, point) in points.iter().enumerate() {
+                for c in point.iter() {
+                    writer.write_all(&c.to_le_bytes())?;
+                }
+                if let Some(rgb) = &rgb {
+                    writer.write_all(&rgb[i].to_le_bytes())?;
+                }
+            }
+        }
+        PcdFormat::BinaryCompressed => {
+            // Compressed data is laid out one field at a time, rather than one point at a time.
+            let mut data = Vec::with_capacity(points.len() * 16);
+            for axis in 0..3 {
+                for point in points.iter() {
+                    data.extend_from_slice(&point[axis].to_le_bytes());
+                }
+            }
+            if let Some(rgb) = &rgb {
+                for value in rgb.iter() {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+
+            let compressed = lzf_compress(&data);
+            writer.write_all(&(compressed.len() as u32).to_le_bytes())?;
+            writer.write_all(&(data.len() as u32).to_le_bytes())?;
+            writer.write_all(&compressed)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// The largest distance back that an LZF back reference can reach.
+const LZF_MAX_OFFSET: usize = 1 << 13;
+/// The longest match that an LZF back reference can encode.
+const LZF_MAX_MATCH: usize = (1 << 8) + (1 << 3);
+/// The longest run of literals that a single LZF control byte can introduce.
+const LZF_MAX_LITERALS: usize = 1 << 5;
+
+/// Compress data with LZF, in the format read by `lzf_decompress` from liblzf (and so PCL).
+fn lzf_compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 16);
+    let mut literals: Vec<u8> = Vec::with_capacity(LZF_MAX_LITERALS);
+    let mut table = vec![usize::MAX; 1 << 14];
+
+    let flush = |output: &mut Vec<u8>, literals: &mut Vec<u8>| {
+        for run in literals.chunks(LZF_MAX_LITERALS) {
+            output.push((run.len() - 1) as u8);
+            output.extend_from_slice(run);
+        }
+        literals.clear();
+    };
+
+    let mut i = 0;
+    while i + 2 < input.len() {
+        let hash =
+            ((input[i] as usize) << 6 ^ (input[i + 1] as usize) << 3 ^ input[i + 2] as usize)
+                & ((1 << 14) - 1);
+        let candidate = table[hash];
+        table[hash] = i;
+
+        if candidate != usize::MAX
+            && i - candidate <= LZF_MAX_OFFSET
+            && input[candidate..candidate + 3] == input[i..i + 3]
+        {
+            let max_length = LZF_MAX_MATCH.min(input.len() - i);
+            let mut length = 3;
+            while length < max_length && input[candidate + length] == input[i + length] {
+                length += 1;
+            }
+
+            flush(&mut output, &mut literals);
+            let offset = i - candidate - 1;
+            let encoded = length - 2;
+            if encoded < 7 {
+                output.push((encoded << 5 | offset >> 8) as u8);
+            } else {
+                output.push((7 << 5 | offset >> 8) as u8);
+                output.push((encoded - 7) as u8);
+            }
+            output.push((offset & 0xFF) as u8);
+            i += length;
+        } else {
+            literals.push(input[i]);
+            i += 1;
+        }
+    }
+
+    literals.extend_from_slice(&input[i..]);
+    flush(&mut output, &mut literals);
+    output
+}
+
+/// The size of a LAS 1.4 header, in bytes.
+const LAS_HEADER_SIZE: u16 = 375;
+/// The size of a LAS point data record format 7 (positions, GPS time and RGB), in bytes.
+const LAS_POINT_SIZE: u16 = 36;
+/// The resolution of positions in LAS files written by this crate, in meters.
+pub(crate) const LAS_SCALE: f64 = 1e-4;
+
+/// Write points (with optional colors) as a LAS 1.4 file, with point data record format 7.
+///
+/// `colors` must have one entry per point when given; points without colors are written white.
+/// Points without a valid position are left out. Positions are written in meters, in the
+/// coordinate frame of the points, with a resolution of [`LAS_SCALE`].
+pub(crate) fn write_las<W: Write>(
+    writer: W,
+    points: &[[f32; 3]],
+    colors: Option<&[[u8; 3]]>,
+) -> Result<(), ExportError> {
+    let mut writer = BufWriter::new(writer);
+    let kept: Vec<usize> = (0..points.len())
+        .filter(|&i| is_valid_point(&points[i]))
+        .collect();
+
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for &i in kept.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(points[i][axis] as f64);
+            max[axis] = max[axis].max(points[i][axis] as f64);
+        }
+    }
+    if kept.is_empty() {
+        min = [0.0; 3];
+        max = [0.0; 3];
+    }
+
+    let (day, year) = creation_date();
+    let mut header = Vec::with_capacity(LAS_HEADER_SIZE as usize);
+    header.extend_from_slice(b"LASF");
+    header.extend_from_slice(&0u16.to_le_bytes()); // File source ID
+    header.extend_from_slice(&16u16.to_le_bytes()); // Global encoding: WKT coordinate system
+    header.extend_from_slice(&[0; 16]); // Project ID (GUID)
+    header.extend_from_slice(&[1, 4]); // Version
+    header.extend_from_slice(&padded::<32>(b"realsense-rust")); // System identifier
+    header.extend_from_slice(&padded::<32>(b"realsense-rust")); // Generating software
+    header.extend_from_slice(&day.to_le_bytes());
+    header.extend_from_slice(&year.to_le_bytes());
+    header.extend_from_slice(&LAS_HEADER_SIZE.to_le_bytes());
+    header.extend_from_slice(&(LAS_HEADER_SIZE as u32).to_le_bytes()); // Offset to point data
+    header.extend_from_slice(&0u32.to_le_bytes()); // Number of variable length records
+    header.push(7); // Point data record format
+    header.extend_from_slice(&LAS_POINT_SIZE.to_le_bytes());
+    header.extend_from_slice(&[0; 4 * 6]); // Legacy point counts, unused for format 7
+    for _ in 0..3 {
+        header.extend_from_slice(&LAS_SCALE.to_le_bytes());
+    }
+    header.extend_from_slice(&[0; 8 * 3]); // Offsets
+    for axis in 0..3 {
+        header.extend_from_slice(&max[axis].to_le_bytes());
+        header.extend_from_slice(&min[axis].to_le_bytes());
+    }
+    header.extend_from_slice(&0u64.to_le_bytes()); // Start of waveform data
+    header.extend_from_slice(&0u64.to_le_bytes()); // Start of extended variable length records
+    header.extend_from_slice(&0u32.to_le_bytes()); // Number of extended variable length records
+    header.extend_from_slice(&(kept.len() as u64).to_le_bytes());
+    header.extend_from_slice(&(kept.len() as u64).to_le_bytes()); // Points with return number 1
+    header.extend_from_slice(&[0; 8 * 14]);
+    writer.write_all(&header)?;
+
+    for &i in kept.iter() {
+        for c in points[i].iter() {
+            writer.write_all(&((*c as f64 / LAS_SCALE).round() as i32).to_le_bytes())?;
+        }
+        writer.write_all(&0u16.to_le_bytes())?; // Intensity
+        writer.write_all(&[0x11, 0, 0, 0])?; // Return 1 of 1, flags, classification, user data
+        writer.write_all(&0i16.to_le_bytes())?; // Scan angle
+        writer.write_all(&0u16.to_le_bytes())?; // Point source ID
+        writer.write_all(&0f64.to_le_bytes())?; // GPS time
+        let color = colors.map_or([u8::MAX; 3], |colors| colors[i]);
+        for c in color.iter() {
+            // Colors are 16-bit, so scale each channel up to the full range.
+            writer.write_all(&(*c as u16 * 257).to_le_bytes())?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Pad a string with zeros to a fixed length, truncating it if it is too long.
+fn padded<const N: usize>(text: &[u8]) -> [u8; N] {
+    let mut padded = [0; N];
+    let length = text.len().min(N);
+    padded[..length].copy_from_slice(&text[..length]);
+    padded
+}
+
+/// Get the current day of the year (starting at 1) and year, in UTC.
+fn creation_date() -> (u16, u16) {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86400);
+    day_of_year(days)
+}
+
+/// Convert a number of days since the Unix epoch to the day of the year (starting at 1) and year.
+fn day_of_year(days: u64) -> (u16, u16) {
+    // Number of leap years from year 1 up to and including `year`.
+    let leap_years = |year: u64| year / 4 - year / 100 + year / 400;
+    // Number of days from the Unix epoch to the first of January of `year`.
+    let new_year = |year: u64| 365 * (year - 1970) + leap_years(year - 1) - leap_years(1969);
+
+    let mut year = 1970;
+    while new_year(year + 1) <= days {
+        year += 1;
+    }
+    ((days - new_year(year) + 1) as u16, year as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    /// A 3x3 grid on the plane z = 1, with the bottom right point missing.
+    const GRID: [[f32; 3]; 9] = [
+        [-0.1, -0.1, 1.0],
+        [0.0, -0.1, 1.0],
+        [0.1, -0.1, 1.0],
+        [-0.1, 0.0, 1.0],
+        [0.0, 0.0, 1.0],
+        [0.1, 0.0, 1.0],
+        [-0.1, 0.1, 1.0],
+        [0.0, 0.1, 1.0],
+        [0.0, 0.0, 0.0],
+    ];
+
+    #[test]
+    fn normals_face_the_camera() {
+        let normals = grid_normals(&GRID, 3);
+
+        assert_eq!(normals[8], [0.0; 3]);
+        for (i, normal) in normals.iter().enumerate() {
+            if i == 8 {
+                continue;
+            }
+            assert!(normal[0].abs() < 1e-6);
+            assert!(normal[1].abs() < 1e-6);
+            assert!((normal[2] + 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn writes_ascii_ply() {
+        let colors = [[255, 0, 0]; 9];
+        let mut output = Vec::new();
+        write_ply(
+            &mut output,
+            &GRID,
+            Some(&colors),
+            None,
+            PlyFormat::Ascii,
+            true,
+        )
+        .unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        let (header, body) = text.split_at(text.find("end_header\n").unwrap() + 11);
+        assert!(header.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(header.contains("element vertex 8\n"));
+        assert!(header.contains("property uchar red\n"));
+        assert!(!header.contains("property float nx\n"));
+        assert_eq!(body.lines().count(), 8);
+        assert_eq!(body.lines().next(), Some("-0.1 -0.1 1 255 0 0"));
+    }
+
+    #[test]
+    fn writes_binary_ply() {
+        let normals = grid_normals(&GRID, 3);
+        let mut output = Vec::new();
+        write_ply(
+            &mut output,
+            &GRID,
+            None,
+            Some(&normals),
+            PlyFormat::BinaryLittleEndian,
+            false,
+        )
+        .unwrap();
+
+        let header_end = output
+            .windows(11)
+            .position(|w| w == b"end_header\n")
+            .unwrap()
+            + 11;
+        let header = std::str::from_utf8(&output[..header_end]).unwrap();
+        assert!(header.contains("format binary_little_endian 1.0\n"));
+        assert!(header.contains("element vertex 9\n"));
+
+        // Six floats per point.
+        let body = &output[header_end..];
+        assert_eq!(body.len(), 9 * 6 * 4);
+        let z = f32::from_le_bytes([body[8], body[9], body[10], body[11]]);
+        assert_eq!(z, 1.0);
+    }
+
+    #[test]
+    fn writes_mesh_ply() {
+        let vertices = [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [0.0, 1.0, 1.0]];
+        let mut output = Vec::new();
+        write_mesh_ply(
+            &mut output,
+            &vertices,
+            Some(&[[255, 0, 0]; 3]),
+            &[[0, 1, 2]],
+            PlyFormat::Ascii,
+        )
+        .unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        let (header, body) = text.split_at(text.find("end_header\n").unwrap() + 11);
+        assert!(header.contains("element vertex 3\n"));
+        assert!(header.contains("element face 1\n"));
+        assert!(header.contains("property list uchar int vertex_indices\n"));
+        assert_eq!(body.lines().nth(1), Some("1 0 1 255 0 0"));
+        assert_eq!(body.lines().last(), Some("3 0 1 2"));
+    }
+
+    #[test]
+    fn texture_coordinates_are_clamped() {
+        assert_eq!(texture_index(0.0, 640), 0);
+        assert_eq!(texture_index(0.5, 640), 320);
+        assert_eq!(texture_index(1.2, 640), 639);
+        assert_eq!(texture_index(-0.3, 640), 0);
+    }
+
+    /// Decompress LZF data, as PCL does when reading `binary_compressed` files.
+    fn lzf_decompress(input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            let control = input[i] as usize;
+            i += 1;
+            if control < LZF_MAX_LITERALS {
+                output.extend_from_slice(&input[i..i + control + 1]);
+                i += control + 1;
+            } else {
+                let mut length = control >> 5;
+                if length == 7 {
+                    length += input[i] as usize;
+                    i += 1;
+                }
+                let start = output.len() - ((control & 0x1F) << 8 | input[i] as usize) - 1;
+                i += 1;
+                for j in 0..length + 2 {
+                    output.push(output[start + j]);
+                }
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn lzf_round_trips() {
+        let mut data: Vec<u8> = (0..2000).map(|i| (i % 7) as u8).collect();
+        data.extend((0..300).map(|i| (i * 37 % 251) as u8));
+        data.extend_from_slice(&[9; 1000]);
+
+        let compressed = lzf_compress(&data);
+        assert!(compressed.len() < data.len() / 4);
+        assert_eq!(lzf_decompress(&compressed), data);
+        assert_eq!(lzf_decompress(&lzf_compress(&[1, 2])), vec![1, 2]);
+    }
+
+    #[test]
+    fn writes_organized_pcd() {
+        let colors = [[1, 2, 3]; 9];
+        let mut output = Vec::new();
+        write_pcd(&mut output, 3, 3, &GRID, Some(&colors), PcdFormat::Ascii).unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        let (header, body) = text.split_at(text.find("DATA ascii\n").unwrap() + 11);
+        assert!(header.contains("FIELDS x y z rgb\n"));
+        assert!(header.contains("WIDTH 3\nHEIGHT 3\n"));
+        assert!(header.contains("POINTS 9\n"));
+        assert_eq!(body.lines().next(), Some("-0.1 -0.1 1 66051"));
+        assert_eq!(body.lines().last(), Some("NaN NaN NaN 66051"));
+    }
+
+    #[test]
+    fn writes_compressed_pcd() {
+        let mut output = Vec::new();
+        write_pcd(&mut output, 9, 1, &GRID, None, PcdFormat::BinaryCompressed).unwrap();
+
+        let header_end = output
+            .windows(22)
+            .position(|w| w == b"DATA binary_compressed")
+            .unwrap()
+            + 23;
+        let data = &output[header_end..];
+        let compressed = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let uncompressed = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        assert_eq!(uncompressed, 9 * 3 * 4);
+        assert_eq!(compressed, data.len() - 8);
+
+        // Fields are stored one after another, so the first nine floats are the x coordinates.
+        let fields = lzf_decompress(&data[8..]);
+        let x: Vec<f32> = fields[..36]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(x[2], 0.1);
+        assert!(x[8].is_nan());
+    }
+
+    #[test]
+    fn writes_las() {
+        let mut output = Vec::new();
+        write_las(&mut output, &GRID, None).unwrap();
+
+        assert_eq!(&output[..4], b"LASF");
+        assert_eq!(&output[24..26], &[1, 4]);
+        assert_eq!(output[104], 7);
+        assert_eq!(output.len(), 375 + 8 * 36);
+
+        // Number of point records, and the first point's X in units of 0.1mm.
+        assert_eq!(u64::from_le_bytes(output[247..255].try_into().unwrap()), 8);
+        assert_eq!(
+            i32::from_le_bytes(output[375..379].try_into().unwrap()),
+            -1000
+        );
+        // Points without colors are white.
+        assert_eq!(&output[375 + 30..375 + 36], &[0xFF; 6]);
+    }
+
+    #[test]
+    fn computes_day_of_year() {
+        assert_eq!(day_of_year(0), (1, 1970));
+        assert_eq!(day_of_year(365), (1, 1971));
+        // 2000 was a leap year, 2100 will not be.
+        assert_eq!(day_of_year(11_323), (1, 2001));
+        assert_eq!(day_of_year(11_322), (366, 2000));
+        assert_eq!(day_of_year(47_846), (365, 2100));
+    }
+}
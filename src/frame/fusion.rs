@@ -0,0 +1,390 @@
+//! Types for fusing the point clouds of several cameras into a common coordinate frame.
+//!
+//! A [`CloudFusion`] is given the pose of each camera in a shared world frame, usually from an
+//! external calibration of the rig. Clouds are then pushed as they arrive from each camera, either
+//! directly or from a [`DepthFrame`] or [`PointsFrame`]. Whenever every camera has a cloud close
+//! enough in time, [`CloudFusion::fuse`] pairs them up by closest timestamp, moves them into the
+//! world frame, and merges them into one [`FusedPointCloud`] that remembers which camera each
+//! point came from.
+//!
+//! Timestamps are only comparable across devices if they share a time domain, e.g. by enabling
+//! [`GlobalTimeEnabled`](crate::kind::Rs2Option::GlobalTimeEnabled) on every device, or when
+//! playing back recordings that were captured together.
+
+use super::{
+    cloud::UnorganizedPointCloud,
+    image::{ColorFrame, DepthFrame},
+    points::PointsFrame,
+    prelude::FrameEx,
+};
+use crate::base::Rs2Extrinsics;
+use anyhow::Result;
+use std::collections::VecDeque;
+use thiserror::Error;
+
+/// The number of clouds kept per camera while waiting for the other cameras.
+const DEFAULT_QUEUE_LENGTH: usize = 8;
+
+/// Occurs when a cloud cannot be added for fusion.
+#[derive(Error, Debug)]
+pub enum FusionError {
+    /// The camera was not added to the fusion.
+    #[error("Unknown camera: {0}")]
+    UnknownCamera(usize),
+}
+
+/// A cloud waiting to be paired with the clouds of the other cameras.
+#[derive(Debug)]
+struct TimedCloud {
+    /// The timestamp of the cloud, in milliseconds.
+    timestamp: f64,
+    /// The points of the cloud, in the camera's coordinate frame.
+    cloud: UnorganizedPointCloud,
+}
+
+/// Pairs up and merges the point clouds of several cameras.
+#[derive(Debug)]
+pub struct CloudFusion {
+    /// The extrinsics from each camera to the world frame.
+    camera_to_world: Vec<Rs2Extrinsics>,
+    /// The clouds of each camera waiting to be fused, from oldest to newest.
+    queues: Vec<VecDeque<TimedCloud>>,
+    /// The largest difference between the timestamps of fused clouds, in milliseconds.
+    max_time_difference: f64,
+    /// The number of clouds kept per camera.
+    queue_length: usize,
+}
+
+impl CloudFusion {
+    /// Construct a fusion without any cameras.
+    ///
+    /// Clouds are only fused if their timestamps are at most `max_time_difference` milliseconds
+    /// apart. Half the frame interval of the cameras is usually a good choice.
+    pub fn new(max_time_difference: f64) -> Self {
+        CloudFusion {
+            camera_to_world: Vec::new(),
+            queues: Vec::new(),
+            max_time_difference,
+            queue_length: DEFAULT_QUEUE_LENGTH,
+        }
+    }
+
+    /// Set the number of clouds kept per camera while waiting for the other cameras.
+    ///
+    /// When a camera falls behind, its oldest clouds are dropped. Defaults to 8.
+    pub fn set_queue_length(&mut self, queue_length: usize) {
+        self.queue_length = queue_length.max(1);
+    }
+
+    /// Add a camera, given the extrinsics from its coordinate frame to the world frame.
+    ///
+    /// Returns the ID of the camera, which is used to push its clouds and is recorded for every
+    /// point it contributes to a fused cloud. IDs are assigned in order, starting at `0`.
+    pub fn add_camera(&mut self, camera_to_world: &Rs2Extrinsics) -> usize {
+        self.camera_to_world.push(*camera_to_world);
+        self.queues.push(VecDeque::new());
+        self.camera_to_world.len() - 1
+    }
+
+    /// Get the number of cameras.
+    pub fn camera_count(&self) -> usize {
+        self.camera_to_world.len()
+    }
+
+    /// Get the extrinsics from a camera's coordinate frame to the world frame.
+    pub fn camera_to_world(&self, camera: usize) -> Option<&Rs2Extrinsics> {
+        self.camera_to_world.get(camera)
+    }
+
+    /// Push a cloud from a camera, in the camera's coordinate frame, taken at `timestamp`
+    /// milliseconds.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FusionError::UnknownCamera`] if the camera was not added.
+    pub fn push(
+        &mut self,
+        camera: usize,
+        timestamp: f64,
+        cloud: UnorganizedPointCloud,
+    ) -> Result<(), FusionError> {
+        let queue_length = self.queue_length;
+        let queue = self
+            .queues
+            .get_mut(camera)
+            .ok_or(FusionError::UnknownCamera(camera))?;
+
+        queue.push_back(TimedCloud { timestamp, cloud });
+        while queue.len() > queue_length {
+            queue.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Deproject a depth frame and push the resulting cloud from a camera.
+    ///
+    /// The frame is deprojected with the intrinsics of its stream, and keeps its timestamp.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FusionError::UnknownCamera`] if the camera was not added.
+    ///
+    /// Returns an error if the frame cannot be deprojected; see
+    /// [`DepthFrame::to_points`](crate::frame::DepthFrame::to_points).
+    pub fn push_depth(&mut self, camera: usize, frame: &DepthFrame) -> Result<()> {
+        if camera >= self.queues.len() {
+            return Err(FusionError::UnknownCamera(camera).into());
+        }
+
+        let intrinsics = frame.stream_profile().intrinsics()?;
+        let cloud = frame.to_points(&intrinsics)?.into();
+        self.push(camera, frame.timestamp(), cloud)?;
+        Ok(())
+    }
+
+    /// Push the points of a points frame from a camera.
+    ///
+    /// Points are textured with `color` if given; see
+    /// [`PointsFrame::to_cloud`](crate::frame::PointsFrame::to_cloud).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FusionError::UnknownCamera`] if the camera was not added.
+    ///
+    /// Returns [`ExportError::UnsupportedColorFormat`](crate::frame::ExportError::UnsupportedColorFormat)
+    /// if the color frame is not in an 8-bit RGB or BGR format.
+    pub fn push_points(
+        &mut self,
+        camera: usize,
+        frame: &PointsFrame,
+        color: Option<&ColorFrame>,
+    ) -> Result<()> {
+        if camera >= self.queues.len() {
+            return Err(FusionError::UnknownCamera(camera).into());
+        }
+
+        let cloud = frame.to_cloud(color)?;
+        self.push(camera, frame.timestamp(), cloud)?;
+        Ok(())
+    }
+
+    /// Fuse the next set of clouds, one from each camera, into the world frame.
+    ///
+    /// Clouds are paired with the cloud of the first camera, by closest timestamp. Returns `None`
+    /// if some camera does not have a cloud close enough in time yet. Fused clouds, and any
+    /// clouds older than them, are removed from the queues; so are clouds of the first camera that
+    /// can no longer be paired.
+    pub fn fuse(&mut self) -> Option<FusedPointCloud> {
+        let selected = self.select()?;
+
+        let mut timestamps = Vec::with_capacity(selected.len());
+        let mut clouds = Vec::with_capacity(selected.len());
+        for (camera, index) in selected.into_iter().enumerate() {
+            let queue = &mut self.queues[camera];
+            queue.drain(..index);
+            let timed = queue.pop_front().expect("Selected cloud is in the queue.");
+            timestamps.push(timed.timestamp);
+            clouds.push(timed.cloud);
+        }
+
+        Some(merge(&clouds, &self.camera_to_world, timestamps))
+    }
+
+    /// Select the index of the cloud to fuse in each queue.
+    fn select(&mut self) -> Option<Vec<usize>> {
+        if self.queues.is_empty() {
+            return None;
+        }
+
+        'reference: loop {
+            let reference = self.queues[0].front()?.timestamp;
+            let mut selected = vec![0];
+
+            for queue in self.queues[1..].iter() {
+                let newest = queue.back()?.timestamp;
+                // A newer cloud could still be closer to the reference.
+                if newest < reference {
+                    return None;
+                }
+
+                let (index, difference) = queue
+                    .iter()
+                    .map(|timed| (timed.timestamp - reference).abs())
+                    .enumerate()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .expect("Queue is not empty.");
+
+                if difference > self.max_time_difference {
+                    // Every later cloud of this camera is even further away, so the reference
+                    // can never be paired.
+                    self.queues[0].pop_front();
+                    continue 'reference;
+                }
+                selected.push(index);
+            }
+
+            return Some(selected);
+        }
+    }
+}
+
+/// Move clouds into the world frame and merge them.
+fn merge(
+    clouds: &[UnorganizedPointCloud],
+    camera_to_world: &[Rs2Extrinsics],
+    timestamps: Vec<f64>,
+) -> FusedPointCloud {
+    let total = clouds.iter().map(|cloud| cloud.len()).sum();
+    let is_colored = clouds.iter().all(|cloud| cloud.colors().is_some());
+
+    let mut points = Vec::with_capacity(total);
+    let mut colors = Vec::with_capacity(if is_colored { total } else { 0 });
+    let mut sources = Vec::with_capacity(total);
+    for (camera, cloud) in clouds.iter().enumerate() {
+        let extrinsics = &camera_to_world[camera];
+        points.extend(
+            cloud
+                .points()
+                .iter()
+                .map(|p| extrinsics.transform_point(*p)),
+        );
+        if let Some(cloud_colors) = cloud.colors().filter(|_| is_colored) {
+            colors.extend_from_slice(cloud_colors);
+        }
+        sources.extend(std::iter::repeat_n(camera, cloud.len()));
+    }
+
+    FusedPointCloud {
+        cloud: UnorganizedPointCloud {
+            points,
+            colors: if is_colored { Some(colors) } else { None },
+        },
+        sources,
+        timestamps,
+    }
+}
+
+/// The merged point cloud of several cameras, in the world frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FusedPointCloud {
+    /// The merged points, with the points of each camera one after another.
+    cloud: UnorganizedPointCloud,
+    /// The camera each point came from.
+    sources: Vec<usize>,
+    /// The timestamp of the cloud of each camera, in milliseconds.
+    timestamps: Vec<f64>,
+}
+
+impl FusedPointCloud {
+    /// Get the merged cloud, in the world frame.
+    ///
+    /// The cloud is colored only if the clouds of every camera were colored.
+    pub fn cloud(&self) -> &UnorganizedPointCloud {
+        &self.cloud
+    }
+
+    /// Take ownership of the merged cloud.
+    pub fn into_cloud(self) -> UnorganizedPointCloud {
+        self.cloud
+    }
+
+    /// Get the ID of the camera each point came from.
+    pub fn sources(&self) -> &[usize] {
+        &self.sources
+    }
+
+    /// Get the timestamp of the cloud of each camera, in milliseconds, in order of camera ID.
+    pub fn timestamps(&self) -> &[f64] {
+        &self.timestamps
+    }
+
+    /// Get the average timestamp of the fused clouds, in milliseconds.
+    pub fn timestamp(&self) -> f64 {
+        self.timestamps.iter().sum::<f64>() / self.timestamps.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32) -> Rs2Extrinsics {
+        Rs2Extrinsics::new([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0], [x, 0.0, 0.0])
+    }
+
+    fn cloud(points: usize) -> UnorganizedPointCloud {
+        UnorganizedPointCloud::new(vec![[0.0, 0.0, 1.0]; points])
+    }
+
+    #[test]
+    fn fuses_clouds_into_world_frame() {
+        let mut fusion = CloudFusion::new(5.0);
+        let left = fusion.add_camera(&translation(-1.0));
+        let right = fusion.add_camera(&translation(1.0));
+
+        fusion.push(left, 100.0, cloud(2)).unwrap();
+        assert!(fusion.fuse().is_none());
+        fusion.push(right, 102.0, cloud(1)).unwrap();
+
+        let fused = fusion.fuse().unwrap();
+        assert_eq!(fused.sources(), &[0, 0, 1]);
+        assert_eq!(fused.timestamps(), &[100.0, 102.0]);
+        assert_eq!(fused.timestamp(), 101.0);
+        assert_eq!(fused.cloud().points()[0], [-1.0, 0.0, 1.0]);
+        assert_eq!(fused.cloud().points()[2], [1.0, 0.0, 1.0]);
+        assert!(fused.cloud().colors().is_none());
+        assert!(fusion.fuse().is_none());
+    }
+
+    #[test]
+    fn pairs_closest_timestamps() {
+        let mut fusion = CloudFusion::new(10.0);
+        let first = fusion.add_camera(&translation(0.0));
+        let second = fusion.add_camera(&translation(0.0));
+
+        fusion.push(first, 100.0, cloud(1)).unwrap();
+        fusion.push(first, 133.0, cloud(1)).unwrap();
+        for &timestamp in [90.0, 98.0, 108.0, 131.0, 165.0].iter() {
+            fusion.push(second, timestamp, cloud(1)).unwrap();
+        }
+
+        assert_eq!(fusion.fuse().unwrap().timestamps(), &[100.0, 98.0]);
+        assert_eq!(fusion.fuse().unwrap().timestamps(), &[133.0, 131.0]);
+        assert!(fusion.fuse().is_none());
+    }
+
+    #[test]
+    fn drops_clouds_that_cannot_be_paired() {
+        let mut fusion = CloudFusion::new(5.0);
+        let first = fusion.add_camera(&translation(0.0));
+        let second = fusion.add_camera(&translation(0.0));
+
+        // The second camera dropped the frame matching the first cloud.
+        fusion.push(first, 100.0, cloud(1)).unwrap();
+        fusion.push(first, 133.0, cloud(1)).unwrap();
+        fusion.push(second, 134.0, cloud(1)).unwrap();
+
+        assert_eq!(fusion.fuse().unwrap().timestamps(), &[133.0, 134.0]);
+        assert!(matches!(
+            fusion.push(2, 0.0, cloud(1)),
+            Err(FusionError::UnknownCamera(2))
+        ));
+    }
+
+    #[test]
+    fn keeps_colors_if_every_cloud_is_colored() {
+        let mut fusion = CloudFusion::new(5.0);
+        let first = fusion.add_camera(&translation(0.0));
+        let second = fusion.add_camera(&translation(0.0));
+
+        let colored = UnorganizedPointCloud::with_colors(vec![[0.0; 3]], vec![[1, 2, 3]]).unwrap();
+        fusion.push(first, 0.0, colored.clone()).unwrap();
+        fusion.push(second, 0.0, colored.clone()).unwrap();
+        let fused = fusion.fuse().unwrap();
+        assert_eq!(fused.cloud().colors(), Some(&[[1, 2, 3], [1, 2, 3]][..]));
+
+        fusion.push(first, 1.0, colored).unwrap();
+        fusion.push(second, 1.0, cloud(1)).unwrap();
+        assert!(fusion.fuse().unwrap().cloud().colors().is_none());
+    }
+}
@@ -5,18 +5,37 @@
 //! - Depth Frame: A depth frame taken from a synthetic depth camera.
 //! - Disparity Frame: A disparity frame taken from a synthetic depth camera.
 //! - Color Frame: A frame holding color or monochrome data.
+//! - Infrared Frame: A frame holding (usually monochrome) data from an infrared imager.
+//! - Fisheye Frame: A frame holding data from a wide-angle fisheye imager.
+//! - Confidence Frame: A frame holding the per-pixel confidence of a depth frame (e.g. on the
+//!   L500 series). See [`ConfidenceLevel`] for how confidence values are interpreted.
 //!
 //! Each frame type can hold data in multiple formats. The data type presented
 //! depends on the settings and flags used at runtime on the RealSense device.
 
-use super::pixel::{get_pixel, PixelKind};
+use super::confidence::{get_confidence, mask_depths, ConfidenceLevel};
+use super::correspondence::ColorDepthCorrespondence;
+use super::deprojection::{cached_table, DeprojectionTable, OrganizedPointCloud};
+use super::disparity::{
+    disparity_image, DepthImage, DisparityImage, StereoParameters, DISPARITY16_SUBPIXELS,
+};
+#[cfg(feature = "jpeg")]
+use super::mjpeg::{decode_rgb8, DecodedColorFrame, MjpegDecodeError};
+use super::pixel::{get_pixel, is_compressed, PixelKind};
 use super::prelude::{
     CouldNotGetFrameSensorError, DepthError, DisparityError, FrameCategory, FrameConstructionError,
     FrameEx, BITS_PER_BYTE,
 };
+use super::registration::{register_depth, register_to_depth, RegisteredImage};
+use super::sampling::{sample_depth, DepthSample, DepthSampling};
+use super::statistics::{roi_ranges, DepthHistogram, DepthStatistics};
+use super::undistortion::{Interpolate, UndistortError, UndistortionMap};
 use crate::{
+    base::{Rs2Extrinsics, Rs2Intrinsics, Rs2Roi},
     check_rs2_error,
-    kind::{Rs2Extension, Rs2FrameMetadata, Rs2Option, Rs2StreamKind, Rs2TimestampDomain},
+    kind::{
+        Rs2Extension, Rs2Format, Rs2FrameMetadata, Rs2Option, Rs2StreamKind, Rs2TimestampDomain,
+    },
     sensor::Sensor,
     stream_profile::StreamProfile,
 };
@@ -227,7 +246,11 @@ impl<K> TryFrom<NonNull<sys::rs2_frame>> for ImageFrame<K> {
             let size = sys::rs2_get_frame_data_size(frame_ptr.as_ptr(), &mut err);
             check_rs2_error!(err, FrameConstructionError::CouldNotGetDataSize)?;
 
-            debug_assert_eq!(size, width * height * bits_per_pixel / BITS_PER_BYTE);
+            // Compressed frames are variable-length, so their size is unrelated to their
+            // dimensions.
+            if !is_compressed(profile.format()) {
+                debug_assert_eq!(size, width * height * bits_per_pixel / BITS_PER_BYTE);
+            }
 
             let data_ptr = sys::rs2_get_frame_data(frame_ptr.as_ptr(), &mut err);
             check_rs2_error!(err, FrameConstructionError::CouldNotGetData)?;
@@ -446,6 +469,402 @@ impl DepthFrame {
         })?;
         Ok(depth_units)
     }
+
+    /// Compute summary statistics over the depth in this frame, in meters.
+    ///
+    /// If `roi` is provided, only the pixels inside that (inclusive) region of interest are
+    /// considered. Regions that extend past the edges of the frame are clamped to the frame.
+    ///
+    /// This is useful as a quick health check of a depth stream; e.g. a low
+    /// [`valid_ratio`](DepthStatistics::valid_ratio) is a good sign that the camera is occluded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn statistics(&self, roi: Option<&Rs2Roi>) -> Result<DepthStatistics> {
+        Ok(DepthStatistics::from_depths(self.depths_in_region(roi)?))
+    }
+
+    /// Compute a histogram of the depth in this frame, with `bins` bins spanning `[min_depth,
+    /// max_depth]` meters.
+    ///
+    /// If `roi` is provided, only the pixels inside that (inclusive) region of interest are
+    /// considered. Regions that extend past the edges of the frame are clamped to the frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`HistogramError`](crate::frame::HistogramError) if the histogram configuration
+    /// is invalid.
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn histogram(
+        &self,
+        min_depth: f32,
+        max_depth: f32,
+        bins: usize,
+        roi: Option<&Rs2Roi>,
+    ) -> Result<DepthHistogram> {
+        let mut histogram = DepthHistogram::new(min_depth, max_depth, bins)?;
+        histogram.extend(self.depths_in_region(roi)?);
+        Ok(histogram)
+    }
+
+    /// Mask the depth in this frame by the per-pixel confidence in `confidence`.
+    ///
+    /// Returns the depth of every pixel in meters, in row-major order, with any pixel whose
+    /// confidence is below `threshold` set to zero (i.e. marked as invalid depth). The result can be
+    /// passed directly to [`DepthStatistics::from_depths`] or [`DepthHistogram`].
+    ///
+    /// The confidence frame must come from the same frameset as this frame, so that both frames
+    /// describe the same pixels.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::MismatchedResolution`] if the two frames do not have the same width
+    /// and height.
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if this frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn mask_by_confidence(
+        &self,
+        confidence: &ConfidenceFrame,
+        threshold: ConfidenceLevel,
+    ) -> Result<Vec<f32>> {
+        if self.width != confidence.width || self.height != confidence.height {
+            return Err(DepthError::MismatchedResolution(
+                self.width,
+                self.height,
+                confidence.width,
+                confidence.height,
+            )
+            .into());
+        }
+
+        Ok(mask_depths(
+            self.depths_in_region(None)?,
+            confidence.iter_confidence(),
+            threshold,
+        ))
+    }
+
+    /// Sample the depth at a sub-pixel coordinate `(x, y)`, in meters.
+    ///
+    /// Pixel coordinates follow the librealsense2 convention: the center of the pixel at column
+    /// `c` and row `r` lies at exactly `(c, r)`. The `mode` determines how depth is computed from
+    /// the pixels around the coordinate; see [`DepthSampling`] for details. In every mode, pixels
+    /// with zero (invalid) depth are ignored, and the returned [`DepthSample`] is marked invalid if
+    /// no valid depth could be found at all.
+    ///
+    /// Coordinates outside of the frame, i.e. outside of `[-0.5, width - 0.5)` horizontally or
+    /// `[-0.5, height - 0.5)` vertically, always produce an invalid sample.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn distance_at(&self, x: f32, y: f32, mode: DepthSampling) -> Result<DepthSample> {
+        let depth_at = self.depth_reader()?;
+        Ok(sample_depth(self.width, self.height, depth_at, x, y, mode))
+    }
+
+    /// Find the pixel in this frame that corresponds to the pixel `pixel` in a color frame.
+    ///
+    /// The search only considers depths between `min_depth` and `max_depth` meters; a tighter
+    /// range makes the search faster and less likely to pick up an occluding surface. See
+    /// [`ColorDepthCorrespondence`] for how the search works.
+    ///
+    /// Returns `None` if none of the pixels searched hold a valid depth.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn color_pixel_to_depth_pixel(
+        &self,
+        correspondence: &ColorDepthCorrespondence,
+        pixel: [f32; 2],
+        min_depth: f32,
+        max_depth: f32,
+    ) -> Result<Option<[f32; 2]>> {
+        let depth_at = self.depth_reader()?;
+        Ok(correspondence.depth_pixel(
+            self.width,
+            self.height,
+            depth_at,
+            pixel,
+            min_depth,
+            max_depth,
+        ))
+    }
+
+    /// Find the pixels in this frame that correspond to each of `pixels` in a color frame.
+    ///
+    /// This behaves like [`DepthFrame::color_pixel_to_depth_pixel`] for every pixel, but only
+    /// queries the depth units of the frame once.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn color_pixels_to_depth_pixels(
+        &self,
+        correspondence: &ColorDepthCorrespondence,
+        pixels: &[[f32; 2]],
+        min_depth: f32,
+        max_depth: f32,
+    ) -> Result<Vec<Option<[f32; 2]>>> {
+        let depth_at = self.depth_reader()?;
+        Ok(pixels
+            .iter()
+            .map(|&pixel| {
+                correspondence.depth_pixel(
+                    self.width,
+                    self.height,
+                    &depth_at,
+                    pixel,
+                    min_depth,
+                    max_depth,
+                )
+            })
+            .collect())
+    }
+
+    /// Deproject every pixel of this frame into an organized point cloud, in meters.
+    ///
+    /// The resulting cloud has one point per pixel, in the coordinate frame of the depth stream;
+    /// pixels without a valid depth produce a point whose coordinates are all NaN. Rather than
+    /// deprojecting each pixel from scratch, this uses a [`DeprojectionTable`] computed from
+    /// `intrinsics`. The most recently used tables are cached, so the table is only computed the
+    /// first time a given set of intrinsics is seen.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::MismatchedIntrinsics`] if `intrinsics` are not for an image of the
+    /// same width and height as this frame.
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn to_points(&self, intrinsics: &Rs2Intrinsics) -> Result<OrganizedPointCloud> {
+        if self.width != intrinsics.width() || self.height != intrinsics.height() {
+            return Err(DepthError::MismatchedIntrinsics(
+                self.width,
+                self.height,
+                intrinsics.width(),
+                intrinsics.height(),
+            )
+            .into());
+        }
+
+        self.to_points_with_table(&cached_table(intrinsics))
+    }
+
+    /// Deproject every pixel of this frame into an organized point cloud, in meters, using a
+    /// precomputed table of rays.
+    ///
+    /// This behaves like [`DepthFrame::to_points`], but lets you manage the lifetime of the table
+    /// yourself.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::MismatchedIntrinsics`] if `table` is not for an image of the same
+    /// width and height as this frame.
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn to_points_with_table(&self, table: &DeprojectionTable) -> Result<OrganizedPointCloud> {
+        if self.width != table.width() || self.height != table.height() {
+            return Err(DepthError::MismatchedIntrinsics(
+                self.width,
+                self.height,
+                table.width(),
+                table.height(),
+            )
+            .into());
+        }
+
+        let depth_at = self.depth_reader()?;
+        Ok(table.deproject(depth_at))
+    }
+
+    /// Register this frame into the geometry of a target camera, producing a depth image (in
+    /// meters) as seen from that camera.
+    ///
+    /// `target_intrinsics` describe the target camera, and `depth_to_target` the extrinsics from
+    /// this frame's stream to the target camera. The target does not need to be a RealSense
+    /// stream. Each depth pixel is deprojected, transformed into the target camera, and fills
+    /// every target pixel its footprint covers. Where pixels overlap, the nearest depth wins, so
+    /// that foreground objects correctly occlude the background.
+    ///
+    /// Depths in the registered image are measured along the target camera's Z axis. Target
+    /// pixels that no depth pixel lands on are zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DataError`](crate::stream_profile::DataError) if the intrinsics of this frame's
+    /// stream cannot be retrieved.
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn register_depth_to(
+        &self,
+        target_intrinsics: &Rs2Intrinsics,
+        depth_to_target: &Rs2Extrinsics,
+    ) -> Result<RegisteredImage<f32>> {
+        let depth_intrinsics = self.frame_stream_profile.intrinsics()?;
+        let depth_at = self.depth_reader()?;
+
+        Ok(register_depth(
+            self.width,
+            self.height,
+            depth_at,
+            &depth_intrinsics,
+            target_intrinsics,
+            depth_to_target,
+        ))
+    }
+
+    /// Resample an image from another camera into the geometry of this frame.
+    ///
+    /// This is the reverse of [`DepthFrame::register_depth_to`], and is typically used to map
+    /// color onto depth. `source_intrinsics` describe the source camera, `depth_to_source` the
+    /// extrinsics from this frame's stream to the source camera, and `source_at` returns the
+    /// pixel of the source image at a given column and row.
+    ///
+    /// Each depth pixel takes the value of the source pixel nearest to where it projects. Depth
+    /// pixels without a valid depth, or that project outside of the source image, are `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DataError`](crate::stream_profile::DataError) if the intrinsics of this frame's
+    /// stream cannot be retrieved.
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns an error if the depth units of the frame's sensor cannot be retrieved.
+    pub fn register_image_to_depth<T, F>(
+        &self,
+        source_intrinsics: &Rs2Intrinsics,
+        depth_to_source: &Rs2Extrinsics,
+        source_at: F,
+    ) -> Result<RegisteredImage<Option<T>>>
+    where
+        F: Fn(usize, usize) -> T,
+    {
+        let table = cached_table(&self.frame_stream_profile.intrinsics()?);
+        if self.width != table.width() || self.height != table.height() {
+            return Err(DepthError::MismatchedIntrinsics(
+                self.width,
+                self.height,
+                table.width(),
+                table.height(),
+            )
+            .into());
+        }
+
+        let depth_at = self.depth_reader()?;
+        Ok(register_to_depth(
+            &table,
+            depth_at,
+            source_intrinsics,
+            depth_to_source,
+            source_at,
+        ))
+    }
+
+    /// Iterate over the depth of every pixel in a region of interest, in meters.
+    ///
+    /// Unlike [`DepthFrame::distance`], this only queries the depth units once and then reads the
+    /// frame data directly.
+    fn depths_in_region(&self, roi: Option<&Rs2Roi>) -> Result<impl Iterator<Item = f32> + '_> {
+        let depth_at = self.depth_reader()?;
+        let (cols, rows) = roi_ranges(roi, self.width, self.height);
+
+        Ok(rows
+            .flat_map(move |row| cols.clone().map(move |col| (col, row)))
+            .map(move |(col, row)| depth_at(col, row)))
+    }
+
+    /// Get the baseline of the stereo imagers that produced this frame, in millimeters.
+    pub fn baseline(&self) -> Result<f32, DisparityError> {
+        unsafe {
+            let mut err = ptr::null_mut::<sys::rs2_error>();
+            let baseline =
+                sys::rs2_depth_stereo_frame_get_baseline(self.frame_ptr.as_ptr(), &mut err);
+            check_rs2_error!(err, DisparityError)?;
+            Ok(baseline)
+        }
+    }
+
+    /// Convert this frame into an owned disparity image, in pixels.
+    ///
+    /// The conversion uses the baseline of the frame, the horizontal focal length of the depth
+    /// stream intrinsics, and the depth units of the sensor. `disparity_shift` is the disparity
+    /// shift (in pixels) configured on the device, which is subtracted from every disparity; pass
+    /// `0.0` to get the disparity in the same convention as the SDK's `DisparityFilter`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Z16`](Rs2Format::Z16) or [`Distance`](Rs2Format::Distance) format.
+    ///
+    /// Returns [`DisparityError`] if the frame was not produced by a stereo depth sensor.
+    ///
+    /// Returns an error if the intrinsics of the stream or the depth units of the frame's sensor
+    /// cannot be retrieved.
+    pub fn to_disparity(&self, disparity_shift: f32) -> Result<DisparityImage> {
+        let parameters = StereoParameters::new(
+            self.frame_stream_profile.intrinsics()?.fx(),
+            self.baseline()?.abs() / 1000.0,
+            disparity_shift,
+        );
+        let depth_at = self.depth_reader()?;
+
+        Ok(disparity_image(
+            self.width,
+            self.height,
+            depth_at,
+            parameters,
+        ))
+    }
+
+    /// Get a function that reads the depth of the pixel at a column and row, in meters.
+    ///
+    /// The depth units are queried once up front, so that the returned function only reads the
+    /// frame data. The returned function does not check that the column and row are in bounds.
+    pub(crate) fn depth_reader(&self) -> Result<impl Fn(usize, usize) -> f32 + '_> {
+        let depth_units = match self.frame_stream_profile.format() {
+            Rs2Format::Z16 => self.depth_units()?,
+            Rs2Format::Distance => 1.0,
+            format => return Err(DepthError::UnsupportedFormat(format).into()),
+        };
+
+        Ok(move |col, row| match self.get_unchecked(col, row) {
+            PixelKind::Z16 { depth } => *depth as f32 * depth_units,
+            PixelKind::Distance { distance } => *distance,
+            _ => unreachable!("Depth format was checked before reading."),
+        })
+    }
 }
 
 impl DisparityFrame {
@@ -493,6 +912,223 @@ impl DisparityFrame {
             Ok(baseline)
         }
     }
+
+    /// Convert this frame into an owned depth image, in meters.
+    ///
+    /// The conversion uses the baseline of the frame and the horizontal focal length of the
+    /// stream intrinsics. [`Disparity16`](Rs2Format::Disparity16) frames are read as fixed-point
+    /// disparities with 1/32 pixel precision. `disparity_shift` is the disparity shift (in pixels)
+    /// configured on the device, which is added to every disparity before conversion; pass `0.0`
+    /// for frames produced by the SDK's `DisparityFilter`.
+    ///
+    /// Use [`DepthImage::to_z16`] to quantize the result with the depth units of the sensor.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DepthError::UnsupportedFormat`] if the frame is not in the
+    /// [`Disparity16`](Rs2Format::Disparity16) or [`Disparity32`](Rs2Format::Disparity32)
+    /// format.
+    ///
+    /// Returns [`DisparityError`] if the baseline of the frame cannot be retrieved.
+    ///
+    /// Returns an error if the intrinsics of the stream cannot be retrieved.
+    pub fn to_depth(&self, disparity_shift: f32) -> Result<DepthImage> {
+        match self.frame_stream_profile.format() {
+            Rs2Format::Disparity16 | Rs2Format::Disparity32 => {}
+            format => return Err(DepthError::UnsupportedFormat(format).into()),
+        }
+
+        let parameters = StereoParameters::new(
+            self.frame_stream_profile.intrinsics()?.fx(),
+            self.baseline()?.abs() / 1000.0,
+            disparity_shift,
+        );
+
+        let mut disparities = Vec::with_capacity(self.width * self.height);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                disparities.push(match self.get_unchecked(col, row) {
+                    PixelKind::Disparity16 { disparity } => {
+                        *disparity as f32 / DISPARITY16_SUBPIXELS
+                    }
+                    PixelKind::Disparity32 { disparity } => *disparity,
+                    _ => unreachable!("Disparity format was checked before reading."),
+                });
+            }
+        }
+
+        Ok(DisparityImage {
+            width: self.width,
+            height: self.height,
+            disparities,
+            parameters,
+        }
+        .to_depth())
+    }
+}
+
+impl ConfidenceFrame {
+    /// Get the confidence of a pixel in this frame.
+    ///
+    /// Unlike [`ImageFrame::get_unchecked`], this understands both the 8-bit and the packed 4-bit
+    /// confidence representations.
+    #[inline(always)]
+    pub fn confidence_unchecked(&self, col: usize, row: usize) -> ConfidenceLevel {
+        unsafe {
+            get_confidence(
+                self.data.as_ptr(),
+                self.data_size_in_bytes,
+                self.stride,
+                self.bits_per_pixel,
+                col,
+                row,
+            )
+        }
+    }
+
+    /// Given a row and column index, get the confidence of a pixel in this frame.
+    pub fn confidence(&self, col: usize, row: usize) -> Option<ConfidenceLevel> {
+        if col >= self.width || row >= self.height {
+            None
+        } else {
+            Some(self.confidence_unchecked(col, row))
+        }
+    }
+
+    /// Row-major iterator over the confidence of every pixel in this frame.
+    pub fn iter_confidence(&self) -> impl Iterator<Item = ConfidenceLevel> + '_ {
+        (0..self.height).flat_map(move |row| {
+            (0..self.width).map(move |col| self.confidence_unchecked(col, row))
+        })
+    }
+}
+
+#[cfg(feature = "jpeg")]
+impl ColorFrame {
+    /// Decode a color frame streamed as [`Mjpeg`](Rs2Format::Mjpeg) into an owned RGB8 image.
+    ///
+    /// The timestamp, frame number, supported metadata and stream intrinsics of this frame are
+    /// copied into the decoded frame, so it can be used after this frame has been dropped.
+    ///
+    /// Requires the `jpeg` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MjpegDecodeError::NotMjpeg`] if this frame is not in the MJPEG format.
+    ///
+    /// Returns [`MjpegDecodeError::CouldNotDecode`] if the compressed data is corrupt.
+    ///
+    /// Returns [`MjpegDecodeError::UnsupportedPixelFormat`] if the data decodes to something
+    /// other than color or grayscale.
+    pub fn decode_mjpeg(&self) -> Result<DecodedColorFrame, MjpegDecodeError> {
+        let format = self.frame_stream_profile.format();
+        if format != Rs2Format::Mjpeg {
+            return Err(MjpegDecodeError::NotMjpeg(format));
+        }
+
+        let jpeg = unsafe {
+            std::slice::from_raw_parts(self.data.as_ptr().cast::<u8>(), self.data_size_in_bytes)
+        };
+        let (width, height, data) = decode_rgb8(jpeg)?;
+
+        let metadata = (0..sys::rs2_frame_metadata_value_RS2_FRAME_METADATA_COUNT as i32)
+            .filter_map(Rs2FrameMetadata::from_i32)
+            .filter_map(|kind| self.metadata(kind).map(|value| (kind, value)))
+            .collect();
+
+        Ok(DecodedColorFrame {
+            width,
+            height,
+            data,
+            timestamp: self.timestamp,
+            timestamp_domain: self.timestamp_domain,
+            frame_number: self.frame_number,
+            metadata,
+            intrinsics: self.frame_stream_profile.intrinsics().ok(),
+        })
+    }
+}
+
+impl ColorFrame {
+    /// Remap this frame through an undistortion map into an undistorted (or rectified) RGB image.
+    ///
+    /// Pixels are sampled with bilinear interpolation. Target pixels that do not see anything in
+    /// this frame are black.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UndistortError::UnsupportedFormat`] if this frame is not in one of the
+    /// [`Rgb8`](Rs2Format::Rgb8), [`Bgr8`](Rs2Format::Bgr8), [`Rgba8`](Rs2Format::Rgba8) or
+    /// [`Bgra8`](Rs2Format::Bgra8) formats.
+    ///
+    /// Returns [`UndistortError::MismatchedResolution`] if the resolution of this frame does not
+    /// match the source resolution of the map.
+    pub fn undistort(
+        &self,
+        map: &UndistortionMap,
+    ) -> Result<RegisteredImage<[u8; 3]>, UndistortError> {
+        let rgb_at = self
+            .rgb_reader()
+            .ok_or_else(|| UndistortError::UnsupportedFormat(self.frame_stream_profile.format()))?;
+
+        self.remap(map, rgb_at)
+    }
+
+    /// Get a function reading the `[r, g, b]` value of a pixel, or `None` if the frame is not in
+    /// an 8-bit RGB or BGR format.
+    pub(crate) fn rgb_reader(&self) -> Option<impl Fn(usize, usize) -> [u8; 3] + '_> {
+        match self.frame_stream_profile.format() {
+            Rs2Format::Rgb8 | Rs2Format::Bgr8 | Rs2Format::Rgba8 | Rs2Format::Bgra8 => {}
+            _ => return None,
+        }
+
+        Some(move |col, row| match self.get_unchecked(col, row) {
+            PixelKind::Rgb8 { r, g, b }
+            | PixelKind::Bgr8 { b, g, r }
+            | PixelKind::Rgba8 { r, g, b, .. }
+            | PixelKind::Bgra8 { b, g, r, .. } => [*r, *g, *b],
+            _ => unreachable!("Color format was checked before reading."),
+        })
+    }
+}
+
+impl InfraredFrame {
+    /// Remap this frame through an undistortion map into an undistorted (or rectified) image.
+    ///
+    /// Pixels are sampled with bilinear interpolation. Target pixels that do not see anything in
+    /// this frame are black.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UndistortError::UnsupportedFormat`] if this frame is not in the
+    /// [`Y8`](Rs2Format::Y8) format.
+    ///
+    /// Returns [`UndistortError::MismatchedResolution`] if the resolution of this frame does not
+    /// match the source resolution of the map.
+    pub fn undistort(&self, map: &UndistortionMap) -> Result<RegisteredImage<u8>, UndistortError> {
+        self.remap_y8(map)
+    }
+}
+
+impl FisheyeFrame {
+    /// Remap this frame through an undistortion map into an undistorted (or rectified) pinhole
+    /// image.
+    ///
+    /// Pixels are sampled with bilinear interpolation. Target pixels that do not see anything in
+    /// this frame are black. For the T265 fisheye pair, see
+    /// [`StereoRectification`](crate::frame::StereoRectification) to build maps that rectify both
+    /// images.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UndistortError::UnsupportedFormat`] if this frame is not in the
+    /// [`Y8`](Rs2Format::Y8) format.
+    ///
+    /// Returns [`UndistortError::MismatchedResolution`] if the resolution of this frame does not
+    /// match the source resolution of the map.
+    pub fn undistort(&self, map: &UndistortionMap) -> Result<RegisteredImage<u8>, UndistortError> {
+        self.remap_y8(map)
+    }
 }
 
 impl<K> ImageFrame<K> {
@@ -570,6 +1206,41 @@ impl<K> ImageFrame<K> {
             Some(self.get_unchecked(col, row))
         }
     }
+
+    /// Remap a Y8 frame through an undistortion map.
+    fn remap_y8(&self, map: &UndistortionMap) -> Result<RegisteredImage<u8>, UndistortError> {
+        let format = self.frame_stream_profile.format();
+        if format != Rs2Format::Y8 {
+            return Err(UndistortError::UnsupportedFormat(format));
+        }
+
+        self.remap(map, |col, row| match self.get_unchecked(col, row) {
+            PixelKind::Y8 { y } => *y,
+            _ => unreachable!(),
+        })
+    }
+
+    /// Remap this frame through an undistortion map, reading each pixel with `pixel_at`.
+    fn remap<T, F>(
+        &self,
+        map: &UndistortionMap,
+        pixel_at: F,
+    ) -> Result<RegisteredImage<T>, UndistortError>
+    where
+        T: Interpolate,
+        F: Fn(usize, usize) -> T,
+    {
+        if self.width != map.source_width() || self.height != map.source_height() {
+            return Err(UndistortError::MismatchedResolution(
+                self.width,
+                self.height,
+                map.source_width(),
+                map.source_height(),
+            ));
+        }
+
+        Ok(map.remap(pixel_at))
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,497 @@
+//! Types for combining accelerometer and gyroscope frames into synchronized IMU samples.
+//!
+//! Devices like the D435i stream [`AccelFrame`]s and [`GyroFrame`]s separately, at different
+//! rates (e.g. 250Hz and 400Hz). Most consumers of IMU data (orientation filters, visual-inertial
+//! odometry) want both measurements at the same instant instead. An [`ImuStream`] collects the
+//! frames of both streams as they arrive, and resamples the accelerometer onto the timestamps of
+//! the gyroscope to produce [`ImuSample`]s.
+//!
+//! Frames can be pushed one at a time, taken from the [`CompositeFrame`]s of a pipeline, or
+//! waited for directly on an [`ActivePipeline`] with [`ImuStream::wait`]. Frames that are
+//! delivered more than once (as pipelines may do for motion streams) are only used once. When
+//! frames of either stream are dropped, or a stream restarts (e.g. after the pipeline is
+//! restarted), the stream records an [`ImuGap`].
+
+use super::{
+    composite::CompositeFrame,
+    motion::{AccelFrame, GyroFrame},
+    prelude::FrameEx,
+};
+use crate::{
+    kind::Rs2StreamKind,
+    pipeline::{ActivePipeline, FrameWaitError},
+};
+use std::{collections::VecDeque, time::Duration};
+
+/// The number of measurements of each stream kept while waiting for the other stream.
+const DEFAULT_QUEUE_LENGTH: usize = 64;
+
+/// How accelerometer measurements are resampled onto gyroscope timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImuInterpolation {
+    /// Interpolate linearly between the accelerometer measurements on either side of each
+    /// gyroscope measurement.
+    ///
+    /// Samples are delayed until the next accelerometer measurement arrives.
+    Linear,
+    /// Use the latest accelerometer measurement at or before each gyroscope measurement.
+    ///
+    /// Samples are produced as soon as the gyroscope measurement arrives.
+    CopyLast,
+}
+
+/// An accelerometer and gyroscope measurement at the same instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImuSample {
+    /// The timestamp of the sample (i.e. of the gyroscope measurement), in milliseconds.
+    pub timestamp: f64,
+    /// The acceleration, in m/s^2. See [`AccelFrame::acceleration`].
+    pub accel: [f32; 3],
+    /// The rotational velocity, in radians/s. See [`GyroFrame::rotational_velocity`].
+    pub gyro: [f32; 3],
+    /// The frame number of the latest accelerometer frame at or before the sample.
+    pub accel_frame_number: u64,
+    /// The frame number of the gyroscope frame.
+    pub gyro_frame_number: u64,
+}
+
+/// Frames of a motion stream that were dropped before reaching the [`ImuStream`], or a restart of
+/// the stream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImuGap {
+    /// The stream that dropped frames, either [`Accel`](Rs2StreamKind::Accel) or
+    /// [`Gyro`](Rs2StreamKind::Gyro).
+    pub stream: Rs2StreamKind,
+    /// The frame number of the last frame received before the gap.
+    pub last_frame_number: u64,
+    /// The frame number of the first frame received after the gap.
+    pub frame_number: u64,
+    /// The time between the frames on either side of the gap, in milliseconds.
+    ///
+    /// This may be negative if the stream restarted.
+    pub duration: f64,
+    /// Whether the stream restarted, i.e. its frame number or timestamp went backwards.
+    ///
+    /// Measurements of both streams that were waiting to be resampled are discarded on a restart.
+    pub restart: bool,
+}
+
+impl ImuGap {
+    /// Get the number of frames that were dropped.
+    ///
+    /// This is zero if the stream restarted, as the number of dropped frames is unknown.
+    pub fn dropped_frames(&self) -> u64 {
+        if self.restart {
+            0
+        } else {
+            self.frame_number
+                .saturating_sub(self.last_frame_number)
+                .saturating_sub(1)
+        }
+    }
+}
+
+/// A measurement of one of the motion streams.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Measurement {
+    /// The timestamp of the measurement, in milliseconds.
+    timestamp: f64,
+    /// The frame number of the measurement.
+    frame_number: u64,
+    /// The measured 3-vector.
+    motion: [f32; 3],
+}
+
+/// Combines the accelerometer and gyroscope streams of a device into [`ImuSample`]s.
+#[derive(Debug)]
+pub struct ImuStream {
+    /// How accelerometer measurements are resampled.
+    interpolation: ImuInterpolation,
+    /// Recent accelerometer measurements, from oldest to newest.
+    accel: VecDeque<Measurement>,
+    /// Gyroscope measurements waiting for accelerometer measurements, from oldest to newest.
+    gyro: VecDeque<Measurement>,
+    /// The last accelerometer measurement received.
+    last_accel: Option<Measurement>,
+    /// The last gyroscope measurement received.
+    last_gyro: Option<Measurement>,
+    /// Samples ready to be taken, from oldest to newest.
+    samples: VecDeque<ImuSample>,
+    /// Gaps recorded since they were last taken.
+    gaps: Vec<ImuGap>,
+    /// The number of measurements kept in each of `accel` and `gyro`.
+    queue_length: usize,
+}
+
+impl ImuStream {
+    /// Construct an empty stream that resamples accelerometer measurements with `interpolation`.
+    pub fn new(interpolation: ImuInterpolation) -> Self {
+        ImuStream {
+            interpolation,
+            accel: VecDeque::new(),
+            gyro: VecDeque::new(),
+            last_accel: None,
+            last_gyro: None,
+            samples: VecDeque::new(),
+            gaps: Vec::new(),
+            queue_length: DEFAULT_QUEUE_LENGTH,
+        }
+    }
+
+    /// Set the number of measurements of each stream kept while waiting for the other stream.
+    ///
+    /// When one stream stalls or is not streamed at all, the oldest measurements of the other
+    /// stream are dropped. Defaults to 64, and is at least 2 so that accelerometer measurements
+    /// can be interpolated.
+    pub fn set_queue_length(&mut self, queue_length: usize) {
+        self.queue_length = queue_length.max(2);
+        Self::truncate(&mut self.accel, self.queue_length);
+        Self::truncate(&mut self.gyro, self.queue_length);
+    }
+
+    /// Discard every measurement, sample and gap, as if the stream was just constructed.
+    ///
+    /// Restarts are also detected when frame numbers or timestamps go backwards, but calling this
+    /// after restarting the pipeline avoids mixing samples from before and after the restart.
+    pub fn reset(&mut self) {
+        self.accel.clear();
+        self.gyro.clear();
+        self.last_accel = None;
+        self.last_gyro = None;
+        self.samples.clear();
+        self.gaps.clear();
+    }
+
+    /// Get how accelerometer measurements are resampled.
+    pub fn interpolation(&self) -> ImuInterpolation {
+        self.interpolation
+    }
+
+    /// Push an accelerometer frame.
+    pub fn push_accel(&mut self, frame: &AccelFrame) {
+        self.push_accel_measurement(
+            frame.timestamp(),
+            frame.frame_number(),
+            *frame.acceleration(),
+        );
+    }
+
+    /// Push a gyroscope frame.
+    pub fn push_gyro(&mut self, frame: &GyroFrame) {
+        self.push_gyro_measurement(
+            frame.timestamp(),
+            frame.frame_number(),
+            *frame.rotational_velocity(),
+        );
+    }
+
+    /// Push every accelerometer and gyroscope frame in a composite frame.
+    pub fn push_frames(&mut self, frames: &CompositeFrame) {
+        for frame in frames.frames_of_type::<AccelFrame>() {
+            self.push_accel(&frame);
+        }
+        for frame in frames.frames_of_type::<GyroFrame>() {
+            self.push_gyro(&frame);
+        }
+    }
+
+    /// Take the oldest sample that is ready, if any.
+    pub fn next_sample(&mut self) -> Option<ImuSample> {
+        self.samples.pop_front()
+    }
+
+    /// Take the gaps recorded since they were last taken, from oldest to newest.
+    pub fn take_gaps(&mut self) -> Vec<ImuGap> {
+        std::mem::take(&mut self.gaps)
+    }
+
+    /// Wait on a pipeline until the next sample is ready.
+    ///
+    /// Each call to [`ActivePipeline::wait`] is given `timeout_ms`. Frames of other streams in the
+    /// pipeline are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if waiting on the pipeline fails; see [`ActivePipeline::wait`].
+    pub fn wait(
+        &mut self,
+        pipeline: &mut ActivePipeline,
+        timeout_ms: Option<Duration>,
+    ) -> Result<ImuSample, FrameWaitError> {
+        loop {
+            if let Some(sample) = self.next_sample() {
+                return Ok(sample);
+            }
+            let frames = pipeline.wait(timeout_ms)?;
+            self.push_frames(&frames);
+        }
+    }
+
+    /// Push an accelerometer measurement, in m/s^2.
+    pub(crate) fn push_accel_measurement(
+        &mut self,
+        timestamp: f64,
+        frame_number: u64,
+        motion: [f32; 3],
+    ) {
+        let measurement = Measurement {
+            timestamp,
+            frame_number,
+            motion,
+        };
+        if !self.record(Rs2StreamKind::Accel, measurement) {
+            return;
+        }
+
+        self.accel.push_back(measurement);
+        Self::truncate(&mut self.accel, self.queue_length);
+        self.resample();
+    }
+
+    /// Push a gyroscope measurement, in radians/s.
+    pub(crate) fn push_gyro_measurement(
+        &mut self,
+        timestamp: f64,
+        frame_number: u64,
+        motion: [f32; 3],
+    ) {
+        let measurement = Measurement {
+            timestamp,
+            frame_number,
+            motion,
+        };
+        if !self.record(Rs2StreamKind::Gyro, measurement) {
+            return;
+        }
+
+        self.gyro.push_back(measurement);
+        Self::truncate(&mut self.gyro, self.queue_length);
+        self.resample();
+    }
+
+    /// Record a measurement as the last one of its stream, noting any gap since the previous one.
+    ///
+    /// If the frame number or timestamp went backwards, the stream restarted: the measurements
+    /// waiting to be resampled belong to the old timeline, so they are discarded along with the
+    /// last measurement of both streams.
+    ///
+    /// Returns `false` if the measurement was already received.
+    fn record(&mut self, stream: Rs2StreamKind, measurement: Measurement) -> bool {
+        let last = match stream {
+            Rs2StreamKind::Accel => self.last_accel,
+            _ => self.last_gyro,
+        };
+
+        if let Some(previous) = last {
+            if measurement.frame_number == previous.frame_number
+                && measurement.timestamp == previous.timestamp
+            {
+                return false;
+            }
+
+            let restart = measurement.frame_number <= previous.frame_number
+                || measurement.timestamp <= previous.timestamp;
+            if restart || measurement.frame_number > previous.frame_number + 1 {
+                self.gaps.push(ImuGap {
+                    stream,
+                    last_frame_number: previous.frame_number,
+                    frame_number: measurement.frame_number,
+                    duration: measurement.timestamp - previous.timestamp,
+                    restart,
+                });
+            }
+
+            if restart {
+                self.accel.clear();
+                self.gyro.clear();
+                self.last_accel = None;
+                self.last_gyro = None;
+            }
+        }
+
+        match stream {
+            Rs2StreamKind::Accel => self.last_accel = Some(measurement),
+            _ => self.last_gyro = Some(measurement),
+        }
+        true
+    }
+
+    /// Drop the oldest measurements of a queue until it holds at most `queue_length`.
+    fn truncate(queue: &mut VecDeque<Measurement>, queue_length: usize) {
+        while queue.len() > queue_length {
+            queue.pop_front();
+        }
+    }
+
+    /// Turn every gyroscope measurement that can be resampled into a sample.
+    fn resample(&mut self) {
+        while let Some(gyro) = self.gyro.front().copied() {
+            // Drop accelerometer measurements that no pending gyroscope measurement needs.
+            while self.accel.len() > 1 && self.accel[1].timestamp <= gyro.timestamp {
+                self.accel.pop_front();
+            }
+
+            let before = match self.accel.front() {
+                Some(accel) if accel.timestamp <= gyro.timestamp => *accel,
+                Some(_) => {
+                    // The gyroscope measurement precedes every accelerometer measurement.
+                    self.gyro.pop_front();
+                    continue;
+                }
+                None => return,
+            };
+
+            let accel = match self.interpolation {
+                ImuInterpolation::CopyLast => before.motion,
+                ImuInterpolation::Linear if before.timestamp == gyro.timestamp => before.motion,
+                ImuInterpolation::Linear => match self.accel.get(1) {
+                    Some(after) => {
+                        let t = ((gyro.timestamp - before.timestamp)
+                            / (after.timestamp - before.timestamp))
+                            as f32;
+                        [0, 1, 2]
+                            .map(|i| before.motion[i] + t * (after.motion[i] - before.motion[i]))
+                    }
+                    None => return,
+                },
+            };
+
+            self.gyro.pop_front();
+            self.samples.push_back(ImuSample {
+                timestamp: gyro.timestamp,
+                accel,
+                gyro: gyro.motion,
+                accel_frame_number: before.frame_number,
+                gyro_frame_number: gyro.frame_number,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(stream: &mut ImuStream) -> Vec<ImuSample> {
+        std::iter::from_fn(|| stream.next_sample()).collect()
+    }
+
+    #[test]
+    fn interpolates_accel_linearly() {
+        let mut stream = ImuStream::new(ImuInterpolation::Linear);
+        stream.push_accel_measurement(0.0, 1, [0.0, 0.0, 0.0]);
+        stream.push_gyro_measurement(1.0, 1, [1.0, 0.0, 0.0]);
+        stream.push_gyro_measurement(3.0, 2, [2.0, 0.0, 0.0]);
+        assert!(stream.next_sample().is_none());
+
+        stream.push_accel_measurement(4.0, 2, [4.0, 8.0, 0.0]);
+        let samples = samples(&mut stream);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].accel, [1.0, 2.0, 0.0]);
+        assert_eq!(samples[0].gyro, [1.0, 0.0, 0.0]);
+        assert_eq!(samples[1].timestamp, 3.0);
+        assert_eq!(samples[1].accel, [3.0, 6.0, 0.0]);
+        assert_eq!(samples[1].accel_frame_number, 1);
+        assert_eq!(samples[1].gyro_frame_number, 2);
+    }
+
+    #[test]
+    fn copies_last_accel() {
+        let mut stream = ImuStream::new(ImuInterpolation::CopyLast);
+        // Gyroscope measurements before the first accelerometer measurement are dropped.
+        stream.push_gyro_measurement(0.5, 1, [1.0, 0.0, 0.0]);
+        stream.push_accel_measurement(1.0, 1, [0.0, 9.8, 0.0]);
+        stream.push_gyro_measurement(2.0, 2, [2.0, 0.0, 0.0]);
+        stream.push_accel_measurement(3.0, 2, [0.0, 9.7, 0.0]);
+        stream.push_gyro_measurement(3.0, 3, [3.0, 0.0, 0.0]);
+
+        let samples = samples(&mut stream);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].accel, [0.0, 9.8, 0.0]);
+        assert_eq!(samples[1].accel, [0.0, 9.7, 0.0]);
+        assert_eq!(samples[1].accel_frame_number, 2);
+    }
+
+    #[test]
+    fn ignores_repeated_frames_and_reports_gaps() {
+        let mut stream = ImuStream::new(ImuInterpolation::CopyLast);
+        stream.push_accel_measurement(0.0, 10, [0.0; 3]);
+        stream.push_gyro_measurement(1.0, 20, [0.0; 3]);
+        stream.push_gyro_measurement(1.0, 20, [0.0; 3]);
+        stream.push_accel_measurement(0.0, 10, [0.0; 3]);
+        assert_eq!(samples(&mut stream).len(), 1);
+        assert!(stream.take_gaps().is_empty());
+
+        stream.push_gyro_measurement(11.0, 24, [0.0; 3]);
+        let gaps = stream.take_gaps();
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].stream, Rs2StreamKind::Gyro);
+        assert_eq!(gaps[0].dropped_frames(), 3);
+        assert_eq!(gaps[0].duration, 10.0);
+        assert!(stream.take_gaps().is_empty());
+    }
+
+    #[test]
+    fn resumes_after_restart() {
+        let mut stream = ImuStream::new(ImuInterpolation::CopyLast);
+        stream.push_accel_measurement(100.0, 50, [0.0; 3]);
+        stream.push_gyro_measurement(101.0, 80, [1.0, 0.0, 0.0]);
+        assert_eq!(samples(&mut stream).len(), 1);
+
+        // The pipeline restarts, and both counters start over.
+        stream.push_accel_measurement(5.0, 1, [0.0, 9.8, 0.0]);
+        stream.push_gyro_measurement(6.0, 1, [2.0, 0.0, 0.0]);
+        stream.push_gyro_measurement(7.0, 2, [3.0, 0.0, 0.0]);
+
+        let samples = samples(&mut stream);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].accel, [0.0, 9.8, 0.0]);
+        assert_eq!(samples[1].gyro, [3.0, 0.0, 0.0]);
+
+        let gaps = stream.take_gaps();
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].stream, Rs2StreamKind::Accel);
+        assert!(gaps[0].restart);
+        assert_eq!(gaps[0].dropped_frames(), 0);
+    }
+
+    #[test]
+    fn caps_queues_while_a_stream_stalls() {
+        // The accelerometer stalls after one measurement, so every gyroscope measurement waits
+        // for the next accelerometer measurement to interpolate.
+        let mut stream = ImuStream::new(ImuInterpolation::Linear);
+        stream.set_queue_length(4);
+        stream.push_accel_measurement(0.0, 1, [0.0; 3]);
+        for i in 1..=10 {
+            stream.push_gyro_measurement(i as f64, i, [0.0; 3]);
+        }
+        assert_eq!(stream.gyro.len(), 4);
+        assert_eq!(stream.gyro.front().unwrap().frame_number, 7);
+
+        // Only the accelerometer is streamed.
+        let mut stream = ImuStream::new(ImuInterpolation::CopyLast);
+        stream.set_queue_length(4);
+        for i in 1..=10 {
+            stream.push_accel_measurement(i as f64, i, [0.0; 3]);
+        }
+        assert_eq!(stream.accel.len(), 4);
+    }
+
+    #[test]
+    fn reset_discards_everything() {
+        let mut stream = ImuStream::new(ImuInterpolation::CopyLast);
+        stream.push_accel_measurement(0.0, 1, [0.0; 3]);
+        stream.push_gyro_measurement(1.0, 1, [0.0; 3]);
+        stream.push_gyro_measurement(5.0, 4, [0.0; 3]);
+        stream.reset();
+
+        assert!(stream.next_sample().is_none());
+        assert!(stream.take_gaps().is_empty());
+
+        // Frames from before the reset no longer count as repeats.
+        stream.push_accel_measurement(0.0, 1, [0.0; 3]);
+        stream.push_gyro_measurement(1.0, 1, [0.0; 3]);
+        assert_eq!(samples(&mut stream).len(), 1);
+        assert!(stream.take_gaps().is_empty());
+    }
+}
@@ -0,0 +1,824 @@
+//! Types for calibrating the accelerometer and gyroscope of a motion module.
+//!
+//! The calibration follows the procedure of librealsense's `rs-imu-calibration` script. The device
+//! is held still in six poses, each with a different IMU axis pointing up (see
+//! [`CalibrationPose`]). In each pose, the accelerometer should measure exactly the reaction to
+//! gravity along that axis, and the gyroscope should measure no rotation. An [`ImuCalibrator`]
+//! collects static samples in each pose, then fits the accelerometer's scale, cross-axis and bias
+//! terms by least squares, and averages the gyroscope's bias. The result is an [`ImuCalibration`]
+//! holding [`Rs2MotionDeviceIntrinsics`] for both sensors.
+//!
+//! Samples must be raw, i.e. streamed with
+//! [`EnableMotionCorrection`](crate::kind::Rs2Option::EnableMotionCorrection) disabled, otherwise
+//! the current calibration would be fitted on top of itself. Samples can also be loaded from a
+//! recording with [`ImuCalibrator::add_recorded_pose`].
+//!
+//! The intrinsics can be applied on the host with [`Rs2MotionDeviceIntrinsics::correct`], or
+//! written to the motion module of a D400 device with [`ImuCalibration::write_to_device`]. Writing
+//! follows `rs-imu-calibration` as well: the intrinsics are packed into the IMU calibration table,
+//! which is written as the motion module's EEPROM image with the device's debug protocol. This
+//! replaces the factory IMU calibration, so it is never done implicitly.
+
+use super::motion::{AccelFrame, GyroFrame};
+use crate::{
+    base::Rs2MotionDeviceIntrinsics,
+    check_rs2_error,
+    device::Device,
+    kind::{Rs2CameraInfo, Rs2Exception, Rs2Extension},
+};
+use realsense_sys as sys;
+use std::os::raw::c_void;
+use thiserror::Error;
+
+/// Standard gravity, in m/s^2.
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+
+/// The largest angle (in degrees) between the measured acceleration and the up axis of a pose,
+/// for the device to be considered in that pose.
+const MAX_POSE_ANGLE: f32 = 30.0;
+
+/// The largest difference (in m/s^2) between a sample and the average of the samples collected in
+/// a pose, for the device to be considered still.
+const MAX_STATIC_DEVIATION: f32 = 0.3;
+
+/// Opcode of the D400 debug protocol command that writes the motion module EEPROM.
+const MMEW_OPCODE: u32 = 0x50;
+
+/// Magic number following the length of every D400 debug protocol command.
+const COMMAND_MAGIC: u16 = 0xCDAB;
+
+/// The size of the motion module EEPROM image, in bytes.
+const EEPROM_SIZE: usize = 520;
+
+/// The size of the header of the EEPROM image and of the tables in it, in bytes.
+const TABLE_HEADER_SIZE: usize = 16;
+
+/// The size of the IMU calibration table, without its header, in bytes.
+const IMU_TABLE_SIZE: usize = 244;
+
+/// Occurs when a calibration cannot be computed from the collected samples, or cannot be written
+/// to a device.
+#[derive(Error, Debug)]
+pub enum ImuCalibrationError {
+    /// Not every pose was collected.
+    #[error("Missing calibration poses: {0:?}")]
+    MissingPoses(Vec<CalibrationPose>),
+    /// No gyroscope samples were collected.
+    #[error("No gyroscope samples were collected")]
+    MissingGyroSamples,
+    /// The poses do not constrain every axis of the accelerometer, e.g. because the device was not
+    /// actually turned between poses.
+    #[error("Calibration poses are degenerate")]
+    DegeneratePoses,
+    /// The device is not a D400 device with a motion module.
+    #[error("Cannot write IMU calibration to device: {0}")]
+    UnsupportedDevice(String),
+    /// Could not send the calibration to the device.
+    #[error("Could not write IMU calibration. Type: {0}; Reason: {1}")]
+    CouldNotWriteToDevice(Rs2Exception, String),
+    /// The device did not acknowledge the calibration.
+    #[error("IMU calibration was rejected by the device")]
+    WriteRejected,
+}
+
+/// A static pose of the device during calibration, named after the IMU axis pointing up (away
+/// from gravity).
+///
+/// For D400 devices, whose IMU X axis points right, Y down and Z forward (out of the lenses):
+///
+/// - [`YDown`](CalibrationPose::YDown) is the device standing upright.
+/// - [`YUp`](CalibrationPose::YUp) is the device upside down.
+/// - [`XUp`](CalibrationPose::XUp) and [`XDown`](CalibrationPose::XDown) are the device standing
+///   on its left and right sides.
+/// - [`ZUp`](CalibrationPose::ZUp) is the device lying on its back, looking up.
+/// - [`ZDown`](CalibrationPose::ZDown) is the device lying on its front, looking down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationPose {
+    /// The +X axis points up.
+    XUp,
+    /// The -X axis points up.
+    XDown,
+    /// The +Y axis points up.
+    YUp,
+    /// The -Y axis points up.
+    YDown,
+    /// The +Z axis points up.
+    ZUp,
+    /// The -Z axis points up.
+    ZDown,
+}
+
+impl CalibrationPose {
+    /// Every pose, in the order they are collected.
+    pub const ALL: [CalibrationPose; 6] = [
+        CalibrationPose::YDown,
+        CalibrationPose::YUp,
+        CalibrationPose::XUp,
+        CalibrationPose::XDown,
+        CalibrationPose::ZUp,
+        CalibrationPose::ZDown,
+    ];
+
+    /// Get the direction pointing up in the IMU's coordinate frame, as a unit vector.
+    pub fn up(&self) -> [f32; 3] {
+        match self {
+            CalibrationPose::XUp => [1.0, 0.0, 0.0],
+            CalibrationPose::XDown => [-1.0, 0.0, 0.0],
+            CalibrationPose::YUp => [0.0, 1.0, 0.0],
+            CalibrationPose::YDown => [0.0, -1.0, 0.0],
+            CalibrationPose::ZUp => [0.0, 0.0, 1.0],
+            CalibrationPose::ZDown => [0.0, 0.0, -1.0],
+        }
+    }
+
+    /// Get the acceleration a calibrated accelerometer measures at rest in this pose, in m/s^2.
+    pub fn expected_acceleration(&self) -> [f32; 3] {
+        self.up().map(|c| c * STANDARD_GRAVITY)
+    }
+
+    /// Whether an acceleration points close enough to the up direction of this pose.
+    fn matches(&self, accel: [f32; 3]) -> bool {
+        let norm = accel.iter().map(|a| a * a).sum::<f32>().sqrt();
+        let up = self.up();
+        let cosine = (0..3).map(|i| accel[i] * up[i]).sum::<f32>() / norm;
+        norm > 0.0 && cosine >= MAX_POSE_ANGLE.to_radians().cos()
+    }
+}
+
+/// The static samples collected in a pose.
+#[derive(Debug, Clone)]
+struct PoseSamples {
+    /// The pose the samples were collected in.
+    pose: CalibrationPose,
+    /// Raw accelerometer samples, in m/s^2.
+    accel: Vec<[f32; 3]>,
+    /// Raw gyroscope samples, in radians/s.
+    gyro: Vec<[f32; 3]>,
+}
+
+/// Guides the collection of static IMU samples in each [`CalibrationPose`], and fits a
+/// calibration to them.
+///
+/// A typical calibration loop asks the user to hold the device in [`ImuCalibrator::next_pose`],
+/// calls [`ImuCalibrator::start_pose`], then pushes frames until the pose is complete (shown by
+/// [`ImuCalibrator::progress`]). Samples are only kept while the device is still and in the
+/// requested pose; moving it restarts the pose.
+#[derive(Debug, Clone)]
+pub struct ImuCalibrator {
+    /// The number of accelerometer samples to collect per pose.
+    samples_per_pose: usize,
+    /// The samples of the pose being collected.
+    current: Option<PoseSamples>,
+    /// The samples of every completed pose.
+    completed: Vec<PoseSamples>,
+}
+
+impl ImuCalibrator {
+    /// Construct a calibrator collecting `samples_per_pose` accelerometer samples in each pose.
+    ///
+    /// A few seconds of samples per pose (e.g. 1000 at 250Hz) averages out most of the noise.
+    pub fn new(samples_per_pose: usize) -> Self {
+        ImuCalibrator {
+            samples_per_pose: samples_per_pose.max(1),
+            current: None,
+            completed: Vec::new(),
+        }
+    }
+
+    /// Get the next pose that has not been collected yet, or `None` if every pose is complete.
+    pub fn next_pose(&self) -> Option<CalibrationPose> {
+        CalibrationPose::ALL
+            .iter()
+            .copied()
+            .find(|pose| !self.is_complete(*pose))
+    }
+
+    /// Whether a pose has been collected.
+    pub fn is_complete(&self, pose: CalibrationPose) -> bool {
+        self.completed.iter().any(|samples| samples.pose == pose)
+    }
+
+    /// Get the pose being collected, if any.
+    pub fn current_pose(&self) -> Option<CalibrationPose> {
+        self.current.as_ref().map(|samples| samples.pose)
+    }
+
+    /// Start collecting samples in a pose.
+    ///
+    /// Any pose in progress is abandoned. Collecting a pose that was already completed replaces
+    /// its samples once complete.
+    pub fn start_pose(&mut self, pose: CalibrationPose) {
+        self.current = Some(PoseSamples {
+            pose,
+            accel: Vec::new(),
+            gyro: Vec::new(),
+        });
+    }
+
+    /// Get the fraction of the current pose that has been collected, between 0 and 1.
+    ///
+    /// Returns `None` if no pose is being collected.
+    pub fn progress(&self) -> Option<f32> {
+        self.current
+            .as_ref()
+            .map(|samples| samples.accel.len() as f32 / self.samples_per_pose as f32)
+    }
+
+    /// Push a raw accelerometer frame.
+    ///
+    /// Returns `true` if the frame completed the current pose.
+    pub fn push_accel(&mut self, frame: &AccelFrame) -> bool {
+        self.push_accel_sample(*frame.acceleration())
+    }
+
+    /// Push a raw gyroscope frame.
+    pub fn push_gyro(&mut self, frame: &GyroFrame) {
+        self.push_gyro_sample(*frame.rotational_velocity());
+    }
+
+    /// Add the samples of a pose recorded beforehand, e.g. from a bag file.
+    ///
+    /// Samples are used as is, without checking that the device was still and in the pose.
+    /// Replaces any samples already collected for the pose.
+    pub fn add_recorded_pose(
+        &mut self,
+        pose: CalibrationPose,
+        accel: &[[f32; 3]],
+        gyro: &[[f32; 3]],
+    ) {
+        self.complete(PoseSamples {
+            pose,
+            accel: accel.to_vec(),
+            gyro: gyro.to_vec(),
+        });
+    }
+
+    /// Fit a calibration to the samples of every pose.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImuCalibrationError::MissingPoses`] if any pose has not been collected.
+    ///
+    /// Returns [`ImuCalibrationError::MissingGyroSamples`] if no gyroscope sample was collected in
+    /// any pose.
+    ///
+    /// Returns [`ImuCalibrationError::DegeneratePoses`] if the accelerometer samples of the poses
+    /// do not constrain every axis.
+    pub fn calibrate(&self) -> Result<ImuCalibration, ImuCalibrationError> {
+        let missing: Vec<CalibrationPose> = CalibrationPose::ALL
+            .iter()
+            .copied()
+            .filter(|pose| !self.is_complete(*pose))
+            .collect();
+        if !missing.is_empty() {
+            return Err(ImuCalibrationError::MissingPoses(missing));
+        }
+
+        let gyro: Vec<[f32; 3]> = self
+            .completed
+            .iter()
+            .flat_map(|samples| samples.gyro.iter().copied())
+            .collect();
+        if gyro.is_empty() {
+            return Err(ImuCalibrationError::MissingGyroSamples);
+        }
+
+        // Fit `S * a + c = g` for the 3x3 scale `S` and offset `c`, over every pose, by solving the
+        // normal equations of the least squares problem.
+        let mut normal = [[0.0f64; 4]; 4];
+        let mut rhs = [[0.0f64; 3]; 4];
+        let mut accel_variances = [0.0; 3];
+        for samples in self.completed.iter() {
+            let [ax, ay, az] = mean(&samples.accel).map(f64::from);
+            let row = [ax, ay, az, 1.0];
+            let expected = samples.pose.expected_acceleration().map(f64::from);
+            for i in 0..4 {
+                for j in 0..4 {
+                    normal[i][j] += row[i] * row[j];
+                }
+                for (k, g) in expected.iter().enumerate() {
+                    rhs[i][k] += row[i] * g;
+                }
+            }
+
+            let variance = variance(&samples.accel);
+            for (total, v) in accel_variances.iter_mut().zip(variance.iter()) {
+                *total += v / CalibrationPose::ALL.len() as f32;
+            }
+        }
+        let solution = solve(normal, rhs).ok_or(ImuCalibrationError::DegeneratePoses)?;
+
+        let mut accel_data = [[0.0; 4]; 3];
+        for (axis, row) in accel_data.iter_mut().enumerate() {
+            for (i, value) in row.iter_mut().enumerate().take(3) {
+                *value = solution[i][axis] as f32;
+            }
+            // Intrinsics subtract the bias, while the fit adds the offset.
+            row[3] = -solution[3][axis] as f32;
+        }
+
+        let gyro_bias = mean(&gyro);
+        let mut gyro_data = [[0.0; 4]; 3];
+        for (axis, row) in gyro_data.iter_mut().enumerate() {
+            row[axis] = 1.0;
+            row[3] = gyro_bias[axis];
+        }
+
+        let accel = Rs2MotionDeviceIntrinsics::new(accel_data, accel_variances, [0.0; 3]);
+        let gyro = Rs2MotionDeviceIntrinsics::new(gyro_data, variance(&gyro), [0.0; 3]);
+
+        let mut squared_error = 0.0;
+        for samples in self.completed.iter() {
+            let corrected = accel.correct(mean(&samples.accel));
+            let expected = samples.pose.expected_acceleration();
+            squared_error += (0..3)
+                .map(|i| (corrected[i] - expected[i]).powi(2))
+                .sum::<f32>();
+        }
+        let residual = (squared_error / self.completed.len() as f32).sqrt();
+
+        Ok(ImuCalibration {
+            accel,
+            gyro,
+            residual,
+        })
+    }
+
+    /// Push a raw accelerometer sample, in m/s^2.
+    ///
+    /// Returns `true` if the sample completed the current pose.
+    pub(crate) fn push_accel_sample(&mut self, accel: [f32; 3]) -> bool {
+        let samples_per_pose = self.samples_per_pose;
+        let current = match self.current.as_mut() {
+            Some(current) => current,
+            None => return false,
+        };
+
+        // Restart the pose whenever the device is out of the pose, or moving.
+        let moving = !current.accel.is_empty() && {
+            let average = mean(&current.accel);
+            let deviation = (0..3).map(|i| (accel[i] - average[i]).powi(2)).sum::<f32>();
+            deviation.sqrt() > MAX_STATIC_DEVIATION
+        };
+        if moving || !current.pose.matches(accel) {
+            current.accel.clear();
+            current.gyro.clear();
+            return false;
+        }
+
+        current.accel.push(accel);
+        if current.accel.len() < samples_per_pose {
+            return false;
+        }
+
+        if let Some(samples) = self.current.take() {
+            self.complete(samples);
+        }
+        true
+    }
+
+    /// Push a raw gyroscope sample, in radians/s.
+    ///
+    /// Gyroscope samples are only kept while accelerometer samples are being collected, i.e. while
+    /// the device is still.
+    pub(crate) fn push_gyro_sample(&mut self, gyro: [f32; 3]) {
+        if let Some(current) = self.current.as_mut() {
+            if !current.accel.is_empty() {
+                current.gyro.push(gyro);
+            }
+        }
+    }
+
+    /// Store the samples of a completed pose.
+    fn complete(&mut self, samples: PoseSamples) {
+        self.completed
+            .retain(|completed| completed.pose != samples.pose);
+        self.completed.push(samples);
+    }
+}
+
+/// The calibration of a motion module's accelerometer and gyroscope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImuCalibration {
+    /// The intrinsics of the accelerometer.
+    accel: Rs2MotionDeviceIntrinsics,
+    /// The intrinsics of the gyroscope.
+    gyro: Rs2MotionDeviceIntrinsics,
+    /// The root mean square error of the calibrated accelerometer over the poses, in m/s^2.
+    residual: f32,
+}
+
+impl ImuCalibration {
+    /// Get the intrinsics of the accelerometer.
+    ///
+    /// The scale, cross-axis and bias terms are fitted. Noise variances are measured from the
+    /// static samples; bias variances cannot be observed by this procedure and are zero.
+    pub fn accel(&self) -> &Rs2MotionDeviceIntrinsics {
+        &self.accel
+    }
+
+    /// Get the intrinsics of the gyroscope.
+    ///
+    /// Only the bias is fitted, as the scale of a gyroscope cannot be observed from static poses.
+    /// Noise variances are measured from the static samples; bias variances are zero.
+    pub fn gyro(&self) -> &Rs2MotionDeviceIntrinsics {
+        &self.gyro
+    }
+
+    /// Get the root mean square error of the calibrated accelerometer over the poses, in m/s^2.
+    ///
+    /// A large residual (e.g. above 0.1 m/s^2) usually means the device moved, or was not aligned
+    /// with gravity, during a pose.
+    pub fn residual(&self) -> f32 {
+        self.residual
+    }
+
+    /// Get the motion module EEPROM image holding this calibration, as written by
+    /// [`write_to_device`](ImuCalibration::write_to_device).
+    ///
+    /// The image is the IMU calibration table of `rs-imu-calibration`: the scale and cross-axis
+    /// terms (column-major) and bias of the accelerometer, then of the gyroscope, each table
+    /// preceded by a header with its size and CRC-32.
+    pub fn to_device_table(&self) -> Vec<u8> {
+        let mut data = vec![0xFF; IMU_TABLE_SIZE];
+        // Extrinsics are not calibrated, intrinsics are.
+        data[..4].copy_from_slice(&[0, 1, 0, 0]);
+
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0];
+        let intrinsics = [
+            identity,
+            intrinsic_table(&self.accel),
+            intrinsic_table(&self.gyro),
+        ];
+        for (chunk, value) in data[4..148]
+            .chunks_exact_mut(4)
+            .zip(intrinsics.iter().flatten())
+        {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+
+        let mut eeprom = vec![0xFF; EEPROM_SIZE];
+        let table = &mut eeprom[TABLE_HEADER_SIZE..];
+        table[..TABLE_HEADER_SIZE].copy_from_slice(&table_header([0x02, 0x01], 0x20, &data));
+        table[TABLE_HEADER_SIZE..TABLE_HEADER_SIZE + IMU_TABLE_SIZE].copy_from_slice(&data);
+
+        let header = table_header([0x02, 0x00], 0x09, &eeprom[TABLE_HEADER_SIZE..]);
+        eeprom[..TABLE_HEADER_SIZE].copy_from_slice(&header);
+        eeprom
+    }
+
+    /// Write this calibration to the motion module of a D400 device (e.g. a D435i or D455),
+    /// replacing its current IMU calibration.
+    ///
+    /// The calibration is persisted on the device, and is used by librealsense2 once the device
+    /// is reset (see [`Device::hardware_reset`]). Consider saving the current intrinsics, e.g. from
+    /// the [motion stream profiles](crate::stream_profile::StreamProfile::motion_intrinsics),
+    /// beforehand.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImuCalibrationError::UnsupportedDevice`] if the device is not a D400 device, or
+    /// has no motion module.
+    ///
+    /// Returns [`ImuCalibrationError::CouldNotWriteToDevice`] if the command could not be sent to
+    /// the device.
+    ///
+    /// Returns [`ImuCalibrationError::WriteRejected`] if the device did not acknowledge the
+    /// command.
+    pub fn write_to_device(&self, device: &mut Device) -> Result<(), ImuCalibrationError> {
+        let product_line = device
+            .info(Rs2CameraInfo::ProductLine)
+            .map(|line| line.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if product_line != "D400" {
+            return Err(ImuCalibrationError::UnsupportedDevice(format!(
+                "product line {:?} is not D400",
+                product_line
+            )));
+        }
+        if !device
+            .sensors()
+            .iter()
+            .any(|sensor| sensor.is_extendable_to(Rs2Extension::MotionSensor))
+        {
+            return Err(ImuCalibrationError::UnsupportedDevice(
+                "device has no motion module".to_owned(),
+            ));
+        }
+
+        let eeprom = self.to_device_table();
+        let mut command = debug_command(MMEW_OPCODE, [0, EEPROM_SIZE as u32, 0, 0], &eeprom);
+
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            let buffer = sys::rs2_send_and_receive_raw_data(
+                device.get_raw().as_ptr(),
+                command.as_mut_ptr().cast::<c_void>(),
+                command.len() as u32,
+                &mut err,
+            );
+            check_rs2_error!(err, ImuCalibrationError::CouldNotWriteToDevice)?;
+
+            let size = sys::rs2_get_raw_data_size(buffer, &mut err);
+            if let Err(e) = check_rs2_error!(err, ImuCalibrationError::CouldNotWriteToDevice) {
+                sys::rs2_delete_raw_data(buffer);
+                return Err(e);
+            }
+
+            let data = sys::rs2_get_raw_data(buffer, &mut err);
+            if let Err(e) = check_rs2_error!(err, ImuCalibrationError::CouldNotWriteToDevice) {
+                sys::rs2_delete_raw_data(buffer);
+                return Err(e);
+            }
+
+            let response = std::slice::from_raw_parts(data, size.max(0) as usize).to_vec();
+            sys::rs2_delete_raw_data(buffer);
+
+            // The device echoes the opcode of commands it executed.
+            if response.len() >= 4 && response[..4] == MMEW_OPCODE.to_le_bytes() {
+                Ok(())
+            } else {
+                Err(ImuCalibrationError::WriteRejected)
+            }
+        }
+    }
+}
+
+/// The scale and cross-axis terms of intrinsics in column-major order, followed by the bias, as
+/// stored in the IMU calibration table.
+fn intrinsic_table(intrinsics: &Rs2MotionDeviceIntrinsics) -> [f32; 12] {
+    let data = intrinsics.data();
+    let mut table = [0.0; 12];
+    for col in 0..3 {
+        for row in 0..3 {
+            table[col * 3 + row] = data[row][col];
+        }
+    }
+    for row in 0..3 {
+        table[9 + row] = data[row][3];
+    }
+    table
+}
+
+/// The header of a table in the motion module EEPROM.
+///
+/// The header holds the version and type of the table, followed by the size and CRC-32 of `data`.
+/// The four bytes between the size and the CRC are unused.
+pub(crate) fn table_header(version: [u8; 2], table_type: u16, data: &[u8]) -> [u8; 16] {
+    let mut header = [0xFF; TABLE_HEADER_SIZE];
+    header[..2].copy_from_slice(&version);
+    header[2..4].copy_from_slice(&table_type.to_le_bytes());
+    header[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    header[12..].copy_from_slice(&crc32(data).to_le_bytes());
+    header
+}
+
+/// Frame a D400 debug protocol command.
+///
+/// The command starts with its length (excluding the length and magic number themselves) and the
+/// magic number, followed by the opcode, four parameters and the data.
+pub(crate) fn debug_command(opcode: u32, parameters: [u32; 4], data: &[u8]) -> Vec<u8> {
+    let mut command = Vec::with_capacity(24 + data.len());
+    command.extend_from_slice(&((20 + data.len()) as u16).to_le_bytes());
+    command.extend_from_slice(&COMMAND_MAGIC.to_le_bytes());
+    command.extend_from_slice(&opcode.to_le_bytes());
+    for parameter in parameters.iter() {
+        command.extend_from_slice(&parameter.to_le_bytes());
+    }
+    command.extend_from_slice(data);
+    command
+}
+
+/// The CRC-32 (as used by zlib and Ethernet) of `data`.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data.iter() {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// The per-axis mean of a set of samples.
+fn mean(samples: &[[f32; 3]]) -> [f32; 3] {
+    let mut total = [0.0f64; 3];
+    for sample in samples.iter() {
+        for (t, s) in total.iter_mut().zip(sample.iter()) {
+            *t += f64::from(*s);
+        }
+    }
+    total.map(|t| (t / samples.len().max(1) as f64) as f32)
+}
+
+/// The per-axis variance of a set of samples.
+fn variance(samples: &[[f32; 3]]) -> [f32; 3] {
+    let average = mean(samples);
+    let mut total = [0.0f64; 3];
+    for sample in samples.iter() {
+        for axis in 0..3 {
+            total[axis] += f64::from(sample[axis] - average[axis]).powi(2);
+        }
+    }
+    total.map(|t| (t / samples.len().max(1) as f64) as f32)
+}
+
+/// Solve `A x = b` for a 4x4 matrix `A` and three right hand sides, by Gaussian elimination with
+/// partial pivoting.
+///
+/// Returns `None` if `A` is singular.
+fn solve(mut a: [[f64; 4]; 4], mut b: [[f64; 3]; 4]) -> Option<[[f64; 3]; 4]> {
+    for column in 0..4 {
+        let pivot =
+            (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() < 1e-9 {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+
+        let (pivot_a, pivot_b) = (a[column], b[column]);
+        for row in column + 1..4 {
+            let factor = a[row][column] / pivot_a[column];
+            for (value, p) in a[row].iter_mut().zip(pivot_a.iter()).skip(column) {
+                *value -= factor * p;
+            }
+            for (value, p) in b[row].iter_mut().zip(pivot_b.iter()) {
+                *value -= factor * p;
+            }
+        }
+    }
+
+    let mut x = [[0.0; 3]; 4];
+    for row in (0..4).rev() {
+        for k in 0..3 {
+            let known: f64 = (row + 1..4).map(|j| a[row][j] * x[j][k]).sum();
+            x[row][k] = (b[row][k] - known) / a[row][row];
+        }
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A miscalibrated accelerometer: `raw = scale * true + bias`.
+    fn raw_accel(pose: CalibrationPose, noise: f32) -> [f32; 3] {
+        let g = pose.expected_acceleration();
+        [
+            1.02 * g[0] + 0.01 * g[1] + 0.15 + noise,
+            0.98 * g[1] - 0.2 - noise,
+            1.01 * g[2] + 0.02 * g[0] + 0.05 + noise,
+        ]
+    }
+
+    /// Alternate small noise around the true value, so that the mean is unchanged.
+    fn noise(i: usize) -> f32 {
+        if i & 1 == 0 {
+            0.01
+        } else {
+            -0.01
+        }
+    }
+
+    #[test]
+    fn fits_recorded_poses() {
+        let mut calibrator = ImuCalibrator::new(100);
+        for pose in CalibrationPose::ALL.iter() {
+            let accel: Vec<_> = (0..100).map(|i| raw_accel(*pose, noise(i))).collect();
+            let gyro = [[0.01, -0.02, 0.003]; 50];
+            calibrator.add_recorded_pose(*pose, &accel, &gyro);
+        }
+
+        let calibration = calibrator.calibrate().unwrap();
+        assert!(calibration.residual() < 1e-3);
+        for pose in CalibrationPose::ALL.iter() {
+            let corrected = calibration.accel().correct(raw_accel(*pose, 0.0));
+            let expected = pose.expected_acceleration();
+            for axis in 0..3 {
+                assert!((corrected[axis] - expected[axis]).abs() < 1e-3);
+            }
+        }
+        assert!((calibration.accel().noise_variances()[0] - 1e-4).abs() < 1e-6);
+
+        let gyro = calibration.gyro().correct([0.01, -0.02, 0.003]);
+        assert!(gyro.iter().all(|g| g.abs() < 1e-6));
+        assert_eq!(calibration.gyro().data()[0][..3], [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn guides_through_poses() {
+        let mut calibrator = ImuCalibrator::new(10);
+        assert!(matches!(
+            calibrator.calibrate(),
+            Err(ImuCalibrationError::MissingPoses(poses)) if poses.len() == 6
+        ));
+
+        while let Some(pose) = calibrator.next_pose() {
+            calibrator.start_pose(pose);
+            // Samples in the wrong pose, or while moving, restart the pose.
+            calibrator.push_accel_sample([0.0, 0.0, 0.0]);
+            calibrator.push_accel_sample(raw_accel(pose, 0.0));
+            calibrator.push_accel_sample(raw_accel(pose, 1.0));
+            assert_eq!(calibrator.progress(), Some(0.0));
+
+            for i in 0..10 {
+                calibrator.push_gyro_sample([0.0, 0.0, 0.01]);
+                assert_eq!(
+                    calibrator.push_accel_sample(raw_accel(pose, noise(i))),
+                    i == 9
+                );
+            }
+            assert!(calibrator.is_complete(pose));
+            assert_eq!(calibrator.current_pose(), None);
+        }
+
+        let calibration = calibrator.calibrate().unwrap();
+        assert!(calibration.residual() < 1e-3);
+        assert!((calibration.gyro().data()[2][3] - 0.01).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_degenerate_poses() {
+        let mut calibrator = ImuCalibrator::new(1);
+        for pose in CalibrationPose::ALL.iter() {
+            // The device never left the first pose.
+            calibrator.add_recorded_pose(*pose, &[[0.0, -9.8, 0.0]], &[[0.0; 3]]);
+        }
+        assert!(matches!(
+            calibrator.calibrate(),
+            Err(ImuCalibrationError::DegeneratePoses)
+        ));
+    }
+
+    #[test]
+    fn computes_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+
+    #[test]
+    fn frames_debug_commands() {
+        let command = debug_command(MMEW_OPCODE, [0, 3, 0, 0], &[1, 2, 3]);
+        assert_eq!(command.len(), 27);
+        assert_eq!(command[..4], [23, 0, 0xAB, 0xCD]);
+        assert_eq!(command[4..8], [0x50, 0, 0, 0]);
+        assert_eq!(command[12..16], [3, 0, 0, 0]);
+        assert_eq!(command[24..], [1, 2, 3]);
+    }
+
+    #[test]
+    fn packs_calibration_into_device_table() {
+        let mut accel = [[0.0; 4]; 3];
+        accel[0] = [1.0, 0.1, 0.2, 0.5];
+        accel[1] = [0.3, 1.0, 0.4, -0.5];
+        accel[2] = [0.6, 0.7, 1.0, 0.25];
+        let gyro = [
+            [1.0, 0.0, 0.0, 0.01],
+            [0.0, 1.0, 0.0, 0.02],
+            [0.0, 0.0, 1.0, 0.03],
+        ];
+        let calibration = ImuCalibration {
+            accel: Rs2MotionDeviceIntrinsics::new(accel, [0.0; 3], [0.0; 3]),
+            gyro: Rs2MotionDeviceIntrinsics::new(gyro, [0.0; 3], [0.0; 3]),
+            residual: 0.0,
+        };
+
+        let eeprom = calibration.to_device_table();
+        assert_eq!(eeprom.len(), EEPROM_SIZE);
+        let float_at = |offset: usize| {
+            f32::from_le_bytes([
+                eeprom[offset],
+                eeprom[offset + 1],
+                eeprom[offset + 2],
+                eeprom[offset + 3],
+            ])
+        };
+
+        // EEPROM header, covering everything after it.
+        assert_eq!(eeprom[..4], [0x02, 0x00, 0x09, 0x00]);
+        assert_eq!(eeprom[4..8], 504u32.to_le_bytes());
+        assert_eq!(eeprom[12..16], crc32(&eeprom[16..]).to_le_bytes());
+
+        // IMU table header, covering the table data.
+        let data = &eeprom[32..32 + IMU_TABLE_SIZE];
+        assert_eq!(eeprom[16..20], [0x02, 0x01, 0x20, 0x00]);
+        assert_eq!(eeprom[20..24], (IMU_TABLE_SIZE as u32).to_le_bytes());
+        assert_eq!(eeprom[28..32], crc32(data).to_le_bytes());
+        assert_eq!(data[..2], [0, 1]);
+
+        // The accelerometer follows the extrinsics, in column-major order, then the gyroscope.
+        let accel_offset = 32 + 4 + 48;
+        assert_eq!(float_at(accel_offset), 1.0);
+        assert_eq!(float_at(accel_offset + 4), 0.3);
+        assert_eq!(float_at(accel_offset + 8), 0.6);
+        assert_eq!(float_at(accel_offset + 12), 0.1);
+        assert_eq!(float_at(accel_offset + 36), 0.5);
+        assert_eq!(float_at(accel_offset + 44), 0.25);
+        assert_eq!(float_at(accel_offset + 48 + 40), 0.02);
+        assert!(eeprom[32 + 148..].iter().all(|&b| b == 0xFF));
+    }
+}
@@ -0,0 +1,201 @@
+//! Type for representing a color frame decoded from a Motion-JPEG stream.
+//!
+//! Some devices can stream color as [`Mjpeg`](crate::kind::Rs2Format::Mjpeg), which is much
+//! lighter on USB bandwidth than raw color formats. Compressed frames cannot be indexed pixel by
+//! pixel, so these frames must first be decoded with
+//! [`ColorFrame::decode_mjpeg`](crate::frame::ColorFrame::decode_mjpeg).
+//!
+//! Decoding requires the `jpeg` feature.
+
+use crate::{
+    base::Rs2Intrinsics,
+    kind::{Rs2Format, Rs2FrameMetadata, Rs2TimestampDomain},
+};
+use jpeg_decoder::{Decoder, PixelFormat};
+use std::{collections::HashMap, os::raw::c_longlong};
+use thiserror::Error;
+
+/// Occurs when a frame cannot be decoded from Motion-JPEG.
+#[derive(Error, Debug)]
+pub enum MjpegDecodeError {
+    /// The frame is not in the Motion-JPEG format.
+    #[error("Frame is not in the MJPEG format. Format: {0:?}")]
+    NotMjpeg(Rs2Format),
+    /// The compressed frame data could not be decoded.
+    #[error("Could not decode MJPEG frame. Reason: {0}")]
+    CouldNotDecode(String),
+    /// The frame decoded to a pixel format that cannot be represented as RGB8.
+    #[error("MJPEG frame decoded to an unsupported pixel format: {0}")]
+    UnsupportedPixelFormat(String),
+}
+
+/// An owned color frame holding RGB8 data decoded from a Motion-JPEG frame.
+///
+/// Unlike the other frame types, this does not hold onto the underlying librealsense2 frame. The
+/// timestamp, metadata and intrinsics of the original frame are copied out when decoding so that
+/// they stay attached to the decoded image.
+#[derive(Debug)]
+pub struct DecodedColorFrame {
+    /// The width of the frame in pixels.
+    pub(crate) width: usize,
+    /// The height of the frame in pixels.
+    pub(crate) height: usize,
+    /// The decoded pixels, as row-major 8-bit red, green, and blue channels.
+    pub(crate) data: Vec<u8>,
+    /// The timestamp of the original frame.
+    pub(crate) timestamp: f64,
+    /// The RealSense time domain from which the timestamp is derived.
+    pub(crate) timestamp_domain: Rs2TimestampDomain,
+    /// The frame number of the original frame.
+    pub(crate) frame_number: u64,
+    /// Every metadata value supported by the original frame.
+    pub(crate) metadata: HashMap<Rs2FrameMetadata, c_longlong>,
+    /// The intrinsics of the stream that produced the original frame, if they were available.
+    pub(crate) intrinsics: Option<Rs2Intrinsics>,
+}
+
+impl DecodedColorFrame {
+    /// Get the width of this frame in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of this frame in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the decoded pixel data, as row-major 8-bit red, green, and blue channels.
+    ///
+    /// The stride of this data is always `3 * width` bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Take ownership of the decoded pixel data.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Given a row and column index, get the `[r, g, b]` value of a pixel in this frame.
+    pub fn get(&self, col: usize, row: usize) -> Option<[u8; 3]> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+
+        let offset = (row * self.width + col) * 3;
+        Some([
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+        ])
+    }
+
+    /// Get the timestamp of the original frame.
+    pub fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    /// Get the RealSense timestamp domain for the current timestamp.
+    pub fn timestamp_domain(&self) -> Rs2TimestampDomain {
+        self.timestamp_domain
+    }
+
+    /// Get the frame number of the original frame.
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
+    /// Get frame metadata.
+    ///
+    /// Returns `None` if the `metadata_kind` was not supported by the original frame.
+    pub fn metadata(&self, metadata_kind: Rs2FrameMetadata) -> Option<c_longlong> {
+        self.metadata.get(&metadata_kind).copied()
+    }
+
+    /// Get the intrinsics of the stream that produced the original frame.
+    ///
+    /// Returns `None` if the intrinsics could not be retrieved when the frame was decoded.
+    pub fn intrinsics(&self) -> Option<&Rs2Intrinsics> {
+        self.intrinsics.as_ref()
+    }
+}
+
+/// Decode a JPEG image into its width, height, and RGB8 pixel data.
+///
+/// Grayscale images are expanded into RGB8 by repeating the luminance across all three channels.
+pub(crate) fn decode_rgb8(jpeg: &[u8]) -> Result<(usize, usize, Vec<u8>), MjpegDecodeError> {
+    let mut decoder = Decoder::new(jpeg);
+    let pixels = decoder
+        .decode()
+        .map_err(|e| MjpegDecodeError::CouldNotDecode(e.to_string()))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| MjpegDecodeError::CouldNotDecode("Missing image header".to_string()))?;
+
+    let rgb = match info.pixel_format {
+        PixelFormat::RGB24 => pixels,
+        PixelFormat::L8 => pixels.iter().flat_map(|&y| [y, y, y]).collect(),
+        other => {
+            return Err(MjpegDecodeError::UnsupportedPixelFormat(format!(
+                "{:?}",
+                other
+            )))
+        }
+    };
+
+    Ok((info.width as usize, info.height as usize, rgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A baseline 8x8 JPEG of a single color (Y = 128, Cb = 128, Cr = 192).
+    ///
+    /// Uses an all-ones quantization table and minimal Huffman tables, so that the scan data is
+    /// only the DC coefficient of each component.
+    const SOLID_8X8_JPEG: &[u8] = &[
+        // SOI
+        0xFF, 0xD8, //
+        // DQT: table 0, all ones
+        0xFF, 0xDB, 0x00, 0x43, 0x00, //
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, //
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, //
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, //
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, //
+        // SOF0: 8x8, three components without subsampling
+        0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x08, 0x00, 0x08, 0x03, //
+        0x01, 0x11, 0x00, 0x02, 0x11, 0x00, 0x03, 0x11, 0x00, //
+        // DHT: DC table 0, categories 0 and 10
+        0xFF, 0xC4, 0x00, 0x15, 0x00, //
+        1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x0A, //
+        // DHT: AC table 0, end-of-block only
+        0xFF, 0xC4, 0x00, 0x14, 0x10, //
+        1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, //
+        // SOS
+        0xFF, 0xDA, 0x00, 0x0C, 0x03, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00, 0x3F, 0x00, //
+        0x0A, 0x00, 0x7F, //
+        // EOI
+        0xFF, 0xD9,
+    ];
+
+    #[test]
+    fn decodes_color_jpeg() {
+        let (width, height, rgb) = decode_rgb8(SOLID_8X8_JPEG).unwrap();
+
+        assert_eq!((width, height), (8, 8));
+        assert_eq!(rgb.len(), 8 * 8 * 3);
+        for pixel in rgb.chunks(3) {
+            assert_eq!(pixel, &[218, 82, 128]);
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_data() {
+        assert!(matches!(
+            decode_rgb8(&SOLID_8X8_JPEG[..40]),
+            Err(MjpegDecodeError::CouldNotDecode(_))
+        ));
+    }
+}
@@ -7,6 +7,7 @@
 
 use super::prelude::{CouldNotGetFrameSensorError, FrameCategory, FrameConstructionError, FrameEx};
 use crate::{
+    base::Rs2MotionDeviceIntrinsics,
     check_rs2_error,
     kind::{Rs2Extension, Rs2FrameMetadata, Rs2StreamKind, Rs2TimestampDomain},
     sensor::Sensor,
@@ -270,6 +271,16 @@ impl AccelFrame {
     pub fn acceleration(&self) -> &[f32; 3] {
         &self.motion
     }
+
+    /// Returns the acceleration recorded in the Accel frame, corrected for the scale and bias of
+    /// the accelerometer.
+    ///
+    /// `intrinsics` are usually retrieved with
+    /// [`StreamProfile::motion_intrinsics`](crate::stream_profile::StreamProfile::motion_intrinsics).
+    /// See [`Rs2MotionDeviceIntrinsics::correct`] for when the correction should be applied.
+    pub fn corrected_acceleration(&self, intrinsics: &Rs2MotionDeviceIntrinsics) -> [f32; 3] {
+        intrinsics.correct(self.motion)
+    }
 }
 
 impl GyroFrame {
@@ -298,6 +309,19 @@ impl GyroFrame {
     pub fn rotational_velocity(&self) -> &[f32; 3] {
         &self.motion
     }
+
+    /// Returns the rotational velocity recorded in the Gyro frame, corrected for the scale and
+    /// bias of the gyroscope.
+    ///
+    /// `intrinsics` are usually retrieved with
+    /// [`StreamProfile::motion_intrinsics`](crate::stream_profile::StreamProfile::motion_intrinsics).
+    /// See [`Rs2MotionDeviceIntrinsics::correct`] for when the correction should be applied.
+    pub fn corrected_rotational_velocity(
+        &self,
+        intrinsics: &Rs2MotionDeviceIntrinsics,
+    ) -> [f32; 3] {
+        intrinsics.correct(self.motion)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,471 @@
+//! Types for estimating the orientation of a device from its IMU.
+//!
+//! An [`OrientationEstimator`] integrates the gyroscope to track the orientation of the device,
+//! and uses the accelerometer as a reference for the direction of gravity to correct the drift of
+//! the gyroscope in pitch and roll. Heading (rotation about the vertical) cannot be observed from
+//! gravity, so it is only tracked relative to the heading when the estimator started.
+//!
+//! Two filters blend the gyroscope and accelerometer:
+//!
+//! - [`ComplementaryFilter`] mixes a small fraction of the accelerometer tilt into the integrated
+//!   gyroscope at every sample, as the `rs-motion` example of librealsense does.
+//! - [`MadgwickFilter`] follows the gradient of the gravity error, as in S. Madgwick, "An efficient
+//!   orientation filter for inertial and inertial/magnetic sensor arrays" (2010).
+//!
+//! Orientations are unit quaternions ordered as Qi, Qj, Qk, Qr, like
+//! [`PoseFrame::rotation`](crate::frame::PoseFrame::rotation), and rotate the device's coordinate
+//! frame into a gravity-aligned world frame.
+
+use super::imu::ImuSample;
+
+/// The direction opposite to gravity in the coordinate frame of D400 IMUs (whose Y axis points
+/// down), when the device is level.
+pub const D400_UP: [f32; 3] = [0.0, -1.0, 0.0];
+
+/// The direction opposite to gravity in the coordinate frame of T265 IMUs (whose Y axis points
+/// up), when the device is level.
+pub const T265_UP: [f32; 3] = [0.0, 1.0, 0.0];
+
+/// The world Z axis, which filters keep pointing up.
+const Z: [f32; 3] = [0.0, 0.0, 1.0];
+
+/// A filter tracking orientation from accelerometer and gyroscope measurements.
+///
+/// Filters track the rotation from the device's coordinate frame to a world frame whose Z axis
+/// points up, away from gravity. See [`OrientationEstimator`] for orientations relative to a level
+/// device instead.
+pub trait OrientationFilter {
+    /// Set the orientation, as a unit quaternion.
+    fn reset(&mut self, orientation: [f32; 4]);
+
+    /// Update the orientation with an acceleration (in any units, as only its direction is used),
+    /// a rotational velocity in radians/s, and the time since the previous update in seconds.
+    fn integrate(&mut self, accel: [f32; 3], gyro: [f32; 3], dt: f32);
+
+    /// Get the orientation, as a unit quaternion.
+    fn orientation(&self) -> [f32; 4];
+}
+
+/// A complementary filter, blending integrated gyroscope with accelerometer tilt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplementaryFilter {
+    /// The weight of the gyroscope, between 0 and 1.
+    alpha: f32,
+    /// The orientation of the device.
+    orientation: [f32; 4],
+}
+
+impl ComplementaryFilter {
+    /// Construct a filter that weighs the gyroscope by `alpha` and the accelerometer by
+    /// `1 - alpha` at every update.
+    ///
+    /// An `alpha` close to 1 (e.g. `0.98`) trusts the gyroscope in the short term, while still
+    /// correcting its drift over a few seconds.
+    pub fn new(alpha: f32) -> Self {
+        ComplementaryFilter {
+            alpha: alpha.clamp(0.0, 1.0),
+            orientation: IDENTITY,
+        }
+    }
+}
+
+impl OrientationFilter for ComplementaryFilter {
+    fn reset(&mut self, orientation: [f32; 4]) {
+        self.orientation = normalize(orientation);
+    }
+
+    fn integrate(&mut self, accel: [f32; 3], gyro: [f32; 3], dt: f32) {
+        let predicted = multiply(self.orientation, from_rotation_vector(gyro.map(|w| w * dt)));
+
+        // Rotate the orientation towards the one where the measured gravity points down, by the
+        // accelerometer's share of the way.
+        let correction = match normalize_vector(rotate(predicted, accel)) {
+            Some(up) => {
+                let axis = cross(up, Z);
+                let angle = dot(up, Z).clamp(-1.0, 1.0).acos();
+                match normalize_vector(axis) {
+                    Some(axis) => {
+                        from_rotation_vector(axis.map(|a| a * angle * (1.0 - self.alpha)))
+                    }
+                    None => IDENTITY,
+                }
+            }
+            None => IDENTITY,
+        };
+
+        self.orientation = normalize(multiply(correction, predicted));
+    }
+
+    fn orientation(&self) -> [f32; 4] {
+        self.orientation
+    }
+}
+
+/// A Madgwick filter, correcting the integrated gyroscope along the gradient of the gravity error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MadgwickFilter {
+    /// The gain of the gradient step, in radians/s.
+    beta: f32,
+    /// The orientation of the device.
+    orientation: [f32; 4],
+}
+
+impl MadgwickFilter {
+    /// Construct a filter with gain `beta`, in radians/s.
+    ///
+    /// `beta` should be close to the expected gyroscope error, e.g. `0.05` to `0.1`. Larger gains
+    /// converge faster but let more accelerometer noise through.
+    pub fn new(beta: f32) -> Self {
+        MadgwickFilter {
+            beta,
+            orientation: IDENTITY,
+        }
+    }
+}
+
+impl OrientationFilter for MadgwickFilter {
+    fn reset(&mut self, orientation: [f32; 4]) {
+        self.orientation = normalize(orientation);
+    }
+
+    fn integrate(&mut self, accel: [f32; 3], gyro: [f32; 3], dt: f32) {
+        let q = self.orientation;
+        let [x, y, z, w] = q;
+
+        // The rate of change of the orientation from the gyroscope.
+        let mut rate = multiply(q, [gyro[0], gyro[1], gyro[2], 0.0]).map(|c| 0.5 * c);
+
+        if let Some([ax, ay, az]) = normalize_vector(accel) {
+            // The error between the predicted and measured direction of gravity, and its Jacobian
+            // with respect to (w, x, y, z).
+            let error = [
+                2.0 * (x * z - w * y) - ax,
+                2.0 * (w * x + y * z) - ay,
+                1.0 - 2.0 * (x * x + y * y) - az,
+            ];
+            let jacobian = [
+                [-2.0 * y, 2.0 * z, -2.0 * w, 2.0 * x],
+                [2.0 * x, 2.0 * w, 2.0 * z, 2.0 * y],
+                [0.0, -4.0 * x, -4.0 * y, 0.0],
+            ];
+
+            let mut gradient = [0.0; 4];
+            for (row, e) in jacobian.iter().zip(error.iter()) {
+                for (g, j) in gradient.iter_mut().zip(row.iter()) {
+                    *g += j * e;
+                }
+            }
+
+            let norm = gradient.iter().map(|g| g * g).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                // The gradient is ordered (w, x, y, z), the rate (x, y, z, w).
+                let [gw, gx, gy, gz] = gradient.map(|g| g / norm);
+                for (r, g) in rate.iter_mut().zip([gx, gy, gz, gw].iter()) {
+                    *r -= self.beta * g;
+                }
+            }
+        }
+
+        let mut integrated = q;
+        for (c, r) in integrated.iter_mut().zip(rate.iter()) {
+            *c += r * dt;
+        }
+        self.orientation = normalize(integrated);
+    }
+
+    fn orientation(&self) -> [f32; 4] {
+        self.orientation
+    }
+}
+
+/// Estimates the orientation of a device relative to level, from [`ImuSample`]s.
+///
+/// The estimator starts from the tilt measured by the first accelerometer sample, so the device
+/// does not need to be level when it starts. Orientations are relative to the device being level
+/// (i.e. its `up` axis pointing away from gravity) with the heading it started with.
+#[derive(Debug, Clone)]
+pub struct OrientationEstimator<F> {
+    /// The filter tracking the orientation.
+    filter: F,
+    /// The rotation from the level device frame to the filter's world frame.
+    level: [f32; 4],
+    /// The timestamp of the previous sample, in milliseconds.
+    last_timestamp: Option<f64>,
+    /// The acceleration of the previous sample.
+    last_accel: [f32; 3],
+}
+
+impl<F: OrientationFilter> OrientationEstimator<F> {
+    /// Construct an estimator from a filter, given the direction opposite to gravity in the
+    /// device's coordinate frame when it is level (e.g. [`D400_UP`] or [`T265_UP`]).
+    pub fn new(filter: F, up: [f32; 3]) -> Self {
+        OrientationEstimator {
+            filter,
+            level: between(up, Z),
+            last_timestamp: None,
+            last_accel: up,
+        }
+    }
+
+    /// Get the filter tracking the orientation.
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    /// Update the orientation with a sample.
+    ///
+    /// Samples older than the previous one are ignored.
+    pub fn update(&mut self, sample: &ImuSample) {
+        match self.last_timestamp {
+            None => self.filter.reset(gravity_alignment(sample.accel)),
+            Some(last) if sample.timestamp > last => {
+                let dt = ((sample.timestamp - last) / 1000.0) as f32;
+                self.filter.integrate(sample.accel, sample.gyro, dt);
+            }
+            Some(_) => return,
+        }
+
+        self.last_timestamp = Some(sample.timestamp);
+        self.last_accel = sample.accel;
+    }
+
+    /// Snap the pitch and roll of the orientation to the latest accelerometer sample, keeping the
+    /// heading.
+    ///
+    /// This is useful after the device was held still, to discard the tilt error accumulated while
+    /// it was moving.
+    pub fn relevel(&mut self) {
+        let orientation = self.filter.orientation();
+        if let Some(up) = normalize_vector(rotate(orientation, self.last_accel)) {
+            self.filter.reset(multiply(between(up, Z), orientation));
+        }
+    }
+
+    /// Get the orientation of the device relative to level, as a unit quaternion.
+    ///
+    /// The orientation rotates the device's coordinate frame into the coordinate frame of the
+    /// device when it is level, at the heading it started with.
+    pub fn orientation(&self) -> [f32; 4] {
+        normalize(multiply(conjugate(self.level), self.filter.orientation()))
+    }
+
+    /// Get the angle between the device's up axis and the direction opposite to gravity, in
+    /// radians.
+    pub fn tilt(&self) -> f32 {
+        let up = rotate(conjugate(self.level), Z);
+        let device_up = rotate(self.orientation(), up);
+        dot(device_up, up).clamp(-1.0, 1.0).acos()
+    }
+}
+
+/// Get the orientation that rotates a measured acceleration to point up along the world Z axis,
+/// without any rotation about Z.
+///
+/// At rest, the accelerometer measures the reaction to gravity, which points up.
+pub fn gravity_alignment(accel: [f32; 3]) -> [f32; 4] {
+    match normalize_vector(accel) {
+        Some(up) => between(up, Z),
+        None => IDENTITY,
+    }
+}
+
+/// The identity rotation.
+pub(crate) const IDENTITY: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// The Hamilton product of two quaternions, i.e. the rotation `b` followed by `a`.
+pub(crate) fn multiply(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let [ax, ay, az, aw] = a;
+    let [bx, by, bz, bw] = b;
+    [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+}
+
+/// The conjugate of a quaternion, i.e. the inverse rotation of a unit quaternion.
+pub(crate) fn conjugate(q: [f32; 4]) -> [f32; 4] {
+    [-q[0], -q[1], -q[2], q[3]]
+}
+
+/// Scale a quaternion to unit length.
+pub(crate) fn normalize(q: [f32; 4]) -> [f32; 4] {
+    let norm = q.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        q.map(|c| c / norm)
+    } else {
+        IDENTITY
+    }
+}
+
+/// Rotate a vector by a unit quaternion.
+pub(crate) fn rotate(q: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+    let [x, y, z, _] = multiply(multiply(q, [v[0], v[1], v[2], 0.0]), conjugate(q));
+    [x, y, z]
+}
+
+/// The rotation about `axis * angle`, given as a single rotation vector in radians.
+pub(crate) fn from_rotation_vector(v: [f32; 3]) -> [f32; 4] {
+    let angle = dot(v, v).sqrt();
+    if angle == 0.0 {
+        return IDENTITY;
+    }
+
+    let s = (angle / 2.0).sin() / angle;
+    [v[0] * s, v[1] * s, v[2] * s, (angle / 2.0).cos()]
+}
+
+/// The shortest rotation from one unit vector to another.
+pub(crate) fn between(from: [f32; 3], to: [f32; 3]) -> [f32; 4] {
+    let axis = cross(from, to);
+    let w = 1.0 + dot(from, to);
+    if w > 1e-6 {
+        return normalize([axis[0], axis[1], axis[2], w]);
+    }
+
+    // The vectors are opposite, so turn half way around any perpendicular axis.
+    let other = if from[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let [x, y, z] = normalize_vector(cross(from, other)).unwrap_or(other);
+    [x, y, z, 0.0]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize_vector(v: [f32; 3]) -> Option<[f32; 3]> {
+    let norm = dot(v, v).sqrt();
+    if norm > 0.0 && norm.is_finite() {
+        Some(v.map(|c| c / norm))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The acceleration measured by a D400 IMU at rest, pitched down by 30 degrees about X.
+    fn pitched_accel() -> [f32; 3] {
+        let angle = 30f32.to_radians();
+        [0.0, -9.81 * angle.cos(), 9.81 * angle.sin()]
+    }
+
+    fn sample(timestamp: f64, accel: [f32; 3], gyro: [f32; 3]) -> ImuSample {
+        ImuSample {
+            timestamp,
+            accel,
+            gyro,
+            accel_frame_number: 0,
+            gyro_frame_number: 0,
+        }
+    }
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        // q and -q are the same rotation.
+        let sign = if dot4(actual, expected) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!(
+                (a * sign - e).abs() < 1e-3,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    fn dot4(a: [f32; 4], b: [f32; 4]) -> f32 {
+        a.iter().zip(b.iter()).map(|(a, b)| a * b).sum()
+    }
+
+    #[test]
+    fn quaternion_helpers_agree_with_extrinsics() {
+        let q = normalize([0.1, 0.2, 0.3, 0.9]);
+        let extrinsics = crate::base::Rs2Extrinsics::from_quaternion(q, [0.0; 3]);
+        let v = [1.0, -2.0, 0.5];
+        let (a, b) = (rotate(q, v), extrinsics.transform_point(v));
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-5);
+        }
+
+        assert_close(
+            between([1.0, 0.0, 0.0], [-1.0, 0.0, 0.0]),
+            [0.0, 0.0, 1.0, 0.0],
+        );
+        let half_turn = from_rotation_vector([0.0, 0.0, std::f32::consts::PI]);
+        assert_close(half_turn, [0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn starts_level_from_gravity() {
+        let mut estimator = OrientationEstimator::new(ComplementaryFilter::new(0.98), D400_UP);
+        estimator.update(&sample(0.0, [0.0, -9.81, 0.0], [0.0; 3]));
+        assert_close(estimator.orientation(), IDENTITY);
+        assert!(estimator.tilt() < 1e-3);
+
+        let mut estimator = OrientationEstimator::new(MadgwickFilter::new(0.1), D400_UP);
+        estimator.update(&sample(0.0, pitched_accel(), [0.0; 3]));
+        assert!((estimator.tilt() - 30f32.to_radians()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn integrates_gyro() {
+        // Spin about the vertical at 90 degrees/s for one second.
+        let mut estimator = OrientationEstimator::new(MadgwickFilter::new(0.1), T265_UP);
+        let rate = 90f32.to_radians();
+        for i in 0..=200 {
+            estimator.update(&sample(i as f64 * 5.0, [0.0, 9.81, 0.0], [0.0, rate, 0.0]));
+        }
+
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(estimator.orientation(), [0.0, half, 0.0, half]);
+        assert!(estimator.tilt() < 1e-3);
+    }
+
+    #[test]
+    fn accelerometer_corrects_drift() {
+        // Both filters start level, then converge to the pitched gravity despite no rotation being
+        // measured by the gyroscope.
+        fn converge<F: OrientationFilter>(filter: F) -> f32 {
+            let mut estimator = OrientationEstimator::new(filter, D400_UP);
+            estimator.update(&sample(0.0, [0.0, -9.81, 0.0], [0.0; 3]));
+            for i in 1..=2000 {
+                estimator.update(&sample(i as f64 * 5.0, pitched_accel(), [0.0; 3]));
+            }
+            estimator.tilt()
+        }
+
+        let expected = 30f32.to_radians();
+        assert!((converge(ComplementaryFilter::new(0.98)) - expected).abs() < 1e-2);
+        assert!((converge(MadgwickFilter::new(0.1)) - expected).abs() < 1e-2);
+    }
+
+    #[test]
+    fn relevels_from_latest_accel() {
+        let mut estimator = OrientationEstimator::new(ComplementaryFilter::new(1.0), D400_UP);
+        estimator.update(&sample(0.0, [0.0, -9.81, 0.0], [0.0; 3]));
+        estimator.update(&sample(5.0, pitched_accel(), [0.0; 3]));
+        assert!(estimator.tilt() < 1e-3);
+
+        estimator.relevel();
+        assert!((estimator.tilt() - 30f32.to_radians()).abs() < 1e-3);
+    }
+}
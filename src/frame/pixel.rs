@@ -47,6 +47,58 @@ pub enum PixelKind<'a> {
     Disparity32 { disparity: &'a f32 },
     /// 32-bit floating point 3D coordinates.
     Xyz32f { x: &'a f32, y: &'a f32, z: &'a f32 },
+    /// 16-bit raw disparity values, as produced by the stereo depth hardware.
+    Disparity16 { disparity: &'a u16 },
+    /// 10-bit raw image, unpacked from four pixels packed into a 5-byte macropixel.
+    Raw10 { val: u16 },
+    /// 16-bit raw image.
+    Raw16 { val: &'a u16 },
+    /// 10-bit per-pixel grayscale image, unpacked into the least significant bits of 16-bit values.
+    Y10Bpack { y: &'a u16 },
+    /// 8-bit per-pixel grayscale stereo pair, interleaved as left then right.
+    Y8I { left: &'a u8, right: &'a u8 },
+    /// 12-bit per-pixel grayscale stereo pair, unpacked from a 24-bit little-endian word.
+    Y12I { left: u16, right: u16 },
+    /// 10-bit per-pixel grayscale image, unpacked from four pixels packed into 5 bytes.
+    W10 { y: u16 },
+    /// 16-bit depth with the accompanying 10-bit infrared value, split from their separate planes.
+    Inzi { depth: &'a u16, ir: &'a u16 },
+    /// 8-bit per-pixel infrared image.
+    Invi { ir: &'a u8 },
+    /// 16-bit per-pixel frame grabber data.
+    Fg { val: &'a u16 },
+    /// 12-bit per-pixel YUV 4:1:1 data, shared across every 2x2 block of pixels.
+    Y411 { y: &'a u8, u: &'a u8, v: &'a u8 },
+    /// Motion-JPEG compressed frame.
+    ///
+    /// Compressed pixels cannot be addressed individually, so this holds the entire compressed
+    /// buffer regardless of which pixel was requested.
+    Mjpeg { data: &'a [u8] },
+    /// Huffman-compressed 16-bit depth frame.
+    ///
+    /// Compressed pixels cannot be addressed individually, so this holds the entire compressed
+    /// buffer regardless of which pixel was requested.
+    Z16H { data: &'a [u8] },
+}
+
+/// Predicate for whether frames in `format` hold variable-length compressed data.
+///
+/// The size of these frames cannot be derived from their dimensions, and their pixels cannot be
+/// addressed individually.
+pub(crate) fn is_compressed(format: Rs2Format) -> bool {
+    matches!(format, Rs2Format::Mjpeg | Rs2Format::Z16H)
+}
+
+/// Unpack the pixel at `index` (0-3) of a 5-byte macropixel holding four 10-bit values.
+///
+/// The first four bytes hold the 8 most significant bits of each pixel, and the fifth byte holds
+/// the 2 least significant bits of each pixel, starting from the lowest bits.
+#[inline]
+fn unpack_10bit(macropixel: &[u8], index: usize) -> u16 {
+    let msb = macropixel[index] as u16;
+    let lsb = (macropixel[4] >> (2 * index)) as u16 & 0b11;
+
+    (msb << 2) | lsb
 }
 
 /// Method to retrieve a pixel from a given rs2_frame in the requested Pixel format.
@@ -241,8 +293,251 @@ pub(crate) unsafe fn get_pixel<'a>(
                 z: slice.get_unchecked(offset + 2),
             }
         }
-        _ => {
-            panic!("Unsupported video format.");
+        Rs2Format::Disparity16 => {
+            let size = data_size_in_bytes / std::mem::size_of::<u16>();
+            let stride = stride_in_bytes / std::mem::size_of::<u16>();
+            let slice = slice::from_raw_parts(data.cast::<u16>(), size);
+            let offset = (row * stride) + col;
+
+            PixelKind::Disparity16 {
+                disparity: slice.get_unchecked(offset),
+            }
+        }
+        // RAW10 and W10 pack four pixels into five bytes. The offset for the macropixel follows
+        // the same idea as YUYV, except with groups of four pixels instead of two:
+        //
+        // offset = (row * stride) + (col / 4) * 5
+        //
+        Rs2Format::Raw10 => {
+            let slice = slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes);
+            let offset = (row * stride_in_bytes) + (col / 4) * 5;
+
+            PixelKind::Raw10 {
+                val: unpack_10bit(slice.get_unchecked(offset..offset + 5), col % 4),
+            }
+        }
+        Rs2Format::W10 => {
+            let slice = slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes);
+            let offset = (row * stride_in_bytes) + (col / 4) * 5;
+
+            PixelKind::W10 {
+                y: unpack_10bit(slice.get_unchecked(offset..offset + 5), col % 4),
+            }
+        }
+        Rs2Format::Raw16 => {
+            let size = data_size_in_bytes / std::mem::size_of::<u16>();
+            let stride = stride_in_bytes / std::mem::size_of::<u16>();
+            let slice = slice::from_raw_parts(data.cast::<u16>(), size);
+            let offset = (row * stride) + col;
+
+            PixelKind::Raw16 {
+                val: slice.get_unchecked(offset),
+            }
+        }
+        // Y10BPACK is already unpacked into 16-bit values by librealsense2 by the time we see it,
+        // so it can be indexed just like Y16.
+        //
+        Rs2Format::Y10Bpack => {
+            let size = data_size_in_bytes / std::mem::size_of::<u16>();
+            let stride = stride_in_bytes / std::mem::size_of::<u16>();
+            let slice = slice::from_raw_parts(data.cast::<u16>(), size);
+            let offset = (row * stride) + col;
+
+            PixelKind::Y10Bpack {
+                y: slice.get_unchecked(offset),
+            }
+        }
+        // Y8I interleaves the left and right imagers, so each pixel is two bytes wide.
+        //
+        Rs2Format::Y8I => {
+            let slice = slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes);
+            let offset = (row * stride_in_bytes) + (col * 2);
+
+            PixelKind::Y8I {
+                left: slice.get_unchecked(offset),
+                right: slice.get_unchecked(offset + 1),
+            }
+        }
+        // Y12I interleaves the left and right imagers into a 24-bit little-endian word per pixel.
+        // Reading the word from the lowest bit, it holds the 12 bits of the right pixel followed
+        // by the 12 bits of the left pixel.
+        //
+        Rs2Format::Y12I => {
+            let slice = slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes);
+            let offset = (row * stride_in_bytes) + (col * 3);
+
+            let b0 = *slice.get_unchecked(offset) as u16;
+            let b1 = *slice.get_unchecked(offset + 1) as u16;
+            let b2 = *slice.get_unchecked(offset + 2) as u16;
+
+            PixelKind::Y12I {
+                left: (b2 << 4) | (b1 >> 4),
+                right: ((b1 & 0x0F) << 8) | b0,
+            }
+        }
+        // INZI is multi-planar: the full infrared plane is followed by the full depth plane, each
+        // holding one 16-bit value per pixel. The offset within each plane is the same.
+        //
+        Rs2Format::Inzi => {
+            let size = data_size_in_bytes / std::mem::size_of::<u16>();
+            let stride = stride_in_bytes / std::mem::size_of::<u16>();
+            let slice = slice::from_raw_parts(data.cast::<u16>(), size);
+            let offset = (row * stride) + col;
+
+            PixelKind::Inzi {
+                ir: slice.get_unchecked(offset),
+                depth: slice.get_unchecked((size / 2) + offset),
+            }
+        }
+        Rs2Format::Invi => {
+            let slice = slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes);
+            let offset = (row * stride_in_bytes) + col;
+
+            PixelKind::Invi {
+                ir: slice.get_unchecked(offset),
+            }
+        }
+        Rs2Format::Fg => {
+            let size = data_size_in_bytes / std::mem::size_of::<u16>();
+            let stride = stride_in_bytes / std::mem::size_of::<u16>();
+            let slice = slice::from_raw_parts(data.cast::<u16>(), size);
+            let offset = (row * stride) + col;
+
+            PixelKind::Fg {
+                val: slice.get_unchecked(offset),
+            }
+        }
+        // Y411 stores every 2x2 block of pixels in six bytes: u, y00, y01, v, y10, y11. Since a
+        // block spans two rows, we offset by pairs of rows and pairs of columns:
+        //
+        // offset = (row / 2) * (stride * 2) + (col / 2) * 6
+        //
+        Rs2Format::Y411 => {
+            let slice = slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes);
+            let offset = (row / 2) * (stride_in_bytes * 2) + (col / 2) * 6;
+
+            PixelKind::Y411 {
+                y: slice.get_unchecked(offset + 1 + (col % 2) + (row % 2) * 3),
+                u: slice.get_unchecked(offset),
+                v: slice.get_unchecked(offset + 3),
+            }
+        }
+        Rs2Format::Mjpeg => PixelKind::Mjpeg {
+            data: slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes),
+        },
+        Rs2Format::Z16H => PixelKind::Z16H {
+            data: slice::from_raw_parts(data.cast::<u8>(), data_size_in_bytes),
+        },
+        Rs2Format::Any
+        | Rs2Format::MotionRaw
+        | Rs2Format::MotionXyz32F
+        | Rs2Format::GpioRaw
+        | Rs2Format::_6Dof => {
+            panic!("{:?} is not a video format.", format);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the pixel at (col, row) out of a synthetic frame buffer.
+    fn pixel<T>(
+        format: Rs2Format,
+        data: &[T],
+        stride: usize,
+        col: usize,
+        row: usize,
+    ) -> PixelKind<'_> {
+        unsafe {
+            get_pixel(
+                format,
+                std::mem::size_of_val(data),
+                data.as_ptr().cast::<c_void>(),
+                stride,
+                col,
+                row,
+            )
+        }
+    }
+
+    #[test]
+    fn unpacks_raw10() {
+        // Pixels 0x3FF, 0x000, 0x155, 0x2AA packed into a macropixel, followed by a second
+        // macropixel holding a lone 0x001.
+        let data = [0xFFu8, 0x00, 0x55, 0xAA, 0b1001_0011, 0x00, 0, 0, 0, 0b01];
+        let expected = [0x3FF, 0x000, 0x155, 0x2AA, 0x001];
+
+        for (col, &value) in expected.iter().enumerate() {
+            match pixel(Rs2Format::Raw10, &data, 10, col, 0) {
+                PixelKind::Raw10 { val } => assert_eq!(val, value, "column {}", col),
+                other => panic!("Unexpected pixel {:?}", other),
+            }
+        }
+        assert!(matches!(
+            pixel(Rs2Format::W10, &data, 10, 3, 0),
+            PixelKind::W10 { y: 0x2AA }
+        ));
+    }
+
+    #[test]
+    fn splits_y8i_stereo_pair() {
+        let data = [1u8, 2, 3, 4, 5, 6, 7, 8];
+
+        match pixel(Rs2Format::Y8I, &data, 4, 1, 1) {
+            PixelKind::Y8I { left, right } => assert_eq!((*left, *right), (7, 8)),
+            other => panic!("Unexpected pixel {:?}", other),
+        }
+    }
+
+    #[test]
+    fn splits_y12i_stereo_pair() {
+        // Left = 0xABC, right = 0x123
+        let data = [0u8, 0, 0, 0x23, 0xC1, 0xAB];
+
+        match pixel(Rs2Format::Y12I, &data, 6, 1, 0) {
+            PixelKind::Y12I { left, right } => assert_eq!((left, right), (0xABC, 0x123)),
+            other => panic!("Unexpected pixel {:?}", other),
+        }
+    }
+
+    #[test]
+    fn splits_inzi_planes() {
+        // A 2x1 frame: infrared plane followed by depth plane.
+        let data = [10u16, 20, 1000, 2000];
+
+        match pixel(Rs2Format::Inzi, &data, 4, 1, 0) {
+            PixelKind::Inzi { depth, ir } => assert_eq!((*depth, *ir), (2000, 20)),
+            other => panic!("Unexpected pixel {:?}", other),
+        }
+    }
+
+    #[test]
+    fn indexes_y411_blocks() {
+        // A 4x2 frame is two 2x2 blocks, each stored as u, y00, y01, v, y10, y11.
+        let data = [1u8, 10, 11, 2, 12, 13, 3, 20, 21, 4, 22, 23];
+
+        let luma = |col, row| match pixel(Rs2Format::Y411, &data, 6, col, row) {
+            PixelKind::Y411 { y, u, v } => (*y, *u, *v),
+            other => panic!("Unexpected pixel {:?}", other),
+        };
+
+        assert_eq!(luma(0, 0), (10, 1, 2));
+        assert_eq!(luma(1, 1), (13, 1, 2));
+        assert_eq!(luma(2, 1), (22, 3, 4));
+        assert_eq!(luma(3, 0), (21, 3, 4));
+    }
+
+    #[test]
+    fn compressed_formats_return_whole_buffer() {
+        let data = [0xFFu8, 0xD8, 0xFF, 0xD9];
+
+        assert!(is_compressed(Rs2Format::Mjpeg));
+        assert!(!is_compressed(Rs2Format::Z16));
+        match pixel(Rs2Format::Mjpeg, &data, 0, 5, 5) {
+            PixelKind::Mjpeg { data: buffer } => assert_eq!(buffer, &data),
+            other => panic!("Unexpected pixel {:?}", other),
         }
     }
 }
@@ -2,6 +2,12 @@
 //!
 //! A Points frame is a RealSense point cloud storage class.
 
+use super::cloud::UnorganizedPointCloud;
+use super::export::{
+    grid_normals, is_zero_point, sample_colors, write_las, write_pcd, write_ply, ExportError,
+    PcdFormat, PlyOptions,
+};
+use super::image::ColorFrame;
 use super::prelude::{CouldNotGetFrameSensorError, FrameCategory, FrameConstructionError, FrameEx};
 use crate::{
     check_rs2_error,
@@ -14,6 +20,7 @@ use num_traits::FromPrimitive;
 use realsense_sys as sys;
 use std::{
     convert::{TryFrom, TryInto},
+    io::Write,
     ptr::{self, NonNull},
     slice,
 };
@@ -251,6 +258,146 @@ impl PointsFrame {
     pub fn points_count(&self) -> usize {
         self.num_points
     }
+
+    /// Copy the points with a valid depth into an owned point cloud for processing.
+    ///
+    /// Points are textured with `color` if given, sampled at the texture coordinates of each
+    /// point.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError::UnsupportedColorFormat`] if the color frame is not in an 8-bit RGB
+    /// or BGR format.
+    pub fn to_cloud(
+        &self,
+        color: Option<&ColorFrame>,
+    ) -> Result<UnorganizedPointCloud, ExportError> {
+        let points: Vec<[f32; 3]> = self.vertices().iter().map(|v| v.xyz).collect();
+        let kept = |i: &usize| !is_zero_point(&points[*i]);
+
+        let cloud_points = (0..points.len()).filter(kept).map(|i| points[i]).collect();
+        match color {
+            Some(color) => {
+                let colors = sample_colors(color, self.texture_coordinates())?;
+                let cloud_colors = (0..points.len()).filter(kept).map(|i| colors[i]).collect();
+                Ok(
+                    UnorganizedPointCloud::with_colors(cloud_points, cloud_colors)
+                        .expect("Every point has a color."),
+                )
+            }
+            None => Ok(UnorganizedPointCloud::new(cloud_points)),
+        }
+    }
+
+    /// Write the point cloud as a PLY file, like the SDK's `export_to_ply`.
+    ///
+    /// Points can optionally be textured with a color frame, given normals computed from their
+    /// neighbours, and have points without a valid depth left out; see [`PlyOptions`]. Normals
+    /// are computed on the grid of the depth frame the points were calculated from, before any
+    /// points are left out. Unlike `export_to_ply`, no mesh faces are written.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError::UnsupportedColorFormat`] if the color frame is not in an 8-bit RGB
+    /// or BGR format.
+    ///
+    /// Returns [`ExportError::NotOrganized`] if normals are requested but the size of the depth
+    /// frame the points were calculated from cannot be determined.
+    ///
+    /// Returns [`ExportError::CouldNotWrite`] if writing to `writer` fails.
+    pub fn write_ply<W: Write>(&self, writer: W, options: &PlyOptions) -> Result<(), ExportError> {
+        let points: Vec<[f32; 3]> = self.vertices().iter().map(|v| v.xyz).collect();
+
+        let colors = options
+            .color
+            .map(|color| sample_colors(color, self.texture_coordinates()))
+            .transpose()?;
+
+        let normals = if options.normals {
+            let width = self
+                .organized_width()
+                .ok_or(ExportError::NotOrganized(self.num_points))?;
+            Some(grid_normals(&points, width))
+        } else {
+            None
+        };
+
+        write_ply(
+            writer,
+            &points,
+            colors.as_deref(),
+            normals.as_deref(),
+            options.format,
+            options.remove_zero_points,
+        )
+    }
+
+    /// Write the point cloud as a PCL `.pcd` file.
+    ///
+    /// If the size of the depth frame the points were calculated from is known, the cloud is
+    /// written organized with the same width and height, and points without a valid depth are
+    /// written as NaN. Points are textured with `color` if given, sampled at the texture
+    /// coordinates of each point.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError::UnsupportedColorFormat`] if the color frame is not in an 8-bit RGB
+    /// or BGR format.
+    ///
+    /// Returns [`ExportError::CouldNotWrite`] if writing to `writer` fails.
+    pub fn write_pcd<W: Write>(
+        &self,
+        writer: W,
+        format: PcdFormat,
+        color: Option<&ColorFrame>,
+    ) -> Result<(), ExportError> {
+        let points: Vec<[f32; 3]> = self.vertices().iter().map(|v| v.xyz).collect();
+        let colors = color
+            .map(|color| sample_colors(color, self.texture_coordinates()))
+            .transpose()?;
+        let (width, height) = match self.organized_width() {
+            Some(width) => (width, self.num_points / width),
+            None => (self.num_points, 1),
+        };
+
+        write_pcd(writer, width, height, &points, colors.as_deref(), format)
+    }
+
+    /// Write the point cloud as a LAS 1.4 file.
+    ///
+    /// Points without a valid depth are left out. Positions are in meters, in the coordinate
+    /// frame of the depth stream, with a resolution of 0.1mm. Points are textured with `color` if
+    /// given, sampled at the texture coordinates of each point.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError::UnsupportedColorFormat`] if the color frame is not in an 8-bit RGB
+    /// or BGR format.
+    ///
+    /// Returns [`ExportError::CouldNotWrite`] if writing to `writer` fails.
+    pub fn write_las<W: Write>(
+        &self,
+        writer: W,
+        color: Option<&ColorFrame>,
+    ) -> Result<(), ExportError> {
+        let points: Vec<[f32; 3]> = self.vertices().iter().map(|v| v.xyz).collect();
+        let colors = color
+            .map(|color| sample_colors(color, self.texture_coordinates()))
+            .transpose()?;
+
+        write_las(writer, &points, colors.as_deref())
+    }
+
+    /// Get the width of the grid the points are organized in, i.e. the width of the depth frame
+    /// they were calculated from.
+    fn organized_width(&self) -> Option<usize> {
+        let intrinsics = self.frame_stream_profile.intrinsics().ok()?;
+        if intrinsics.width() * intrinsics.height() == self.num_points {
+            Some(intrinsics.width())
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -4,8 +4,12 @@
 //! at a point in time. See the member and function declarations for how these values are stored
 //! and retrieved.
 
-use super::prelude::{CouldNotGetFrameSensorError, FrameCategory, FrameConstructionError, FrameEx};
+use super::{
+    prelude::{CouldNotGetFrameSensorError, FrameCategory, FrameConstructionError, FrameEx},
+    trajectory::Pose,
+};
 use crate::{
+    base::Rs2Extrinsics,
     check_rs2_error,
     kind::{Rs2Extension, Rs2FrameMetadata, Rs2StreamKind, Rs2TimestampDomain},
     sensor::Sensor,
@@ -77,6 +81,47 @@ impl PoseFrame {
         [x, y, z, w]
     }
 
+    /// Get the pose as extrinsics from the device's coordinate frame to the world frame, i.e. the
+    /// coordinate frame of the device when tracking started.
+    pub fn to_extrinsics(&self) -> Rs2Extrinsics {
+        Rs2Extrinsics::from_quaternion(self.rotation(), self.translation())
+    }
+
+    /// Copy the timestamp, translation and rotation of the frame into a [`Pose`].
+    pub fn pose(&self) -> Pose {
+        self.into()
+    }
+
+    /// Get the rotation from the device's coordinate frame to the world frame as a 3x3 matrix.
+    ///
+    /// See [`Pose::rotation_matrix`].
+    pub fn rotation_matrix(&self) -> [[f32; 3]; 3] {
+        self.pose().rotation_matrix()
+    }
+
+    /// Get the rotation as roll, pitch and yaw angles in radians.
+    ///
+    /// See [`Pose::euler_angles`].
+    pub fn euler_angles(&self) -> [f32; 3] {
+        self.pose().euler_angles()
+    }
+
+    /// Get the pose as a 4x4 homogeneous transformation matrix from the device's coordinate frame
+    /// to the world frame.
+    ///
+    /// See [`Pose::to_matrix`].
+    pub fn to_matrix(&self) -> [[f32; 4]; 4] {
+        self.to_extrinsics().to_matrix()
+    }
+
+    /// Get the extrinsics from the device's coordinate frame in this frame to its coordinate frame
+    /// in `reference`.
+    ///
+    /// See [`Pose::relative_to`].
+    pub fn relative_to(&self, reference: &PoseFrame) -> Rs2Extrinsics {
+        self.pose().relative_to(&reference.pose())
+    }
+
     /// X, Y, Z values of angular velocity, in radians/sec
     pub fn angular_velocity(&self) -> [f32; 3] {
         let sys::rs2_vector { x, y, z } = self.data.angular_velocity;
@@ -5,7 +5,9 @@
 //! with the wildcard describing the specialization that goes with that type.
 
 use crate::{
-    kind::{Rs2Exception, Rs2Extension, Rs2FrameMetadata, Rs2StreamKind, Rs2TimestampDomain},
+    kind::{
+        Rs2Exception, Rs2Extension, Rs2Format, Rs2FrameMetadata, Rs2StreamKind, Rs2TimestampDomain,
+    },
     sensor::Sensor,
     stream_profile::StreamProfile,
 };
@@ -65,6 +67,15 @@ pub enum DepthError {
     /// Cannot derive the depth units used.
     #[error("Could not get depth units. Type: {0}; Reason: {1}")]
     CouldNotGetDepthUnits(Rs2Exception, String),
+    /// The frame data is in a format that cannot be interpreted as depth.
+    #[error("Frame format cannot be interpreted as depth: {0:?}")]
+    UnsupportedFormat(Rs2Format),
+    /// The depth frame and the frame it is being combined with have different resolutions.
+    #[error("Frame resolution {2}x{3} does not match depth frame resolution {0}x{1}")]
+    MismatchedResolution(usize, usize, usize, usize),
+    /// The intrinsics being applied to the depth frame are for a different resolution.
+    #[error("Intrinsics resolution {2}x{3} does not match depth frame resolution {0}x{1}")]
+    MismatchedIntrinsics(usize, usize, usize, usize),
 }
 
 /// Occurs when a baseline cannot be derived from a Disparity frame.
@@ -0,0 +1,306 @@
+//! Types for registering depth frames with the geometry of another camera.
+//!
+//! Registration is the native counterpart to the SDK's `Align` processing block. Since it only
+//! needs intrinsics and extrinsics, it can register depth onto any calibrated camera, including
+//! cameras that are not RealSense streams at all.
+//!
+//! Two directions are supported:
+//!
+//! - [`DepthFrame::register_depth_to`](crate::frame::DepthFrame::register_depth_to) produces a
+//!   depth image as seen from the target camera.
+//! - [`DepthFrame::register_image_to_depth`](crate::frame::DepthFrame::register_image_to_depth)
+//!   resamples an image from another camera (e.g. color) into the geometry of the depth frame.
+
+use super::{deprojection::DeprojectionTable, statistics::is_valid_depth};
+use crate::base::{Rs2Extrinsics, Rs2Intrinsics};
+
+/// An image registered into the geometry of another camera.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisteredImage<T> {
+    /// The width of the image in pixels.
+    pub(crate) width: usize,
+    /// The height of the image in pixels.
+    pub(crate) height: usize,
+    /// The pixels of the image, in row-major order.
+    pub(crate) pixels: Vec<T>,
+}
+
+impl<T> RegisteredImage<T> {
+    /// Get the width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get every pixel in the image, in row-major order.
+    pub fn pixels(&self) -> &[T] {
+        &self.pixels
+    }
+
+    /// Take ownership of the pixels, in row-major order.
+    pub fn into_pixels(self) -> Vec<T> {
+        self.pixels
+    }
+
+    /// Given a row and column index, get a pixel in the image.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    pub fn get(&self, col: usize, row: usize) -> Option<&T> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        self.pixels.get(row * self.width + col)
+    }
+}
+
+/// Register a `width` x `height` depth image into the geometry of a target camera.
+///
+/// `depth_at` returns the depth (in meters) of the pixel at a given column and row, and is only
+/// called with coordinates inside the image. Each valid depth pixel is deprojected, transformed
+/// into the target camera by `depth_to_target`, and every target pixel whose center falls within
+/// the footprint of the depth pixel is filled. Where footprints overlap, the nearest depth wins.
+///
+/// The depths written to the target image are measured along the target camera's Z axis. Target
+/// pixels that no depth pixel lands on are zero.
+pub(crate) fn register_depth<F>(
+    width: usize,
+    height: usize,
+    depth_at: F,
+    depth_intrinsics: &Rs2Intrinsics,
+    target_intrinsics: &Rs2Intrinsics,
+    depth_to_target: &Rs2Extrinsics,
+) -> RegisteredImage<f32>
+where
+    F: Fn(usize, usize) -> f32,
+{
+    let target_width = target_intrinsics.width();
+    let target_height = target_intrinsics.height();
+    let mut pixels = vec![0.0f32; target_width * target_height];
+
+    // Project a point on the depth image into the target image.
+    let to_target = |pixel: [f32; 2], depth: f32| {
+        let point = depth_intrinsics.deproject(pixel, depth);
+        let point = depth_to_target.transform_point(point);
+        (target_intrinsics.project(point), point[2])
+    };
+    // The target pixel indices whose centers lie in `[start, end)`, or the pixel nearest to
+    // `center` if the footprint is too small to cover any pixel center.
+    let covered = |start: f32, end: f32, center: f32| {
+        let (first, last) = (start.ceil(), end.ceil() - 1.0);
+        if last >= first {
+            (first, last)
+        } else {
+            let nearest = (center + 0.5).floor();
+            (nearest, nearest)
+        }
+    };
+    // Clip a range of indices to an image dimension, or `None` if it is entirely outside.
+    let clip = |(first, last): (f32, f32), upper: usize| {
+        if first.is_nan() || last.is_nan() || last < 0.0 || first >= upper as f32 {
+            None
+        } else {
+            Some(first.max(0.0) as usize..=(last as usize).min(upper - 1))
+        }
+    };
+
+    for row in 0..height {
+        for col in 0..width {
+            let depth = depth_at(col, row);
+            if !is_valid_depth(depth) {
+                continue;
+            }
+
+            let (center, target_depth) = to_target([col as f32, row as f32], depth);
+            if !is_valid_depth(target_depth) {
+                continue;
+            }
+
+            // The footprint of the pixel is bounded by the projections of its opposite corners.
+            let (p0, _) = to_target([col as f32 - 0.5, row as f32 - 0.5], depth);
+            let (p1, _) = to_target([col as f32 + 0.5, row as f32 + 0.5], depth);
+
+            let xs = covered(p0[0].min(p1[0]), p0[0].max(p1[0]), center[0]);
+            let ys = covered(p0[1].min(p1[1]), p0[1].max(p1[1]), center[1]);
+            let (xs, ys) = match (clip(xs, target_width), clip(ys, target_height)) {
+                (Some(xs), Some(ys)) => (xs, ys),
+                _ => continue,
+            };
+
+            for y in ys {
+                for x in xs.clone() {
+                    let pixel = &mut pixels[y * target_width + x];
+                    if *pixel == 0.0 || target_depth < *pixel {
+                        *pixel = target_depth;
+                    }
+                }
+            }
+        }
+    }
+
+    RegisteredImage {
+        width: target_width,
+        height: target_height,
+        pixels,
+    }
+}
+
+/// Resample an image from a source camera into the geometry of a depth image.
+///
+/// `depth_at` returns the depth (in meters) of the pixel at a given column and row of the depth
+/// image described by `depth_table`, and `source_at` returns the pixel at a given column and row
+/// of the source image. Both are only called with coordinates inside their respective images.
+///
+/// Each valid depth pixel is deprojected, transformed into the source camera by
+/// `depth_to_source`, and takes the value of the source pixel nearest to where it projects. Depth
+/// pixels that are invalid or that project outside of the source image are `None`.
+pub(crate) fn register_to_depth<T, F, G>(
+    depth_table: &DeprojectionTable,
+    depth_at: F,
+    source_intrinsics: &Rs2Intrinsics,
+    depth_to_source: &Rs2Extrinsics,
+    source_at: G,
+) -> RegisteredImage<Option<T>>
+where
+    F: Fn(usize, usize) -> f32,
+    G: Fn(usize, usize) -> T,
+{
+    let source_width = source_intrinsics.width() as f32;
+    let source_height = source_intrinsics.height() as f32;
+    let cloud = depth_table.deproject(depth_at);
+
+    let pixels = cloud
+        .points
+        .iter()
+        .map(|&point| {
+            if point[2].is_nan() {
+                return None;
+            }
+
+            let point = depth_to_source.transform_point(point);
+            if !is_valid_depth(point[2]) {
+                return None;
+            }
+
+            let [x, y] = source_intrinsics.project(point);
+            let (col, row) = ((x + 0.5).floor(), (y + 0.5).floor());
+            if col >= 0.0 && row >= 0.0 && col < source_width && row < source_height {
+                Some(source_at(col as usize, row as usize))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    RegisteredImage {
+        width: cloud.width,
+        height: cloud.height,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kind::Rs2DistortionModel;
+    use num_traits::ToPrimitive;
+    use realsense_sys as sys;
+
+    fn intrinsics() -> Rs2Intrinsics {
+        Rs2Intrinsics(sys::rs2_intrinsics {
+            width: 8,
+            height: 4,
+            ppx: 3.5,
+            ppy: 1.5,
+            fx: 4.0,
+            fy: 4.0,
+            model: Rs2DistortionModel::None.to_u32().unwrap(),
+            coeffs: [0.0; 5],
+        })
+    }
+
+    /// A wall at 2m, with a pillar at 1m in front of it in column 2.
+    fn depth_at(col: usize, _row: usize) -> f32 {
+        if col == 2 {
+            1.0
+        } else {
+            2.0
+        }
+    }
+
+    /// A target camera 0.5m to the left, so that points appear shifted to the right.
+    fn depth_to_target() -> Rs2Extrinsics {
+        Rs2Extrinsics::new(Rs2Extrinsics::identity().rotation(), [0.5, 0.0, 0.0])
+    }
+
+    #[test]
+    fn identity_registration_preserves_depth() {
+        let registered = register_depth(
+            8,
+            4,
+            depth_at,
+            &intrinsics(),
+            &intrinsics(),
+            &Rs2Extrinsics::identity(),
+        );
+
+        for row in 0..4 {
+            for col in 0..8 {
+                assert_eq!(registered.get(col, row), Some(&depth_at(col, row)));
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_depth_wins() {
+        let registered = register_depth(
+            8,
+            4,
+            depth_at,
+            &intrinsics(),
+            &intrinsics(),
+            &depth_to_target(),
+        );
+
+        // The wall shifts by 1 column and the pillar by 2, so the pillar hides part of the wall
+        // and leaves a hole where the wall behind it could not be seen.
+        for row in 0..4 {
+            let cols: Vec<f32> = (0..8)
+                .map(|col| *registered.get(col, row).unwrap())
+                .collect();
+            assert_eq!(cols, vec![0.0, 2.0, 2.0, 0.0, 1.0, 2.0, 2.0, 2.0]);
+        }
+    }
+
+    #[test]
+    fn resamples_source_into_depth_geometry() {
+        let table = DeprojectionTable::new(&intrinsics());
+        let depth_at = |col, row| if row == 0 { 0.0 } else { depth_at(col, row) };
+        let registered = register_to_depth(
+            &table,
+            depth_at,
+            &intrinsics(),
+            &depth_to_target(),
+            |col, _| col,
+        );
+
+        assert_eq!(registered.get(0, 0), Some(&None));
+        let cols: Vec<Option<usize>> = (0..8).map(|col| *registered.get(col, 1).unwrap()).collect();
+        assert_eq!(
+            cols,
+            vec![
+                Some(1),
+                Some(2),
+                Some(4),
+                Some(4),
+                Some(5),
+                Some(6),
+                Some(7),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Types for sampling depth at sub-pixel image coordinates.
+//!
+//! Pixel coordinates follow the librealsense2 convention, where the center of the pixel at column
+//! `c` and row `r` lies at exactly `(c, r)`. See
+//! [`DepthFrame::distance_at`](crate::frame::DepthFrame::distance_at) for how to sample a frame.
+
+use super::statistics::is_valid_depth;
+
+/// The strategy used to compute the depth at a sub-pixel coordinate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthSampling {
+    /// Use the depth of the pixel whose center is closest to the coordinate.
+    Nearest,
+    /// Interpolate between the four pixels surrounding the coordinate.
+    ///
+    /// Invalid pixels are left out of the interpolation, and the weights of the remaining pixels
+    /// are renormalized. The sample is only invalid if all four pixels are invalid.
+    Bilinear,
+    /// Use the median of the valid depths in a square window centered on the nearest pixel.
+    ///
+    /// The window spans `2 * radius + 1` pixels on each side, clipped to the frame. The sample is
+    /// only invalid if every pixel in the window is invalid.
+    Median {
+        /// The number of pixels the window extends from its center in each direction.
+        radius: usize,
+    },
+}
+
+/// The result of sampling depth at a sub-pixel coordinate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthSample {
+    /// The sampled depth, in meters.
+    ///
+    /// This is `0.0` if the sample is not valid.
+    pub depth: f32,
+    /// Whether or not any valid depth contributed to the sample.
+    pub valid: bool,
+}
+
+impl DepthSample {
+    /// A sample for which no valid depth could be found.
+    pub const INVALID: DepthSample = DepthSample {
+        depth: 0.0,
+        valid: false,
+    };
+
+    /// Convert the sample to an `Option`, which is `None` if the sample is not valid.
+    pub fn into_option(self) -> Option<f32> {
+        if self.valid {
+            Some(self.depth)
+        } else {
+            None
+        }
+    }
+}
+
+/// Sample depth at `(x, y)` from a `width` x `height` image.
+///
+/// `depth_at` returns the depth (in meters) of the pixel at a given column and row, and is only
+/// called with coordinates inside the image. Coordinates outside the image, i.e. outside
+/// `[-0.5, width - 0.5) x [-0.5, height - 0.5)`, or that are not finite produce an invalid sample.
+pub(crate) fn sample_depth<F>(
+    width: usize,
+    height: usize,
+    depth_at: F,
+    x: f32,
+    y: f32,
+    mode: DepthSampling,
+) -> DepthSample
+where
+    F: Fn(usize, usize) -> f32,
+{
+    if !x.is_finite() || !y.is_finite() {
+        return DepthSample::INVALID;
+    }
+
+    // The edges of the image lie half a pixel beyond the centers of the outermost pixels.
+    if x < -0.5 || y < -0.5 || x >= width as f32 - 0.5 || y >= height as f32 - 0.5 {
+        return DepthSample::INVALID;
+    }
+
+    // The pixel whose center is closest to the coordinate. Coordinates on the left and top edges
+    // round away from the image, so they are clamped back onto the outermost pixels.
+    let nearest = || {
+        (
+            (x.round() as i64).max(0).min(width as i64 - 1),
+            (y.round() as i64).max(0).min(height as i64 - 1),
+        )
+    };
+
+    // Depth of a pixel if it is inside the image and valid.
+    let valid_depth_at = |col: i64, row: i64| {
+        if col < 0 || row < 0 || col >= width as i64 || row >= height as i64 {
+            return None;
+        }
+
+        let depth = depth_at(col as usize, row as usize);
+        if is_valid_depth(depth) {
+            Some(depth)
+        } else {
+            None
+        }
+    };
+
+    match mode {
+        DepthSampling::Nearest => {
+            let (col, row) = nearest();
+            let sample = valid_depth_at(col, row);
+            sample.map_or(DepthSample::INVALID, |depth| DepthSample {
+                depth,
+                valid: true,
+            })
+        }
+        DepthSampling::Bilinear => {
+            let col = x.floor();
+            let row = y.floor();
+            let dx = x - col;
+            let dy = y - row;
+            let (col, row) = (col as i64, row as i64);
+
+            let neighbours = [
+                (col, row, (1.0 - dx) * (1.0 - dy)),
+                (col + 1, row, dx * (1.0 - dy)),
+                (col, row + 1, (1.0 - dx) * dy),
+                (col + 1, row + 1, dx * dy),
+            ];
+
+            let mut weighted_sum = 0.0;
+            let mut total_weight = 0.0;
+            for &(col, row, weight) in neighbours.iter() {
+                if weight <= 0.0 {
+                    continue;
+                }
+                if let Some(depth) = valid_depth_at(col, row) {
+                    weighted_sum += depth * weight;
+                    total_weight += weight;
+                }
+            }
+
+            if total_weight > 0.0 {
+                DepthSample {
+                    depth: weighted_sum / total_weight,
+                    valid: true,
+                }
+            } else {
+                DepthSample::INVALID
+            }
+        }
+        DepthSampling::Median { radius } => {
+            let (col, row) = nearest();
+            let radius = radius as i64;
+
+            let mut window = Vec::new();
+            for r in (row - radius)..=(row + radius) {
+                for c in (col - radius)..=(col + radius) {
+                    if let Some(depth) = valid_depth_at(c, r) {
+                        window.push(depth);
+                    }
+                }
+            }
+
+            if window.is_empty() {
+                return DepthSample::INVALID;
+            }
+
+            // Every depth in the window is valid (and so finite), so this ordering is total.
+            window.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let n = window.len();
+            let depth = if n % 2 == 1 {
+                window[n / 2]
+            } else {
+                (window[n / 2 - 1] + window[n / 2]) / 2.0
+            };
+
+            DepthSample { depth, valid: true }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3x3 synthetic depth image, with one invalid pixel in the middle of the top row.
+    const DEPTHS: [[f32; 3]; 3] = [[1.0, 0.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]];
+
+    fn sample(x: f32, y: f32, mode: DepthSampling) -> DepthSample {
+        sample_depth(3, 3, |col, row| DEPTHS[row][col], x, y, mode)
+    }
+
+    #[test]
+    fn nearest_rounds_to_pixel_center() {
+        assert_eq!(
+            sample(1.4, 1.6, DepthSampling::Nearest).into_option(),
+            Some(8.0)
+        );
+        assert_eq!(
+            sample(1.0, 0.2, DepthSampling::Nearest),
+            DepthSample::INVALID
+        );
+        assert_eq!(
+            sample(-0.6, 0.0, DepthSampling::Nearest),
+            DepthSample::INVALID
+        );
+        assert_eq!(
+            sample(f32::NAN, 0.0, DepthSampling::Nearest),
+            DepthSample::INVALID
+        );
+    }
+
+    #[test]
+    fn bilinear_interpolates_valid_neighbours() {
+        let depth = sample(1.5, 1.5, DepthSampling::Bilinear)
+            .into_option()
+            .unwrap();
+        assert!((depth - 7.0).abs() < 1e-6);
+
+        // Exactly on a pixel center only that pixel contributes.
+        assert_eq!(
+            sample(2.0, 2.0, DepthSampling::Bilinear).into_option(),
+            Some(9.0)
+        );
+
+        // The invalid pixel at (1, 0) is left out of the interpolation.
+        let depth = sample(0.5, 0.0, DepthSampling::Bilinear)
+            .into_option()
+            .unwrap();
+        assert_eq!(depth, 1.0);
+    }
+
+    #[test]
+    fn bilinear_is_invalid_without_valid_neighbours() {
+        assert_eq!(
+            sample(1.0, 0.0, DepthSampling::Bilinear),
+            DepthSample::INVALID
+        );
+        assert_eq!(
+            sample(5.0, 5.0, DepthSampling::Bilinear),
+            DepthSample::INVALID
+        );
+    }
+
+    #[test]
+    fn median_ignores_invalid_depths() {
+        // Window is the entire image minus the invalid pixel: [1, 3, 4, 5, 6, 7, 8, 9].
+        let depth = sample(1.0, 1.0, DepthSampling::Median { radius: 1 }).into_option();
+        assert_eq!(depth, Some(5.5));
+
+        // Window clipped to the top-left corner: [1, 4, 5].
+        let depth = sample(0.0, 0.0, DepthSampling::Median { radius: 1 }).into_option();
+        assert_eq!(depth, Some(4.0));
+
+        let sample = sample(1.0, 0.0, DepthSampling::Median { radius: 0 });
+        assert_eq!(sample, DepthSample::INVALID);
+    }
+
+    #[test]
+    fn coordinates_outside_the_image_are_invalid() {
+        let modes = [
+            DepthSampling::Nearest,
+            DepthSampling::Bilinear,
+            DepthSampling::Median { radius: 1 },
+        ];
+        for &mode in modes.iter() {
+            assert_eq!(sample(-1.0, 1.0, mode), DepthSample::INVALID);
+            assert_eq!(sample(-0.6, 1.0, mode), DepthSample::INVALID);
+            assert_eq!(sample(1.0, 2.5, mode), DepthSample::INVALID);
+            assert!(sample(2.4, 1.0, mode).valid);
+        }
+    }
+
+    #[test]
+    fn coordinates_on_the_edge_sample_the_outermost_pixels() {
+        assert_eq!(
+            sample(-0.5, -0.5, DepthSampling::Nearest).into_option(),
+            Some(1.0)
+        );
+        assert_eq!(
+            sample(-0.5, 1.0, DepthSampling::Median { radius: 0 }).into_option(),
+            Some(4.0)
+        );
+        assert_eq!(
+            sample(-0.5, -0.5, DepthSampling::Bilinear).into_option(),
+            Some(1.0)
+        );
+    }
+}
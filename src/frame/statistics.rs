@@ -0,0 +1,343 @@
+//! Types for summarizing the depth values held in a depth frame.
+//!
+//! These types back the [`DepthFrame::statistics`](crate::frame::DepthFrame::statistics) and
+//! [`DepthFrame::histogram`](crate::frame::DepthFrame::histogram) methods. They are also usable on
+//! their own, which is helpful if you are working with depth values you've already copied out of a
+//! frame.
+//!
+//! All depths passed into these types are expected to be in meters. A depth of zero (or any value
+//! that is not a finite, positive number) is considered invalid, which matches how librealsense2
+//! marks pixels for which no depth could be computed.
+
+use crate::base::Rs2Roi;
+use std::ops::Range;
+use thiserror::Error;
+
+/// Occurs when a depth histogram cannot be constructed from the requested configuration.
+#[derive(Error, Debug)]
+pub enum HistogramError {
+    /// The histogram was requested with zero bins.
+    #[error("A depth histogram requires at least one bin.")]
+    NoBins,
+    /// The requested depth range is empty, inverted, or not finite.
+    #[error("Invalid histogram depth range: [{0}, {1}].")]
+    InvalidRange(f32, f32),
+}
+
+/// Predicate for whether a depth (in meters) is a valid measurement.
+#[inline]
+pub(crate) fn is_valid_depth(depth: f32) -> bool {
+    depth.is_finite() && depth > 0.0
+}
+
+/// Clamps an optional region of interest to the bounds of a `width` x `height` image.
+///
+/// The bounds of an [`Rs2Roi`] are inclusive, so this returns the half-open column and row ranges
+/// that cover the region. If `roi` is `None` the ranges cover the entire image. A region lying
+/// entirely outside the image produces empty ranges.
+pub(crate) fn roi_ranges(
+    roi: Option<&Rs2Roi>,
+    width: usize,
+    height: usize,
+) -> (Range<usize>, Range<usize>) {
+    match roi {
+        None => (0..width, 0..height),
+        Some(roi) => {
+            let clamp = |value: i32, upper: usize| (value.max(0) as usize).min(upper);
+            let min_x = clamp(roi.min_x, width);
+            let min_y = clamp(roi.min_y, height);
+            let max_x = clamp(roi.max_x.saturating_add(1), width).max(min_x);
+            let max_y = clamp(roi.max_y.saturating_add(1), height).max(min_y);
+
+            (min_x..max_x, min_y..max_y)
+        }
+    }
+}
+
+/// Summary statistics of the depth values in a depth frame (or a region of one).
+///
+/// All depth values are reported in meters. Since a frame may contain no valid depth at all (e.g.
+/// when the camera is fully occluded), the depth values are only present if at least one valid
+/// pixel was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthStatistics {
+    /// The total number of pixels considered, valid or not.
+    pub total_pixels: usize,
+    /// The number of pixels that held a valid depth.
+    pub valid_pixels: usize,
+    /// The closest valid depth.
+    pub min: Option<f32>,
+    /// The furthest valid depth.
+    pub max: Option<f32>,
+    /// The mean of all valid depths.
+    pub mean: Option<f32>,
+    /// The median of all valid depths.
+    pub median: Option<f32>,
+}
+
+impl DepthStatistics {
+    /// Compute statistics over a sequence of depth values, in meters.
+    ///
+    /// Invalid depths (zero, negative, or non-finite values) are counted towards
+    /// [`total_pixels`](DepthStatistics::total_pixels) but are otherwise ignored.
+    pub fn from_depths<I>(depths: I) -> Self
+    where
+        I: IntoIterator<Item = f32>,
+    {
+        let mut total_pixels = 0;
+        let mut sum = 0f64;
+        let mut valid = Vec::new();
+
+        for depth in depths {
+            total_pixels += 1;
+
+            if is_valid_depth(depth) {
+                sum += depth as f64;
+                valid.push(depth);
+            }
+        }
+
+        if valid.is_empty() {
+            return DepthStatistics {
+                total_pixels,
+                valid_pixels: 0,
+                min: None,
+                max: None,
+                mean: None,
+                median: None,
+            };
+        }
+
+        // Every depth in `valid` is finite, so this ordering is total.
+        valid.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let n = valid.len();
+        let median = if n % 2 == 0 {
+            (valid[n / 2 - 1] + valid[n / 2]) / 2.0
+        } else {
+            valid[n / 2]
+        };
+
+        DepthStatistics {
+            total_pixels,
+            valid_pixels: n,
+            min: Some(valid[0]),
+            max: Some(valid[n - 1]),
+            mean: Some((sum / n as f64) as f32),
+            median: Some(median),
+        }
+    }
+
+    /// The ratio of valid pixels to the total number of pixels considered, in the range `[0, 1]`.
+    ///
+    /// Returns `0.0` if no pixels were considered at all.
+    pub fn valid_ratio(&self) -> f32 {
+        if self.total_pixels == 0 {
+            0.0
+        } else {
+            self.valid_pixels as f32 / self.total_pixels as f32
+        }
+    }
+}
+
+/// A histogram of depth values, in meters, with uniformly sized bins.
+///
+/// The histogram covers the closed range `[min_depth, max_depth]`. Valid depths falling outside of
+/// this range are tallied separately as underflow / overflow, and invalid depths are tallied as
+/// invalid rather than being placed into any bin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthHistogram {
+    /// The lower bound of the first bin, in meters.
+    min_depth: f32,
+    /// The upper bound of the last bin, in meters.
+    max_depth: f32,
+    /// The number of depths that fell into each bin.
+    counts: Vec<usize>,
+    /// The number of valid depths that were below `min_depth`.
+    underflow: usize,
+    /// The number of valid depths that were above `max_depth`.
+    overflow: usize,
+    /// The number of invalid depths.
+    invalid: usize,
+}
+
+impl DepthHistogram {
+    /// Construct an empty histogram with `bins` bins spanning `[min_depth, max_depth]` meters.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`HistogramError::NoBins`] if `bins` is zero.
+    ///
+    /// Returns [`HistogramError::InvalidRange`] if either bound is not finite, or if `max_depth`
+    /// is not strictly greater than `min_depth`.
+    pub fn new(min_depth: f32, max_depth: f32, bins: usize) -> Result<Self, HistogramError> {
+        if bins == 0 {
+            return Err(HistogramError::NoBins);
+        }
+
+        if !min_depth.is_finite() || !max_depth.is_finite() || max_depth <= min_depth {
+            return Err(HistogramError::InvalidRange(min_depth, max_depth));
+        }
+
+        Ok(DepthHistogram {
+            min_depth,
+            max_depth,
+            counts: vec![0; bins],
+            underflow: 0,
+            overflow: 0,
+            invalid: 0,
+        })
+    }
+
+    /// Add a single depth value, in meters, to the histogram.
+    pub fn add(&mut self, depth: f32) {
+        if !is_valid_depth(depth) {
+            self.invalid += 1;
+        } else if depth < self.min_depth {
+            self.underflow += 1;
+        } else if depth > self.max_depth {
+            self.overflow += 1;
+        } else {
+            let bins = self.counts.len();
+            let bin = ((depth - self.min_depth) / self.bin_width()) as usize;
+            // The upper bound is inclusive, so it belongs to the last bin.
+            self.counts[bin.min(bins - 1)] += 1;
+        }
+    }
+
+    /// The number of depths that fell into each bin, ordered from nearest to furthest.
+    pub fn counts(&self) -> &[usize] {
+        &self.counts
+    }
+
+    /// The width of each bin, in meters.
+    pub fn bin_width(&self) -> f32 {
+        (self.max_depth - self.min_depth) / self.counts.len() as f32
+    }
+
+    /// The `[start, end)` depth range covered by the bin at `index`, in meters.
+    ///
+    /// Returns `None` if `index` is out of bounds.
+    pub fn bin_range(&self, index: usize) -> Option<(f32, f32)> {
+        if index >= self.counts.len() {
+            return None;
+        }
+
+        let start = self.min_depth + index as f32 * self.bin_width();
+        Some((start, start + self.bin_width()))
+    }
+
+    /// The lower bound of the histogram, in meters.
+    pub fn min_depth(&self) -> f32 {
+        self.min_depth
+    }
+
+    /// The upper bound of the histogram, in meters.
+    pub fn max_depth(&self) -> f32 {
+        self.max_depth
+    }
+
+    /// The number of valid depths that were closer than [`min_depth`](DepthHistogram::min_depth).
+    pub fn underflow(&self) -> usize {
+        self.underflow
+    }
+
+    /// The number of valid depths that were further than [`max_depth`](DepthHistogram::max_depth).
+    pub fn overflow(&self) -> usize {
+        self.overflow
+    }
+
+    /// The number of invalid depths that were added to the histogram.
+    pub fn invalid(&self) -> usize {
+        self.invalid
+    }
+}
+
+impl Extend<f32> for DepthHistogram {
+    fn extend<I: IntoIterator<Item = f32>>(&mut self, depths: I) {
+        for depth in depths {
+            self.add(depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statistics_ignore_invalid_depths() {
+        let stats = DepthStatistics::from_depths(vec![0.0, 1.0, 3.0, f32::NAN, 2.0, 4.0]);
+
+        assert_eq!(stats.total_pixels, 6);
+        assert_eq!(stats.valid_pixels, 4);
+        assert_eq!(stats.min, Some(1.0));
+        assert_eq!(stats.max, Some(4.0));
+        assert_eq!(stats.mean, Some(2.5));
+        assert_eq!(stats.median, Some(2.5));
+        assert!((stats.valid_ratio() - 4.0 / 6.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn statistics_without_valid_depths() {
+        let stats = DepthStatistics::from_depths(vec![0.0; 10]);
+
+        assert_eq!(stats.total_pixels, 10);
+        assert_eq!(stats.valid_pixels, 0);
+        assert_eq!(stats.median, None);
+        assert_eq!(stats.valid_ratio(), 0.0);
+        assert_eq!(DepthStatistics::from_depths(None).valid_ratio(), 0.0);
+    }
+
+    #[test]
+    fn histogram_bins_depths() {
+        let mut histogram = DepthHistogram::new(1.0, 3.0, 4).unwrap();
+        histogram.extend(vec![0.0, 0.5, 1.0, 1.2, 1.6, 2.9, 3.0, 3.5]);
+
+        assert_eq!(histogram.counts(), &[2, 1, 0, 2]);
+        assert_eq!(histogram.invalid(), 1);
+        assert_eq!(histogram.underflow(), 1);
+        assert_eq!(histogram.overflow(), 1);
+        assert_eq!(histogram.bin_width(), 0.5);
+        assert_eq!(histogram.bin_range(1), Some((1.5, 2.0)));
+        assert_eq!(histogram.bin_range(4), None);
+    }
+
+    #[test]
+    fn histogram_rejects_bad_configuration() {
+        assert!(matches!(
+            DepthHistogram::new(0.0, 1.0, 0),
+            Err(HistogramError::NoBins)
+        ));
+        assert!(matches!(
+            DepthHistogram::new(1.0, 1.0, 4),
+            Err(HistogramError::InvalidRange(..))
+        ));
+        assert!(matches!(
+            DepthHistogram::new(0.0, f32::INFINITY, 4),
+            Err(HistogramError::InvalidRange(..))
+        ));
+    }
+
+    #[test]
+    fn roi_is_clamped_to_image() {
+        assert_eq!(roi_ranges(None, 4, 3), (0..4, 0..3));
+
+        let roi = Rs2Roi {
+            min_x: 1,
+            min_y: -2,
+            max_x: 10,
+            max_y: 1,
+        };
+        assert_eq!(roi_ranges(Some(&roi), 4, 3), (1..4, 0..2));
+
+        let outside = Rs2Roi {
+            min_x: 8,
+            min_y: 8,
+            max_x: 9,
+            max_y: 9,
+        };
+        let (cols, rows) = roi_ranges(Some(&outside), 4, 3);
+        assert!(cols.is_empty() && rows.is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+//! Types for working with the poses reported by a tracking device.
+//!
+//! A [`Pose`] is the position and orientation of a device at an instant, copied out of a
+//! [`PoseFrame`] so that it can be kept around without holding on to the frame. Poses convert to
+//! rotation matrices, Euler angles and homogeneous transforms, and can be expressed relative to
+//! one another.
+//!
+//! Pose streams run at a different rate than the cameras they are used with (e.g. 200Hz for the
+//! T265), so a [`PoseBuffer`] keeps the recent poses of a stream and interpolates the pose at the
+//! timestamp of any other frame.
+//!
+//! # Coordinate conventions
+//!
+//! The T265 reports poses in its own convention: X points right, Y up and Z backwards (out of the
+//! back of the device). The world frame is the device frame when tracking started, with Y aligned
+//! to gravity. Robotics software following [REP-103](https://www.ros.org/reps/rep-0103.html)
+//! expects X forward, Y left and Z up instead, which [`Pose::to_rep103`] converts to.
+
+use super::{
+    orientation::{conjugate, multiply, normalize, rotate},
+    pose::PoseFrame,
+    prelude::FrameEx,
+};
+use crate::base::Rs2Extrinsics;
+use std::collections::VecDeque;
+
+/// The number of poses kept by default, i.e. one second of a T265 pose stream.
+const DEFAULT_CAPACITY: usize = 200;
+
+/// The rotation from the T265 convention to REP-103, as a column-major matrix.
+///
+/// REP-103 X is T265 -Z, Y is T265 -X and Z is T265 Y.
+const T265_TO_REP103: [f32; 9] = [0.0, -1.0, 0.0, 0.0, 0.0, 1.0, -1.0, 0.0, 0.0];
+
+/// The position and orientation of a device at an instant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    /// The timestamp of the pose, in milliseconds.
+    pub timestamp: f64,
+    /// The position of the device in the world frame, in meters.
+    pub translation: [f32; 3],
+    /// The rotation from the device frame to the world frame, as a unit quaternion ordered as
+    /// Qi, Qj, Qk, Qr.
+    pub rotation: [f32; 4],
+}
+
+impl Pose {
+    /// Construct a pose from a timestamp in milliseconds, a translation in meters, and a rotation
+    /// quaternion ordered as Qi, Qj, Qk, Qr.
+    ///
+    /// The quaternion does not need to be normalized.
+    pub fn new(timestamp: f64, translation: [f32; 3], rotation: [f32; 4]) -> Self {
+        Pose {
+            timestamp,
+            translation,
+            rotation: normalize(rotation),
+        }
+    }
+
+    /// Get the pose as extrinsics from the device frame to the world frame.
+    pub fn to_extrinsics(&self) -> Rs2Extrinsics {
+        Rs2Extrinsics::from_quaternion(self.rotation, self.translation)
+    }
+
+    /// Get the rotation from the device frame to the world frame as a 3x3 matrix.
+    ///
+    /// The matrix is returned as an array of rows, i.e. `matrix[row][col]`.
+    pub fn rotation_matrix(&self) -> [[f32; 3]; 3] {
+        let m = self.to_matrix();
+        [
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ]
+    }
+
+    /// Get the pose as a 4x4 homogeneous transformation matrix from the device frame to the world
+    /// frame.
+    ///
+    /// The matrix is returned as an array of rows, i.e. `matrix[row][col]`.
+    pub fn to_matrix(&self) -> [[f32; 4]; 4] {
+        self.to_extrinsics().to_matrix()
+    }
+
+    /// Get the rotation as roll, pitch and yaw angles in radians.
+    ///
+    /// The rotation is `yaw` about Z, after `pitch` about Y, after `roll` about X (i.e. intrinsic
+    /// Z-Y'-X'' angles, as used by REP-103). Yaw is only a heading if Z is the vertical axis, so
+    /// convert T265 poses with [`Pose::to_rep103`] first. Pitch is in `[-pi/2, pi/2]`.
+    pub fn euler_angles(&self) -> [f32; 3] {
+        let [x, y, z, w] = self.rotation;
+        let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+        let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+        let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+        [roll, pitch, yaw]
+    }
+
+    /// Get the extrinsics from this pose's device frame to the device frame of `reference`.
+    ///
+    /// For example, the relative pose between two consecutive poses is the motion of the device
+    /// between them, expressed in the frame of the earlier pose.
+    pub fn relative_to(&self, reference: &Pose) -> Rs2Extrinsics {
+        self.to_extrinsics()
+            .compose(&reference.to_extrinsics().inverse())
+    }
+
+    /// Interpolate between this pose and `other` at `timestamp`.
+    ///
+    /// The translation is interpolated linearly and the rotation spherically (slerp). Timestamps
+    /// outside of the two poses extrapolate linearly.
+    pub fn interpolate(&self, other: &Pose, timestamp: f64) -> Pose {
+        let span = other.timestamp - self.timestamp;
+        let t = if span == 0.0 {
+            0.0
+        } else {
+            ((timestamp - self.timestamp) / span) as f32
+        };
+
+        let mut translation = self.translation;
+        for (p, q) in translation.iter_mut().zip(other.translation.iter()) {
+            *p += t * (q - *p);
+        }
+
+        Pose {
+            timestamp,
+            translation,
+            rotation: slerp(self.rotation, other.rotation, t),
+        }
+    }
+
+    /// Convert the pose from the T265 convention (X right, Y up, Z backwards) to the REP-103
+    /// convention (X forward, Y left, Z up).
+    ///
+    /// Both the device frame and the world frame are converted, so the converted pose maps from a
+    /// REP-103 body frame (e.g. `base_link`) to a REP-103 world frame (e.g. `odom`).
+    pub fn to_rep103(&self) -> Pose {
+        let conversion = Rs2Extrinsics::new(T265_TO_REP103, [0.0; 3]);
+        let q = conversion.quaternion();
+
+        Pose {
+            timestamp: self.timestamp,
+            translation: conversion.transform_point(self.translation),
+            rotation: normalize(multiply(multiply(q, self.rotation), conjugate(q))),
+        }
+    }
+
+    /// Transform a point (in meters) from the device frame to the world frame.
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        let rotated = rotate(self.rotation, point);
+        [
+            rotated[0] + self.translation[0],
+            rotated[1] + self.translation[1],
+            rotated[2] + self.translation[2],
+        ]
+    }
+}
+
+impl From<&PoseFrame> for Pose {
+    fn from(frame: &PoseFrame) -> Self {
+        Pose::new(frame.timestamp(), frame.translation(), frame.rotation())
+    }
+}
+
+/// Keeps the recent poses of a pose stream, to look up the pose at any timestamp.
+#[derive(Debug, Clone)]
+pub struct PoseBuffer {
+    /// The poses, from oldest to newest.
+    poses: VecDeque<Pose>,
+    /// The largest number of poses kept.
+    capacity: usize,
+}
+
+impl Default for PoseBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl PoseBuffer {
+    /// Construct an empty buffer keeping up to `capacity` poses.
+    pub fn new(capacity: usize) -> Self {
+        PoseBuffer {
+            poses: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(2),
+        }
+    }
+
+    /// Push the pose of a pose frame.
+    pub fn push_frame(&mut self, frame: &PoseFrame) {
+        self.push(frame.into());
+    }
+
+    /// Push a pose.
+    ///
+    /// Poses must be pushed in order of their timestamps; poses older than the newest one are
+    /// ignored. When the buffer is full, the oldest pose is dropped.
+    pub fn push(&mut self, pose: Pose) {
+        if let Some(newest) = self.poses.back() {
+            if pose.timestamp <= newest.timestamp {
+                return;
+            }
+        }
+
+        if self.poses.len() == self.capacity {
+            self.poses.pop_front();
+        }
+        self.poses.push_back(pose);
+    }
+
+    /// Get the number of poses in the buffer.
+    pub fn len(&self) -> usize {
+        self.poses.len()
+    }
+
+    /// Predicate for checking if the buffer holds no poses.
+    pub fn is_empty(&self) -> bool {
+        self.poses.is_empty()
+    }
+
+    /// Get the newest pose, if any.
+    pub fn latest(&self) -> Option<&Pose> {
+        self.poses.back()
+    }
+
+    /// Interpolate the pose at a timestamp (in milliseconds).
+    ///
+    /// Returns `None` if the timestamp is not between the oldest and newest poses in the buffer,
+    /// since extrapolating a pose quickly becomes inaccurate.
+    pub fn pose_at(&self, timestamp: f64) -> Option<Pose> {
+        let oldest = self.poses.front()?;
+        let newest = self.poses.back()?;
+        if timestamp < oldest.timestamp || timestamp > newest.timestamp {
+            return None;
+        }
+
+        // The index of the first pose after the timestamp.
+        let after = self
+            .poses
+            .partition_point(|pose| pose.timestamp <= timestamp);
+        if after == 0 {
+            return Some(Pose {
+                timestamp,
+                ..*oldest
+            });
+        }
+        if after == self.poses.len() {
+            return Some(Pose {
+                timestamp,
+                ..*newest
+            });
+        }
+
+        Some(self.poses[after - 1].interpolate(&self.poses[after], timestamp))
+    }
+}
+
+/// Spherically interpolate between two unit quaternions, along the shortest path.
+fn slerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut cosine: f32 = a.iter().zip(b.iter()).map(|(a, b)| a * b).sum();
+    let b = if cosine < 0.0 {
+        cosine = -cosine;
+        b.map(|c| -c)
+    } else {
+        b
+    };
+
+    // Close quaternions are interpolated linearly, as the angle is too small to divide by.
+    let (wa, wb) = if cosine > 0.9995 {
+        (1.0 - t, t)
+    } else {
+        let angle = cosine.acos();
+        let sine = angle.sin();
+        (((1.0 - t) * angle).sin() / sine, (t * angle).sin() / sine)
+    };
+
+    normalize([
+        wa * a[0] + wb * b[0],
+        wa * a[1] + wb * b[1],
+        wa * a[2] + wb * b[2],
+        wa * a[3] + wb * b[3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4};
+
+    /// A quarter turn about an axis.
+    fn quarter_turn(axis: [f32; 3]) -> [f32; 4] {
+        let s = FRAC_1_SQRT_2;
+        [axis[0] * s, axis[1] * s, axis[2] * s, s]
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn converts_to_matrices_and_angles() {
+        let pose = Pose::new(0.0, [1.0, 2.0, 3.0], quarter_turn([0.0, 0.0, 1.0]));
+        let rotation = pose.rotation_matrix();
+        assert_close(&rotation[0], &[0.0, -1.0, 0.0]);
+        assert_close(&rotation[1], &[1.0, 0.0, 0.0]);
+        assert_close(&pose.to_matrix()[1], &[1.0, 0.0, 0.0, 2.0]);
+        assert_close(&pose.euler_angles(), &[0.0, 0.0, FRAC_PI_2]);
+
+        let eighth = FRAC_PI_4 / 2.0;
+        let pitched = Pose::new(0.0, [0.0; 3], [0.0, eighth.sin(), 0.0, eighth.cos()]);
+        assert_close(&pitched.euler_angles(), &[0.0, FRAC_PI_4, 0.0]);
+
+        assert_close(&pose.transform_point([1.0, 0.0, 0.0]), &[1.0, 3.0, 3.0]);
+        assert_close(
+            &pose.to_extrinsics().transform_point([1.0, 0.0, 0.0]),
+            &[1.0, 3.0, 3.0],
+        );
+    }
+
+    #[test]
+    fn computes_relative_pose() {
+        let start = Pose::new(0.0, [1.0, 0.0, 0.0], quarter_turn([0.0, 0.0, 1.0]));
+        let end = Pose::new(10.0, [1.0, 1.0, 0.0], quarter_turn([0.0, 0.0, 1.0]));
+
+        // Moving along world Y is moving along the device's X, as it is turned a quarter.
+        let motion = end.relative_to(&start);
+        assert_close(&motion.translation(), &[1.0, 0.0, 0.0]);
+        assert_close(&motion.quaternion(), &[0.0, 0.0, 0.0, 1.0]);
+
+        let point = [0.3, -0.2, 0.5];
+        assert_close(
+            &start.transform_point(motion.transform_point(point)),
+            &end.transform_point(point),
+        );
+    }
+
+    #[test]
+    fn interpolates_buffered_poses() {
+        let mut buffer = PoseBuffer::new(3);
+        assert!(buffer.pose_at(0.0).is_none());
+
+        buffer.push(Pose::new(0.0, [9.0; 3], [0.0, 0.0, 0.0, 1.0]));
+        buffer.push(Pose::new(10.0, [0.0; 3], [0.0, 0.0, 0.0, 1.0]));
+        buffer.push(Pose::new(
+            20.0,
+            [2.0, 0.0, 0.0],
+            quarter_turn([0.0, 1.0, 0.0]),
+        ));
+        buffer.push(Pose::new(15.0, [5.0; 3], [0.0, 0.0, 0.0, 1.0]));
+        buffer.push(Pose::new(
+            30.0,
+            [2.0, 0.0, 0.0],
+            quarter_turn([0.0, 1.0, 0.0]),
+        ));
+        assert_eq!(buffer.len(), 3);
+
+        let pose = buffer.pose_at(15.0).unwrap();
+        assert_eq!(pose.timestamp, 15.0);
+        assert_close(&pose.translation, &[1.0, 0.0, 0.0]);
+        let eighth = FRAC_PI_4 / 2.0;
+        assert_close(&pose.rotation, &[0.0, eighth.sin(), 0.0, eighth.cos()]);
+
+        assert_eq!(buffer.pose_at(30.0).unwrap().translation, [2.0, 0.0, 0.0]);
+        assert!(buffer.pose_at(5.0).is_none());
+        assert!(buffer.pose_at(31.0).is_none());
+    }
+
+    #[test]
+    fn converts_to_rep103() {
+        // Moving forward (T265 -Z) and turning left (about T265 Y).
+        let pose = Pose::new(0.0, [0.0, 0.5, -1.0], quarter_turn([0.0, 1.0, 0.0]));
+        let converted = pose.to_rep103();
+
+        assert_close(&converted.translation, &[1.0, 0.0, 0.5]);
+        assert_close(&converted.euler_angles(), &[0.0, 0.0, FRAC_PI_2]);
+
+        // The device's forward axis now points left in the world.
+        assert_close(
+            &(converted.transform_point([1.0, 0.0, 0.0])),
+            &[1.0, 1.0, 0.5],
+        );
+    }
+}
@@ -0,0 +1,756 @@
+//! Types for fusing depth frames into a volume, and extracting a mesh from it.
+//!
+//! A [`TsdfVolume`] is a dense voxel grid holding a truncated signed distance function (TSDF): for
+//! each voxel, the distance to the nearest observed surface along the camera ray, clamped to a
+//! truncation distance. Every integrated depth frame updates a running weighted average of the
+//! distance in each voxel it sees, so noise averages out as more frames are integrated.
+//!
+//! Once the object has been scanned, [`TsdfVolume::extract_mesh`] finds the zero crossing of the
+//! distance function with marching cubes, producing a [`TriangleMesh`] that can be written to PLY.
+//!
+//! Each frame needs the pose of the depth camera in the world frame of the volume. This can come
+//! from a T265 through [`TsdfVolume::integrate_with_pose`], or from any other tracking source
+//! through [`TsdfVolume::integrate`].
+
+use super::{
+    export::{write_mesh_ply, ExportError, PlyFormat},
+    image::{ColorFrame, DepthFrame},
+    pose::PoseFrame,
+    prelude::FrameEx,
+    statistics::is_valid_depth,
+};
+use crate::{
+    base::{Rs2Extrinsics, Rs2Intrinsics},
+    kind::Rs2Format,
+};
+use anyhow::Result;
+use std::{collections::HashMap, io::Write, sync::OnceLock};
+use thiserror::Error;
+
+/// The largest weight a voxel can accumulate, so that the volume can still adapt to changes.
+const DEFAULT_MAX_WEIGHT: f32 = 64.0;
+
+/// Occurs when a volume cannot be constructed, or a frame cannot be integrated into it.
+#[derive(Error, Debug)]
+pub enum TsdfError {
+    /// The voxel size is not positive and finite.
+    #[error("Voxel size must be positive and finite, got {0}.")]
+    InvalidVoxelSize(f32),
+    /// The truncation distance is not positive and finite.
+    #[error("Truncation distance must be positive and finite, got {0}.")]
+    InvalidTruncation(f32),
+    /// The volume has no voxels along an axis, or too many voxels to be addressed.
+    #[error("Invalid volume dimensions: {0}x{1}x{2}")]
+    InvalidDimensions(usize, usize, usize),
+    /// The color frame is in a format that cannot be integrated.
+    #[error("Color frame format cannot be integrated: {0:?}")]
+    UnsupportedColorFormat(Rs2Format),
+    /// The color frame is not aligned to the depth frame.
+    #[error("Color frame resolution {0}x{1} does not match depth frame resolution {2}x{3}")]
+    MismatchedColor(usize, usize, usize, usize),
+}
+
+/// A dense voxel grid of truncated signed distances.
+#[derive(Debug, Clone)]
+pub struct TsdfVolume {
+    /// The position of the corner of the first voxel in the world frame, in meters.
+    origin: [f32; 3],
+    /// The number of voxels along each axis.
+    dimensions: [usize; 3],
+    /// The length of the edge of a voxel, in meters.
+    voxel_size: f32,
+    /// The distance beyond which signed distances are truncated, in meters.
+    truncation: f32,
+    /// The largest weight a voxel can accumulate.
+    max_weight: f32,
+    /// The signed distance of each voxel, as a fraction of the truncation distance.
+    ///
+    /// Voxels are ordered with X varying fastest, then Y, then Z.
+    distances: Vec<f32>,
+    /// The accumulated weight of each voxel. Voxels that were never observed have zero weight.
+    weights: Vec<f32>,
+    /// The average color of each voxel, if any colored frame was integrated.
+    colors: Option<Vec<[f32; 3]>>,
+}
+
+impl TsdfVolume {
+    /// Construct an empty volume.
+    ///
+    /// The volume is a box of `dimensions` voxels along each axis, each `voxel_size` meters wide,
+    /// with its minimum corner at `origin` in the world frame. Signed distances are truncated at
+    /// `truncation` meters, which should be a few voxels wide.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TsdfError::InvalidVoxelSize`] if `voxel_size` is not positive and finite.
+    ///
+    /// Returns [`TsdfError::InvalidTruncation`] if `truncation` is not positive and finite.
+    ///
+    /// Returns [`TsdfError::InvalidDimensions`] if any of the `dimensions` is zero, or the total
+    /// number of voxels overflows.
+    pub fn new(
+        origin: [f32; 3],
+        dimensions: [usize; 3],
+        voxel_size: f32,
+        truncation: f32,
+    ) -> Result<Self, TsdfError> {
+        if !(voxel_size > 0.0 && voxel_size.is_finite()) {
+            return Err(TsdfError::InvalidVoxelSize(voxel_size));
+        }
+        if !(truncation > 0.0 && truncation.is_finite()) {
+            return Err(TsdfError::InvalidTruncation(truncation));
+        }
+
+        let [nx, ny, nz] = dimensions;
+        let count = nx
+            .checked_mul(ny)
+            .and_then(|count| count.checked_mul(nz))
+            .filter(|&count| count > 0)
+            .ok_or(TsdfError::InvalidDimensions(nx, ny, nz))?;
+
+        Ok(TsdfVolume {
+            origin,
+            dimensions,
+            voxel_size,
+            truncation,
+            max_weight: DEFAULT_MAX_WEIGHT,
+            distances: vec![1.0; count],
+            weights: vec![0.0; count],
+            colors: None,
+        })
+    }
+
+    /// Set the largest weight a voxel can accumulate. Defaults to 64.
+    ///
+    /// Lower weights let the volume adapt faster to a changing scene or a drifting pose, at the
+    /// cost of averaging out less noise.
+    pub fn set_max_weight(&mut self, max_weight: f32) {
+        self.max_weight = max_weight;
+    }
+
+    /// Get the position of the minimum corner of the volume in the world frame, in meters.
+    pub fn origin(&self) -> [f32; 3] {
+        self.origin
+    }
+
+    /// Get the number of voxels along each axis.
+    pub fn dimensions(&self) -> [usize; 3] {
+        self.dimensions
+    }
+
+    /// Get the length of the edge of a voxel, in meters.
+    pub fn voxel_size(&self) -> f32 {
+        self.voxel_size
+    }
+
+    /// Get the distance beyond which signed distances are truncated, in meters.
+    pub fn truncation(&self) -> f32 {
+        self.truncation
+    }
+
+    /// Get the signed distance (in meters) at a voxel, or `None` if the voxel is out of bounds or
+    /// has not been observed.
+    ///
+    /// Distances are positive in front of the surface and negative behind it.
+    pub fn distance(&self, x: usize, y: usize, z: usize) -> Option<f32> {
+        let [nx, ny, nz] = self.dimensions;
+        if x >= nx || y >= ny || z >= nz {
+            return None;
+        }
+
+        let index = self.index(x, y, z);
+        if self.weights[index] > 0.0 {
+            Some(self.distances[index] * self.truncation)
+        } else {
+            None
+        }
+    }
+
+    /// Integrate a depth frame, seen from a camera with a given pose.
+    ///
+    /// `camera_to_world` are the extrinsics from the depth camera to the world frame of the
+    /// volume. If `color` is given, it must be aligned to the depth frame (i.e. have the same
+    /// resolution and geometry), and the volume keeps the average color of each voxel.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TsdfError::MismatchedColor`] if the color frame does not have the same resolution
+    /// as the depth frame.
+    ///
+    /// Returns [`TsdfError::UnsupportedColorFormat`] if the color frame is not in an 8-bit RGB or
+    /// BGR format.
+    ///
+    /// Returns an error if the depth frame cannot be read; see
+    /// [`DepthFrame::to_points`](crate::frame::DepthFrame::to_points).
+    pub fn integrate(
+        &mut self,
+        depth: &DepthFrame,
+        camera_to_world: &Rs2Extrinsics,
+        color: Option<&ColorFrame>,
+    ) -> Result<()> {
+        let intrinsics = depth.stream_profile().intrinsics()?;
+        let depth_at = depth.depth_reader()?;
+
+        match color {
+            Some(color) => {
+                if color.width() != depth.width() || color.height() != depth.height() {
+                    return Err(TsdfError::MismatchedColor(
+                        color.width(),
+                        color.height(),
+                        depth.width(),
+                        depth.height(),
+                    )
+                    .into());
+                }
+
+                let rgb_at = color.rgb_reader().ok_or_else(|| {
+                    TsdfError::UnsupportedColorFormat(color.stream_profile().format())
+                })?;
+                self.integrate_depth(&intrinsics, camera_to_world, depth_at, Some(rgb_at));
+            }
+            None => self.integrate_depth(
+                &intrinsics,
+                camera_to_world,
+                depth_at,
+                None::<fn(usize, usize) -> [u8; 3]>,
+            ),
+        }
+        Ok(())
+    }
+
+    /// Integrate a depth frame, posed by a T265 pose frame.
+    ///
+    /// The world frame of the volume is the world frame of the pose sensor, i.e. its coordinate
+    /// frame when tracking started. `depth_to_pose` are the extrinsics from the depth camera to
+    /// the pose sensor, which depend on how the two devices are mounted together.
+    ///
+    /// # Errors
+    ///
+    /// See [`TsdfVolume::integrate`].
+    pub fn integrate_with_pose(
+        &mut self,
+        depth: &DepthFrame,
+        pose: &PoseFrame,
+        depth_to_pose: &Rs2Extrinsics,
+        color: Option<&ColorFrame>,
+    ) -> Result<()> {
+        let camera_to_world = depth_to_pose.compose(&pose.to_extrinsics());
+        self.integrate(depth, &camera_to_world, color)
+    }
+
+    /// Integrate a depth image into the volume.
+    ///
+    /// `depth_at` returns the depth (in meters) of the pixel at a given column and row of an image
+    /// described by `intrinsics`, and `color_at` its color.
+    pub(crate) fn integrate_depth<D, C>(
+        &mut self,
+        intrinsics: &Rs2Intrinsics,
+        camera_to_world: &Rs2Extrinsics,
+        depth_at: D,
+        color_at: Option<C>,
+    ) where
+        D: Fn(usize, usize) -> f32,
+        C: Fn(usize, usize) -> [u8; 3],
+    {
+        let world_to_camera = camera_to_world.inverse();
+        let (width, height) = (intrinsics.width() as f32, intrinsics.height() as f32);
+        if color_at.is_some() && self.colors.is_none() {
+            self.colors = Some(vec![[0.0; 3]; self.distances.len()]);
+        }
+
+        let [nx, ny, nz] = self.dimensions;
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let point = world_to_camera.transform_point(self.voxel_center(x, y, z));
+                    if point[2] <= 0.0 {
+                        continue;
+                    }
+
+                    let [u, v] = intrinsics.project(point);
+                    let (col, row) = (u.round(), v.round());
+                    if !(col >= 0.0 && row >= 0.0 && col < width && row < height) {
+                        continue;
+                    }
+                    let (col, row) = (col as usize, row as usize);
+
+                    let depth = depth_at(col, row);
+                    if !is_valid_depth(depth) {
+                        continue;
+                    }
+
+                    // Voxels far behind the observed surface may be occluded, so leave them be.
+                    let distance = depth - point[2];
+                    if distance < -self.truncation {
+                        continue;
+                    }
+
+                    let index = self.index(x, y, z);
+                    let weight = self.weights[index];
+                    let total = weight + 1.0;
+                    let distance = (distance / self.truncation).min(1.0);
+                    self.distances[index] = (self.distances[index] * weight + distance) / total;
+
+                    if let (Some(color_at), Some(colors)) = (&color_at, &mut self.colors) {
+                        let color = color_at(col, row);
+                        for channel in 0..3 {
+                            colors[index][channel] =
+                                (colors[index][channel] * weight + color[channel] as f32) / total;
+                        }
+                    }
+
+                    self.weights[index] = total.min(self.max_weight);
+                }
+            }
+        }
+    }
+
+    /// Extract the surface of the volume (the zero crossing of the signed distances) as a
+    /// triangle mesh, with marching cubes.
+    ///
+    /// Only cubes whose eight corners have all been observed produce triangles. Triangles are
+    /// wound counter-clockwise when seen from in front of the surface, and vertices are colored
+    /// if any colored frame was integrated.
+    pub fn extract_mesh(&self) -> TriangleMesh {
+        let cases = case_triangles();
+        let [nx, ny, nz] = self.dimensions;
+
+        let mut mesh = TriangleMesh {
+            vertices: Vec::new(),
+            colors: self.colors.as_ref().map(|_| Vec::new()),
+            triangles: Vec::new(),
+        };
+        // Index of the vertex on each edge of the grid, keyed by the edge's first voxel and axis.
+        let mut edge_vertices: HashMap<(usize, usize), u32> = HashMap::new();
+
+        for z in 0..nz.saturating_sub(1) {
+            for y in 0..ny.saturating_sub(1) {
+                for x in 0..nx.saturating_sub(1) {
+                    let corners = CORNERS.map(|[dx, dy, dz]| self.index(x + dx, y + dy, z + dz));
+                    if corners.iter().any(|&i| self.weights[i] <= 0.0) {
+                        continue;
+                    }
+
+                    let case = corners
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &i)| self.distances[i] < 0.0)
+                        .fold(0, |case, (corner, _)| case | 1 << corner);
+
+                    for triangle in cases[case].iter() {
+                        let vertices = triangle.map(|edge| {
+                            let [a, b] = EDGES[edge];
+                            *edge_vertices
+                                .entry((corners[a].min(corners[b]), edge_axis(edge)))
+                                .or_insert_with(|| {
+                                    self.add_vertex(&mut mesh, corners[a], corners[b])
+                                })
+                        });
+                        mesh.triangles
+                            .push(self.orient(&mesh, vertices, corners[0]));
+                    }
+                }
+            }
+        }
+
+        mesh
+    }
+
+    /// Add the vertex where the surface crosses the edge between two voxels to a mesh.
+    fn add_vertex(&self, mesh: &mut TriangleMesh, a: usize, b: usize) -> u32 {
+        let (da, db) = (self.distances[a], self.distances[b]);
+        let t = if da == db { 0.5 } else { da / (da - db) };
+
+        let (pa, pb) = (self.position(a), self.position(b));
+        mesh.vertices.push([
+            pa[0] + t * (pb[0] - pa[0]),
+            pa[1] + t * (pb[1] - pa[1]),
+            pa[2] + t * (pb[2] - pa[2]),
+        ]);
+
+        if let (Some(colors), Some(mesh_colors)) = (&self.colors, &mut mesh.colors) {
+            let mut color = [0; 3];
+            for (channel, value) in color.iter_mut().enumerate() {
+                let blended = colors[a][channel] + t * (colors[b][channel] - colors[a][channel]);
+                *value = blended.round() as u8;
+            }
+            mesh_colors.push(color);
+        }
+
+        (mesh.vertices.len() - 1) as u32
+    }
+
+    /// Wind a triangle so that its normal points along the gradient of the signed distances,
+    /// i.e. out of the surface.
+    fn orient(&self, mesh: &TriangleMesh, triangle: [u32; 3], corner: usize) -> [u32; 3] {
+        let [a, b, c] = triangle.map(|i| mesh.vertices[i as usize]);
+        let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let normal = [
+            ab[1] * ac[2] - ab[2] * ac[1],
+            ab[2] * ac[0] - ab[0] * ac[2],
+            ab[0] * ac[1] - ab[1] * ac[0],
+        ];
+
+        let gradient = self.gradient(corner);
+        let alignment: f32 = (0..3).map(|axis| normal[axis] * gradient[axis]).sum();
+        if alignment < 0.0 {
+            [triangle[0], triangle[2], triangle[1]]
+        } else {
+            triangle
+        }
+    }
+
+    /// The gradient of the signed distances over the cube whose first voxel is `index`.
+    fn gradient(&self, index: usize) -> [f32; 3] {
+        let mut gradient = [0.0; 3];
+        for [dx, dy, dz] in CORNERS.iter() {
+            let offset =
+                dx + dy * self.dimensions[0] + dz * self.dimensions[0] * self.dimensions[1];
+            let distance = self.distances[index + offset];
+            gradient[0] += if *dx == 1 { distance } else { -distance };
+            gradient[1] += if *dy == 1 { distance } else { -distance };
+            gradient[2] += if *dz == 1 { distance } else { -distance };
+        }
+        gradient
+    }
+
+    /// The index of a voxel in the grid.
+    #[inline]
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + self.dimensions[0] * (y + self.dimensions[1] * z)
+    }
+
+    /// The position of the center of a voxel in the world frame, given its index in the grid.
+    fn position(&self, index: usize) -> [f32; 3] {
+        let [nx, ny, _] = self.dimensions;
+        self.voxel_center(index % nx, (index / nx) % ny, index / (nx * ny))
+    }
+
+    /// The position of the center of a voxel in the world frame.
+    #[inline]
+    fn voxel_center(&self, x: usize, y: usize, z: usize) -> [f32; 3] {
+        [
+            self.origin[0] + (x as f32 + 0.5) * self.voxel_size,
+            self.origin[1] + (y as f32 + 0.5) * self.voxel_size,
+            self.origin[2] + (z as f32 + 0.5) * self.voxel_size,
+        ]
+    }
+}
+
+/// A triangle mesh, with optional per-vertex colors.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TriangleMesh {
+    /// The position of each vertex, in meters.
+    pub(crate) vertices: Vec<[f32; 3]>,
+    /// The color of each vertex, if the mesh is colored.
+    pub(crate) colors: Option<Vec<[u8; 3]>>,
+    /// The indices of the vertices of each triangle.
+    pub(crate) triangles: Vec<[u32; 3]>,
+}
+
+impl TriangleMesh {
+    /// Get the position of each vertex, in meters.
+    pub fn vertices(&self) -> &[[f32; 3]] {
+        &self.vertices
+    }
+
+    /// Get the color of each vertex, if the mesh is colored.
+    pub fn colors(&self) -> Option<&[[u8; 3]]> {
+        self.colors.as_deref()
+    }
+
+    /// Get the indices of the vertices of each triangle.
+    pub fn triangles(&self) -> &[[u32; 3]] {
+        &self.triangles
+    }
+
+    /// Write the mesh as a PLY file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError::CouldNotWrite`] if writing to `writer` fails.
+    pub fn write_ply<W: Write>(&self, writer: W, format: PlyFormat) -> Result<(), ExportError> {
+        write_mesh_ply(
+            writer,
+            &self.vertices,
+            self.colors.as_deref(),
+            &self.triangles,
+            format,
+        )
+    }
+}
+
+/// The offset of each corner of a cube from its first corner.
+///
+/// Corner `i` is offset by bit 0 of `i` along X, bit 1 along Y and bit 2 along Z.
+const CORNERS: [[usize; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+/// The corners at either end of each edge of a cube, from the lower to the higher corner.
+const EDGES: [[usize; 2]; 12] = [
+    [0, 1],
+    [2, 3],
+    [4, 5],
+    [6, 7],
+    [0, 2],
+    [1, 3],
+    [4, 6],
+    [5, 7],
+    [0, 4],
+    [1, 5],
+    [2, 6],
+    [3, 7],
+];
+
+/// The axis an edge of a cube runs along.
+#[inline]
+fn edge_axis(edge: usize) -> usize {
+    edge / 4
+}
+
+/// The edge between two corners of a cube.
+fn edge_between(a: usize, b: usize) -> usize {
+    let (low, high) = (a.min(b), a.max(b));
+    EDGES
+        .iter()
+        .position(|&edge| edge == [low, high])
+        .expect("Corners share an edge.")
+}
+
+/// The triangles for each of the 256 marching cubes cases, as triples of edges.
+///
+/// Bit `i` of a case is set if corner `i` is behind the surface. Rather than tabulating the
+/// triangles by hand, each case is triangulated by walking the faces of the cube: on each face,
+/// the surface crosses the edges whose corners are on opposite sides, and the crossings are joined
+/// into segments that cut off the corners in front of the surface. Segments from all faces join up
+/// into closed polygons, which are then split into triangles. Triangles are not consistently
+/// wound, see [`TsdfVolume::orient`].
+fn case_triangles() -> &'static [Vec<[usize; 3]>] {
+    static CASES: OnceLock<Vec<Vec<[usize; 3]>>> = OnceLock::new();
+    CASES.get_or_init(|| (0..256).map(triangulate_case).collect())
+}
+
+/// The corners of each face of a cube, in order around the face.
+///
+/// Every face is ordered counter-clockwise when seen from outside the cube, so that the faces on
+/// either side of an edge walk it in opposite directions.
+const FACES: [[usize; 4]; 6] = [
+    [0, 4, 6, 2],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 6, 7, 3],
+    [0, 2, 3, 1],
+    [4, 5, 7, 6],
+];
+
+fn triangulate_case(case: usize) -> Vec<[usize; 3]> {
+    let behind = |corner: usize| case & (1 << corner) != 0;
+
+    // Directed segments between edges, from the crossing leaving the corners behind the surface
+    // to the next crossing returning to them.
+    let mut next: HashMap<usize, usize> = HashMap::new();
+    for face in FACES.iter() {
+        let crossings: Vec<(usize, bool)> = (0..4)
+            .filter_map(|i| {
+                let (a, b) = (face[i], face[(i + 1) % 4]);
+                if behind(a) != behind(b) {
+                    Some((edge_between(a, b), behind(a)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for (i, &(edge, leaving)) in crossings.iter().enumerate() {
+            if leaving {
+                next.insert(edge, crossings[(i + 1) % crossings.len()].0);
+            }
+        }
+    }
+
+    // Follow the segments around each polygon, and split it into a fan of triangles.
+    let mut triangles = Vec::new();
+    while let Some(&start) = next.keys().min() {
+        let mut polygon = vec![start];
+        let mut edge = next.remove(&start).expect("Edge has a segment.");
+        while edge != start {
+            polygon.push(edge);
+            edge = next.remove(&edge).expect("Polygon is closed.");
+        }
+
+        for i in 1..polygon.len() - 1 {
+            triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+        }
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{base::Rs2Distortion, kind::Rs2DistortionModel};
+
+    fn camera() -> Rs2Intrinsics {
+        Rs2Intrinsics::new(
+            64,
+            48,
+            [31.5, 23.5],
+            [50.0, 50.0],
+            Rs2Distortion {
+                model: Rs2DistortionModel::None,
+                coeffs: [0.0; 5],
+            },
+        )
+    }
+
+    /// A volume holding the exact signed distance to a sphere.
+    fn sphere(radius: f32) -> TsdfVolume {
+        let mut volume = TsdfVolume::new([-0.5; 3], [20, 20, 20], 0.05, 0.15).unwrap();
+        for z in 0..20 {
+            for y in 0..20 {
+                for x in 0..20 {
+                    let p = volume.voxel_center(x, y, z);
+                    let distance = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt() - radius;
+                    let index = volume.index(x, y, z);
+                    volume.distances[index] = (distance / volume.truncation).clamp(-1.0, 1.0);
+                    volume.weights[index] = 1.0;
+                }
+            }
+        }
+        volume
+    }
+
+    #[test]
+    fn every_case_forms_closed_polygons() {
+        let cases = case_triangles();
+        assert!(cases[0].is_empty());
+        assert!(cases[255].is_empty());
+        assert_eq!(cases[1].len(), 1);
+        // Two opposite corners behind the surface are cut off separately.
+        assert_eq!(cases[1 | 1 << 7].len(), 2);
+
+        for (case, triangles) in cases.iter().enumerate() {
+            // Every edge crossed by the surface is used, and no others.
+            for (edge, [a, b]) in EDGES.iter().enumerate() {
+                let crossed = (case >> a & 1) != (case >> b & 1);
+                let used = triangles.iter().any(|t| t.contains(&edge));
+                assert_eq!(crossed, used, "case {} edge {}", case, edge);
+            }
+        }
+    }
+
+    #[test]
+    fn extracts_closed_sphere() {
+        let mesh = sphere(0.3).extract_mesh();
+        assert!(mesh.triangles().len() > 100);
+        assert!(mesh.colors().is_none());
+
+        for vertex in mesh.vertices() {
+            let radius = vertex.iter().map(|c| c * c).sum::<f32>().sqrt();
+            assert!((radius - 0.3).abs() < 0.01);
+        }
+
+        // A closed surface uses every edge in exactly two triangles, once in each direction.
+        let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+        for [a, b, c] in mesh.triangles().iter().copied() {
+            for edge in [(a, b), (b, c), (c, a)].iter() {
+                *edges.entry(*edge).or_insert(0) += 1;
+            }
+        }
+        for (&(a, b), &count) in edges.iter() {
+            assert_eq!(count, 1);
+            assert_eq!(edges.get(&(b, a)), Some(&1));
+        }
+
+        // Triangles face outwards.
+        let [a, b, c] = mesh.triangles()[0].map(|i| mesh.vertices()[i as usize]);
+        let normal = [
+            (b[1] - a[1]) * (c[2] - a[2]) - (b[2] - a[2]) * (c[1] - a[1]),
+            (b[2] - a[2]) * (c[0] - a[0]) - (b[0] - a[0]) * (c[2] - a[2]),
+            (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]),
+        ];
+        assert!(normal[0] * a[0] + normal[1] * a[1] + normal[2] * a[2] > 0.0);
+    }
+
+    #[test]
+    fn rejects_invalid_volumes() {
+        assert!(matches!(
+            TsdfVolume::new([0.0; 3], [4, 4, 4], 0.0, 0.1),
+            Err(TsdfError::InvalidVoxelSize(_))
+        ));
+        assert!(matches!(
+            TsdfVolume::new([0.0; 3], [4, 4, 4], f32::NAN, 0.1),
+            Err(TsdfError::InvalidVoxelSize(_))
+        ));
+        assert!(matches!(
+            TsdfVolume::new([0.0; 3], [4, 4, 4], 0.01, 0.0),
+            Err(TsdfError::InvalidTruncation(_))
+        ));
+        assert!(matches!(
+            TsdfVolume::new([0.0; 3], [4, 0, 4], 0.01, 0.03),
+            Err(TsdfError::InvalidDimensions(4, 0, 4))
+        ));
+        assert!(matches!(
+            TsdfVolume::new([0.0; 3], [usize::MAX, 2, 1], 0.01, 0.03),
+            Err(TsdfError::InvalidDimensions(..))
+        ));
+    }
+
+    #[test]
+    fn integrates_plane() {
+        // A wall one meter in front of the camera, seen twice.
+        let mut volume = TsdfVolume::new([-0.2, -0.2, 0.8], [20, 20, 20], 0.02, 0.06).unwrap();
+        for _ in 0..2 {
+            volume.integrate_depth(
+                &camera(),
+                &Rs2Extrinsics::identity(),
+                |_, _| 1.0,
+                Some(|_, _| [200, 100, 0]),
+            );
+        }
+
+        // Voxels in front of the wall are empty space, and those far behind it are unobserved.
+        assert_eq!(volume.distance(10, 10, 0), Some(0.06));
+        assert!(volume.distance(10, 10, 19).is_none());
+        let near = volume.distance(10, 10, 10).unwrap();
+        assert!((near - (1.0 - 1.01)).abs() < 1e-5);
+
+        let mesh = volume.extract_mesh();
+        assert!(!mesh.triangles().is_empty());
+        for vertex in mesh.vertices() {
+            assert!((vertex[2] - 1.0).abs() < 1e-4);
+        }
+        assert_eq!(mesh.colors().unwrap()[0], [200, 100, 0]);
+    }
+
+    #[test]
+    fn integrates_with_camera_pose() {
+        // The camera is moved back by half a meter, so the wall is at Z = 1.5 in the world frame.
+        let camera_to_world = Rs2Extrinsics::new(
+            [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.5],
+        );
+        let mut volume = TsdfVolume::new([-0.2, -0.2, 1.3], [20, 20, 20], 0.02, 0.06).unwrap();
+        volume.integrate_depth(
+            &camera(),
+            &camera_to_world,
+            |_, _| 1.0,
+            None::<fn(usize, usize) -> [u8; 3]>,
+        );
+
+        let mesh = volume.extract_mesh();
+        assert!(mesh.colors().is_none());
+        for vertex in mesh.vertices() {
+            assert!((vertex[2] - 1.5).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,496 @@
+//! Types for undistorting and rectifying images without any external image processing library.
+//!
+//! An [`UndistortionMap`] is a lookup table from every pixel of a target pinhole (undistorted)
+//! image to the coordinate in a distorted source image that it samples. Computing the map involves
+//! projecting through the source camera's distortion model for every pixel, so the map should be
+//! built once per stream configuration and reused for every frame.
+//!
+//! Maps can additionally rotate the target camera relative to the source camera, which is how a
+//! [`StereoRectification`] aligns the image rows of a stereo pair (such as the two fisheye cameras
+//! on the T265).
+//!
+//! See [`ColorFrame::undistort`](crate::frame::ColorFrame::undistort),
+//! [`InfraredFrame::undistort`](crate::frame::InfraredFrame::undistort) and
+//! [`FisheyeFrame::undistort`](crate::frame::FisheyeFrame::undistort) for how to remap a frame.
+
+use super::registration::RegisteredImage;
+use crate::{
+    base::{Rs2Extrinsics, Rs2Intrinsics},
+    kind::Rs2Format,
+};
+use thiserror::Error;
+
+/// Occurs when a frame cannot be remapped through an undistortion map.
+#[derive(Error, Debug)]
+pub enum UndistortError {
+    /// The frame is in a pixel format that cannot be remapped.
+    #[error("Frame format cannot be undistorted: {0:?}")]
+    UnsupportedFormat(Rs2Format),
+    /// The frame and the source image of the map have different resolutions.
+    #[error("Frame resolution {0}x{1} does not match undistortion map source resolution {2}x{3}")]
+    MismatchedResolution(usize, usize, usize, usize),
+}
+
+/// Pixel types that can be bilinearly interpolated when remapping an image.
+pub trait Interpolate: Copy + Default {
+    /// Blend four pixels together, with weights that sum to one.
+    fn interpolate(pixels: [Self; 4], weights: [f32; 4]) -> Self;
+}
+
+/// Blend four scalar values together.
+#[inline]
+fn blend(values: [f32; 4], weights: [f32; 4]) -> f32 {
+    values
+        .iter()
+        .zip(weights.iter())
+        .map(|(value, weight)| value * weight)
+        .sum()
+}
+
+macro_rules! impl_interpolate_for_integer {
+    ($t:ty) => {
+        impl Interpolate for $t {
+            fn interpolate(pixels: [Self; 4], weights: [f32; 4]) -> Self {
+                let values = [
+                    pixels[0] as f32,
+                    pixels[1] as f32,
+                    pixels[2] as f32,
+                    pixels[3] as f32,
+                ];
+                blend(values, weights)
+                    .round()
+                    .max(<$t>::MIN as f32)
+                    .min(<$t>::MAX as f32) as $t
+            }
+        }
+    };
+}
+
+impl_interpolate_for_integer!(u8);
+impl_interpolate_for_integer!(u16);
+
+impl Interpolate for f32 {
+    fn interpolate(pixels: [Self; 4], weights: [f32; 4]) -> Self {
+        blend(pixels, weights)
+    }
+}
+
+impl<T: Interpolate, const N: usize> Interpolate for [T; N]
+where
+    [T; N]: Default,
+{
+    fn interpolate(pixels: [Self; 4], weights: [f32; 4]) -> Self {
+        let mut result = Self::default();
+        for (channel, value) in result.iter_mut().enumerate() {
+            *value = T::interpolate(
+                [
+                    pixels[0][channel],
+                    pixels[1][channel],
+                    pixels[2][channel],
+                    pixels[3][channel],
+                ],
+                weights,
+            );
+        }
+        result
+    }
+}
+
+/// A lookup table from every pixel of a target pinhole image to the coordinate it samples in a
+/// distorted source image.
+#[derive(Debug, Clone)]
+pub struct UndistortionMap {
+    /// The width of the target image in pixels.
+    width: usize,
+    /// The height of the target image in pixels.
+    height: usize,
+    /// The width of the source image in pixels.
+    source_width: usize,
+    /// The height of the source image in pixels.
+    source_height: usize,
+    /// The source coordinate sampled by each target pixel, in row-major order.
+    ///
+    /// Coordinates are NaN for target pixels that do not see anything in the source image.
+    coordinates: Vec<[f32; 2]>,
+}
+
+impl UndistortionMap {
+    /// Build a map from a distorted `source` image to an undistorted `target` image.
+    ///
+    /// Only the size, focal length and principal point of `target` are used; its distortion model
+    /// is ignored, and the target image is always a pinhole image. To undistort an image without
+    /// changing its field of view, use the source intrinsics with their distortion removed.
+    pub fn new(source: &Rs2Intrinsics, target: &Rs2Intrinsics) -> Self {
+        Self::with_rotation(source, target, &Rs2Extrinsics::identity())
+    }
+
+    /// Build a map from a distorted `source` image to an undistorted `target` image, where the
+    /// target camera is rotated relative to the source camera.
+    ///
+    /// `target_to_source` are the extrinsics from the target camera to the source camera. The
+    /// cameras share a center, so only the rotation is used.
+    pub fn with_rotation(
+        source: &Rs2Intrinsics,
+        target: &Rs2Intrinsics,
+        target_to_source: &Rs2Extrinsics,
+    ) -> Self {
+        let rotation = Rs2Extrinsics::new(target_to_source.rotation(), [0.0; 3]);
+        let (width, height) = (target.width(), target.height());
+
+        let mut coordinates = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for col in 0..width {
+                // Undistorted ray through the target pixel.
+                let x = (col as f32 - target.ppx()) / target.fx();
+                let y = (row as f32 - target.ppy()) / target.fy();
+                let ray = rotation.transform_point([x, y, 1.0]);
+
+                if ray[2] > 0.0 {
+                    coordinates.push(source.project(ray));
+                } else {
+                    // Rays behind the source camera cannot be projected into it.
+                    coordinates.push([f32::NAN; 2]);
+                }
+            }
+        }
+
+        UndistortionMap {
+            width,
+            height,
+            source_width: source.width(),
+            source_height: source.height(),
+            coordinates,
+        }
+    }
+
+    /// Get the width of the target image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the height of the target image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the width of the source image in pixels.
+    pub fn source_width(&self) -> usize {
+        self.source_width
+    }
+
+    /// Get the height of the source image in pixels.
+    pub fn source_height(&self) -> usize {
+        self.source_height
+    }
+
+    /// Get the coordinate in the source image sampled by the target pixel at a given column and
+    /// row.
+    ///
+    /// Returns `None` if the target pixel is out of bounds, or if it does not see anything in the
+    /// source image.
+    pub fn source_coordinate(&self, col: usize, row: usize) -> Option<[f32; 2]> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+
+        let [x, y] = self.coordinates[row * self.width + col];
+        if self.in_source(x, y) {
+            Some([x, y])
+        } else {
+            None
+        }
+    }
+
+    /// Remap a source image into the target image with bilinear interpolation.
+    ///
+    /// `source_at` returns the pixel of the source image at a given column and row, and is only
+    /// called with coordinates inside the source image. Target pixels that do not see anything in
+    /// the source image are filled with `T::default()` (i.e. black).
+    pub fn remap<T, F>(&self, source_at: F) -> RegisteredImage<T>
+    where
+        T: Interpolate,
+        F: Fn(usize, usize) -> T,
+    {
+        let pixels = self
+            .coordinates
+            .iter()
+            .map(|&[x, y]| {
+                if !self.in_source(x, y) {
+                    return T::default();
+                }
+
+                // Pixel centers lie at integer coordinates, so pixels within half a pixel of the
+                // edge are clamped to the edge pixels.
+                let x = x.max(0.0).min((self.source_width - 1) as f32);
+                let y = y.max(0.0).min((self.source_height - 1) as f32);
+                let (col, row) = (x.floor() as usize, y.floor() as usize);
+                let (dx, dy) = (x - col as f32, y - row as f32);
+                let next_col = (col + 1).min(self.source_width - 1);
+                let next_row = (row + 1).min(self.source_height - 1);
+
+                T::interpolate(
+                    [
+                        source_at(col, row),
+                        source_at(next_col, row),
+                        source_at(col, next_row),
+                        source_at(next_col, next_row),
+                    ],
+                    [
+                        (1.0 - dx) * (1.0 - dy),
+                        dx * (1.0 - dy),
+                        (1.0 - dx) * dy,
+                        dx * dy,
+                    ],
+                )
+            })
+            .collect();
+
+        RegisteredImage {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+
+    /// Whether a coordinate lies inside the source image, including the outer half of its edge
+    /// pixels.
+    #[inline]
+    fn in_source(&self, x: f32, y: f32) -> bool {
+        x >= -0.5
+            && y >= -0.5
+            && x <= self.source_width as f32 - 0.5
+            && y <= self.source_height as f32 - 0.5
+    }
+}
+
+/// A pair of undistortion maps that rectify a stereo pair of cameras.
+///
+/// Both rectified images share the same pinhole intrinsics and orientation, with the baseline
+/// between the cameras along the rectified X axis. A point in the scene therefore appears on the
+/// same row of both rectified images, and its disparity (the column in the left image minus the
+/// column in the right image) is `fx * baseline / depth`.
+#[derive(Debug, Clone)]
+pub struct StereoRectification {
+    /// The map rectifying the left image.
+    left: UndistortionMap,
+    /// The map rectifying the right image.
+    right: UndistortionMap,
+    /// The intrinsics shared by both rectified images.
+    intrinsics: Rs2Intrinsics,
+    /// The extrinsics from the left camera to the rectified left camera.
+    left_to_rectified: Rs2Extrinsics,
+    /// The distance between the cameras, in meters.
+    baseline: f32,
+}
+
+impl StereoRectification {
+    /// Compute the rectification of a stereo pair.
+    ///
+    /// `left` and `right` are the intrinsics of the two cameras, and `left_to_right` the
+    /// extrinsics from the left camera to the right camera, as returned by
+    /// [`StreamProfile::extrinsics`](crate::stream_profile::StreamProfile::extrinsics). The right
+    /// camera is expected to lie roughly along the positive X axis of the left camera, as is the
+    /// case for the T265 fisheye pair.
+    ///
+    /// `rectified` gives the size, focal length and principal point of the rectified images. As
+    /// with [`UndistortionMap::new`], its distortion model is ignored.
+    pub fn new(
+        left: &Rs2Intrinsics,
+        right: &Rs2Intrinsics,
+        left_to_right: &Rs2Extrinsics,
+        rectified: &Rs2Intrinsics,
+    ) -> Self {
+        // Center of the right camera in the left camera's coordinate frame.
+        let center = left_to_right.inverse().translation();
+        let baseline = center.iter().map(|c| c * c).sum::<f32>().sqrt();
+
+        // The rectified X axis points along the baseline, and the Y axis is kept as close as
+        // possible to that of the left camera.
+        let e1 = [
+            center[0] / baseline,
+            center[1] / baseline,
+            center[2] / baseline,
+        ];
+        let norm = (e1[0] * e1[0] + e1[1] * e1[1]).sqrt();
+        let e2 = [-e1[1] / norm, e1[0] / norm, 0.0];
+        let e3 = [
+            e1[1] * e2[2] - e1[2] * e2[1],
+            e1[2] * e2[0] - e1[0] * e2[2],
+            e1[0] * e2[1] - e1[1] * e2[0],
+        ];
+
+        // The rows of the rectifying rotation are the rectified axes; stored column-major.
+        let left_to_rectified = Rs2Extrinsics::new(
+            [
+                e1[0], e2[0], e3[0], e1[1], e2[1], e3[1], e1[2], e2[2], e3[2],
+            ],
+            [0.0; 3],
+        );
+        let rectified_to_left = left_to_rectified.inverse();
+        let rectified_to_right = Rs2Extrinsics::new(
+            rectified_to_left.compose(left_to_right).rotation(),
+            [0.0; 3],
+        );
+
+        StereoRectification {
+            left: UndistortionMap::with_rotation(left, rectified, &rectified_to_left),
+            right: UndistortionMap::with_rotation(right, rectified, &rectified_to_right),
+            intrinsics: *rectified,
+            left_to_rectified,
+            baseline,
+        }
+    }
+
+    /// Get the map rectifying the left image.
+    pub fn left(&self) -> &UndistortionMap {
+        &self.left
+    }
+
+    /// Get the map rectifying the right image.
+    pub fn right(&self) -> &UndistortionMap {
+        &self.right
+    }
+
+    /// Get the pinhole intrinsics shared by both rectified images.
+    pub fn intrinsics(&self) -> &Rs2Intrinsics {
+        &self.intrinsics
+    }
+
+    /// Get the extrinsics from the left camera to the rectified left camera.
+    ///
+    /// This rotates points from the left camera's coordinate frame into the coordinate frame of
+    /// the rectified images, e.g. to place points triangulated from disparity back into the
+    /// scene.
+    pub fn left_to_rectified(&self) -> &Rs2Extrinsics {
+        &self.left_to_rectified
+    }
+
+    /// Get the distance between the two cameras, in meters.
+    pub fn baseline(&self) -> f32 {
+        self.baseline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{base::Rs2Distortion, kind::Rs2DistortionModel};
+
+    fn fisheye() -> Rs2Intrinsics {
+        Rs2Intrinsics::new(
+            848,
+            800,
+            [424.3, 400.1],
+            [286.2, 286.4],
+            Rs2Distortion {
+                model: Rs2DistortionModel::KannalaBrandt,
+                coeffs: [-0.0066, 0.0432, -0.0409, 0.0077, 0.0],
+            },
+        )
+    }
+
+    fn pinhole(width: usize, height: usize, focal_length: f32) -> Rs2Intrinsics {
+        Rs2Intrinsics::new(
+            width,
+            height,
+            [(width - 1) as f32 / 2.0, (height - 1) as f32 / 2.0],
+            [focal_length, focal_length],
+            Rs2Distortion {
+                model: Rs2DistortionModel::None,
+                coeffs: [0.0; 5],
+            },
+        )
+    }
+
+    #[test]
+    fn interpolates_pixels() {
+        let weights = [0.25, 0.25, 0.25, 0.25];
+        assert_eq!(u8::interpolate([0, 10, 20, 31], weights), 15);
+        assert_eq!(
+            u16::interpolate([1000, 0, 0, 0], [1.0, 0.0, 0.0, 0.0]),
+            1000
+        );
+        assert_eq!(
+            <[u8; 3]>::interpolate(
+                [[0, 0, 255], [255, 0, 255], [0, 0, 255], [255, 0, 255]],
+                weights
+            ),
+            [128, 0, 255]
+        );
+    }
+
+    #[test]
+    fn identity_map_copies_image() {
+        let intrinsics = pinhole(6, 4, 5.0);
+        let map = UndistortionMap::new(&intrinsics, &intrinsics);
+        let image = map.remap(|col, row| (row * 6 + col) as u8);
+
+        assert_eq!(image.pixels(), (0..24).collect::<Vec<u8>>().as_slice());
+    }
+
+    #[test]
+    fn map_samples_distorted_source() {
+        let source = fisheye();
+        let target = pinhole(400, 300, 200.0);
+        let map = UndistortionMap::new(&source, &target);
+
+        // Every target pixel samples the source pixel that sees the same ray.
+        for &(col, row) in [(0, 0), (199, 150), (399, 299)].iter() {
+            let ray = target.deproject([col as f32, row as f32], 1.0);
+            let expected = source.project(ray);
+            let coordinate = map.source_coordinate(col, row).unwrap();
+            assert!((coordinate[0] - expected[0]).abs() < 1e-3);
+            assert!((coordinate[1] - expected[1]).abs() < 1e-3);
+        }
+
+        // A target image much wider than the source sees nothing at its edges.
+        let wide = UndistortionMap::new(&pinhole(640, 480, 150.0), &pinhole(640, 480, 50.0));
+        assert_eq!(wide.source_coordinate(0, 0), None);
+        assert_eq!(wide.remap(|_, _| 255u8).get(0, 0), Some(&0));
+    }
+
+    #[test]
+    fn rectified_points_share_rows() {
+        let left = fisheye();
+        let right = fisheye();
+        // The right camera is 6.4cm along X, and slightly rotated about Y and Z.
+        let right_to_left = Rs2Extrinsics::new(
+            [
+                0.9998, 0.0100, -0.0174, -0.0102, 0.9999, -0.0050, 0.0174, 0.0052, 0.9998,
+            ],
+            [0.064, 0.0005, -0.0003],
+        );
+        let left_to_right = right_to_left.inverse();
+        let rectified = pinhole(300, 300, 150.0);
+        let rectification = StereoRectification::new(&left, &right, &left_to_right, &rectified);
+
+        assert!((rectification.baseline() - 0.064).abs() < 1e-3);
+
+        // Project a point into both source images, then find where the rectified maps sample it.
+        let point = [0.3, -0.2, 2.0];
+        let rectified_point = rectification.left_to_rectified().transform_point(point);
+        let left_pixel = rectified.project(rectified_point);
+
+        let right_source = right.project(left_to_right.transform_point(point));
+        let right_row = left_pixel[1].round() as usize;
+        let right_col = (0..300)
+            .filter_map(|col| {
+                let coordinate = rectification.right().source_coordinate(col, right_row)?;
+                let dx = coordinate[0] - right_source[0];
+                let dy = coordinate[1] - right_source[1];
+                Some((col, dx * dx + dy * dy))
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap()
+            .0;
+
+        // The point is on the same row in both images, shifted by the expected disparity.
+        let expected_disparity = 150.0 * 0.064 / rectified_point[2];
+        assert!((left_pixel[0] - right_col as f32 - expected_disparity).abs() <= 1.0);
+        let right_coordinate = rectification
+            .right()
+            .source_coordinate(right_col, right_row)
+            .unwrap();
+        assert!((right_coordinate[1] - right_source[1]).abs() < 2.0);
+    }
+}
@@ -13,6 +13,7 @@ use realsense_sys as sys;
 
 #[repr(i32)]
 #[derive(FromPrimitive, ToPrimitive, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Rs2DistortionModel {
     /// Rectilinear images. No distortion compensation required.
     None = sys::rs2_distortion_RS2_DISTORTION_NONE as i32,
@@ -28,6 +28,8 @@
 //!
 //! - **buildtime-bindgen**: Generate Rust bindings during build time.
 //! - **device-test**: Enable tests that requires connections to RealSense devices.
+//! - **jpeg**: Decode color frames streamed in the MJPEG format into RGB8 images.
+//! - **serde**: Serialize calibration types with serde, and read / write ROS, OpenCV and Kalibr calibration files.
 //!
 //! ## Regenerating the API Bindings
 //!
@@ -79,6 +81,8 @@
 //! Apache 2.0. See [LICENSE](LICENSE) file.
 
 pub mod base;
+#[cfg(feature = "serde")]
+pub mod calibration;
 pub mod config;
 pub mod context;
 pub mod device;
@@ -22,4 +22,4 @@ mod profile;
 
 pub use active::{ActivePipeline, FrameWaitError};
 pub use inactive::{InactivePipeline, PipelineActivationError, PipelineConstructionError};
-pub use profile::{PipelineProfile, PipelineProfileConstructionError};
+pub use profile::{ExtrinsicsGraph, PipelineProfile, PipelineProfileConstructionError};
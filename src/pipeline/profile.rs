@@ -1,9 +1,15 @@
 //! Defines a type which holds the device & streams associated with an `ActivePipeline`.
 
-use crate::{check_rs2_error, device::Device, kind::Rs2Exception, stream_profile::StreamProfile};
+use crate::{
+    base::Rs2Extrinsics,
+    check_rs2_error,
+    device::Device,
+    kind::Rs2Exception,
+    stream_profile::{DataError, StreamProfile},
+};
 use anyhow::Result;
 use realsense_sys as sys;
-use std::{convert::TryFrom, ptr::NonNull};
+use std::{collections::HashMap, convert::TryFrom, ptr::NonNull};
 use thiserror::Error;
 
 /// Type representing the device and streams associated with a pipeline.
@@ -100,4 +106,61 @@ impl PipelineProfile {
     pub fn streams(&self) -> &Vec<StreamProfile> {
         &self.streams
     }
+
+    /// Gets the extrinsics between every pair of streams associated with a pipeline.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DataError::CouldNotGetExtrinsics`] if the extrinsics between any two streams
+    /// cannot be obtained.
+    pub fn extrinsics_graph(&self) -> Result<ExtrinsicsGraph, DataError> {
+        let mut transforms = HashMap::new();
+
+        for from in self.streams.iter() {
+            for to in self.streams.iter() {
+                let extrinsics = if from.unique_id() == to.unique_id() {
+                    Rs2Extrinsics::identity()
+                } else {
+                    from.extrinsics(to)?
+                };
+                transforms.insert((from.unique_id(), to.unique_id()), extrinsics);
+            }
+        }
+
+        Ok(ExtrinsicsGraph { transforms })
+    }
+}
+
+/// The extrinsics between every pair of streams in a [`PipelineProfile`].
+///
+/// Streams are identified by their [unique ID](StreamProfile::unique_id), so the graph can be
+/// queried with stream profiles taken from frames as well as from the pipeline profile itself.
+#[derive(Debug, Clone)]
+pub struct ExtrinsicsGraph {
+    /// Extrinsics keyed by the unique IDs of the origin and target streams.
+    transforms: HashMap<(i32, i32), Rs2Extrinsics>,
+}
+
+impl ExtrinsicsGraph {
+    /// Gets the extrinsics from the `from` stream to the `to` stream.
+    ///
+    /// Returns `None` if either stream is not part of the pipeline profile.
+    pub fn get(&self, from: &StreamProfile, to: &StreamProfile) -> Option<&Rs2Extrinsics> {
+        self.get_by_id(from.unique_id(), to.unique_id())
+    }
+
+    /// Gets the extrinsics between the streams with unique IDs `from_id` and `to_id`.
+    ///
+    /// Returns `None` if either stream is not part of the pipeline profile.
+    pub fn get_by_id(&self, from_id: i32, to_id: i32) -> Option<&Rs2Extrinsics> {
+        self.transforms.get(&(from_id, to_id))
+    }
+
+    /// Gets the unique IDs of all streams in the graph, in ascending order.
+    pub fn stream_ids(&self) -> Vec<i32> {
+        let mut ids: Vec<i32> = self.transforms.keys().map(|&(from, _)| from).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
 }
@@ -10,6 +10,13 @@
 //! The hierarchy is effectively:
 //!
 //! [`Device`] |-> [`Sensor`] |-> [`StreamProfile`]
+//!
+//! Capabilities specific to a kind of sensor are exposed through types wrapping a [`Sensor`],
+//! such as [`PoseSensor`].
+
+mod pose;
+
+pub use pose::{NotAPoseSensorError, PoseSensor, PoseSensorError, RelocalizationEvent, StaticNode};
 
 use crate::{
     base::Rs2Roi,
@@ -66,6 +73,7 @@ pub enum RoiSetError {
 ///
 /// 1. From the device's [sensor list](crate::device::Device::sensors)
 /// 2. By getting the sensor that [corresponds to a given frame](crate::frame::FrameEx::sensor)
+#[derive(Debug)]
 pub struct Sensor {
     /// The underlying non-null sensor pointer.
     ///
@@ -160,26 +168,35 @@ impl Sensor {
     pub fn extension(&self) -> Rs2Extension {
         let ext = SENSOR_EXTENSIONS
             .iter()
-            .find(|ext| unsafe {
-                let mut err = std::ptr::null_mut::<sys::rs2_error>();
-                let is_extendable = sys::rs2_is_sensor_extendable_to(
-                    self.sensor_ptr.as_ptr(),
-                    #[allow(clippy::useless_conversion)]
-                    (**ext as i32).try_into().unwrap(),
-                    &mut err,
-                );
-
-                if err.as_ref().is_none() {
-                    is_extendable != 0
-                } else {
-                    sys::rs2_free_error(err);
-                    false
-                }
-            })
+            .find(|ext| self.is_extendable_to(**ext))
             .unwrap();
         *ext
     }
 
+    /// Predicate for determining if the sensor can be extended to the given extension.
+    ///
+    /// A sensor may be extendable to more than one extension; e.g. the T265 tracking sensor is
+    /// both a [`Rs2Extension::PoseSensor`] and a [`Rs2Extension::Tm2Sensor`], while
+    /// [`Sensor::extension`] only reports one of these.
+    pub fn is_extendable_to(&self, extension: Rs2Extension) -> bool {
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            let is_extendable = sys::rs2_is_sensor_extendable_to(
+                self.sensor_ptr.as_ptr(),
+                #[allow(clippy::useless_conversion)]
+                (extension as i32).try_into().unwrap(),
+                &mut err,
+            );
+
+            if err.as_ref().is_none() {
+                is_extendable != 0
+            } else {
+                sys::rs2_free_error(err);
+                false
+            }
+        }
+    }
+
     /// Get the value associated with the provided Rs2Option for the sensor.
     ///
     /// Returns An `f32` value corresponding to that option within the librealsense2 library, or None
@@ -0,0 +1,558 @@
+//! Type for the pose sensor of tracking devices such as the T265.
+//!
+//! Beyond streaming poses, the pose sensor keeps a localization map of the environment it has
+//! seen. The map can be exported and later imported again so that the device relocalizes against
+//! it, e.g. after a reboot. Static nodes are named poses stored in that map; once the device
+//! relocalizes, their poses are reported in the coordinate frame of the current session.
+//!
+//! Relocalization is reported asynchronously through a notification from the sensor, see
+//! [`PoseSensor::relocalization_events`].
+
+use super::Sensor;
+use crate::{
+    base::Rs2Extrinsics,
+    check_rs2_error,
+    kind::{Rs2Exception, Rs2Extension},
+};
+use realsense_sys as sys;
+use std::{
+    convert::TryFrom,
+    ffi::{CStr, CString},
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    os::raw::c_void,
+    ptr::NonNull,
+    sync::mpsc::{self, Receiver, Sender},
+};
+use thiserror::Error;
+
+/// The maximum length in bytes of a static node's GUID.
+const MAX_GUID_LENGTH: usize = 127;
+
+/// Type describing errors that can occur when using the capabilities of a pose sensor.
+///
+/// Follows the standard pattern of errors where the enum variant describes what the low-level code
+/// was attempting to do while the string carried alongside describes the underlying error message
+/// from any C++ exceptions that occur.
+#[derive(Error, Debug)]
+pub enum PoseSensorError {
+    /// The GUID of a static node is too long or contains a null byte.
+    #[error("Static node GUID must be at most 127 bytes without null bytes: {0:?}")]
+    InvalidGuid(String),
+    /// Could not import the localization map.
+    #[error("Could not import localization map. Type: {0}; Reason: {1}")]
+    CouldNotImportLocalizationMap(Rs2Exception, String),
+    /// The sensor did not accept the localization map.
+    #[error("Localization map was rejected by the sensor.")]
+    LocalizationMapRejected,
+    /// Could not export the localization map.
+    #[error("Could not export localization map. Type: {0}; Reason: {1}")]
+    CouldNotExportLocalizationMap(Rs2Exception, String),
+    /// Could not set the static node.
+    #[error("Could not set static node. Type: {0}; Reason: {1}")]
+    CouldNotSetStaticNode(Rs2Exception, String),
+    /// The sensor did not accept the static node.
+    #[error("Static node {0:?} was rejected by the sensor.")]
+    StaticNodeRejected(String),
+    /// Could not get the static node.
+    #[error("Could not get static node. Type: {0}; Reason: {1}")]
+    CouldNotGetStaticNode(Rs2Exception, String),
+    /// Could not remove the static node.
+    #[error("Could not remove static node. Type: {0}; Reason: {1}")]
+    CouldNotRemoveStaticNode(Rs2Exception, String),
+    /// Could not register for notifications from the sensor.
+    #[error("Could not set notifications callback. Type: {0}; Reason: {1}")]
+    CouldNotSetNotificationsCallback(Rs2Exception, String),
+}
+
+/// Occurs when a sensor cannot be extended to a pose sensor.
+///
+/// The sensor is handed back so that it can still be used.
+#[derive(Error, Debug)]
+#[error("Sensor is not a pose sensor.")]
+pub struct NotAPoseSensorError(pub Sensor);
+
+/// A named pose stored in the localization map of a pose sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaticNode {
+    /// X, Y, Z values of translation in meters, relative to the origin of the current session.
+    pub translation: [f32; 3],
+    /// Qi, Qj, Qk, Qr components of rotation, relative to the origin of the current session.
+    pub rotation: [f32; 4],
+}
+
+impl StaticNode {
+    /// Get the node as extrinsics from the node's coordinate frame to the world frame of the
+    /// current session.
+    pub fn to_extrinsics(&self) -> Rs2Extrinsics {
+        Rs2Extrinsics::from_quaternion(self.rotation, self.translation)
+    }
+}
+
+/// Notification that the pose sensor relocalized against its localization map.
+///
+/// After relocalizing, poses are reported relative to the origin of the map rather than the
+/// origin of the current session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelocalizationEvent {
+    /// The time of the notification in milliseconds.
+    pub timestamp: f64,
+    /// Human readable description of the event.
+    pub description: String,
+    /// Serialized data attached to the notification by the device.
+    pub serialized_data: String,
+}
+
+/// Type for the pose sensor of a tracking device.
+///
+/// A `PoseSensor` is obtained from a [`Sensor`] that can be extended to
+/// [`Rs2Extension::PoseSensor`], and dereferences to it for options, stream profiles, etc.
+pub struct PoseSensor {
+    /// The current subscription to relocalization events, if any.
+    ///
+    /// Declared before `sensor` so that it is dropped while the sensor is still alive.
+    subscription: Option<Subscription>,
+    /// The sensor being extended.
+    sensor: Sensor,
+}
+
+impl TryFrom<Sensor> for PoseSensor {
+    type Error = NotAPoseSensorError;
+
+    /// Attempt to extend a sensor to a pose sensor.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NotAPoseSensorError`], holding the sensor, if the sensor cannot be extended to
+    /// [`Rs2Extension::PoseSensor`].
+    fn try_from(sensor: Sensor) -> Result<Self, Self::Error> {
+        if sensor.is_extendable_to(Rs2Extension::PoseSensor) {
+            Ok(PoseSensor {
+                subscription: None,
+                sensor,
+            })
+        } else {
+            Err(NotAPoseSensorError(sensor))
+        }
+    }
+}
+
+impl Deref for PoseSensor {
+    type Target = Sensor;
+
+    fn deref(&self) -> &Sensor {
+        &self.sensor
+    }
+}
+
+impl DerefMut for PoseSensor {
+    fn deref_mut(&mut self) -> &mut Sensor {
+        &mut self.sensor
+    }
+}
+
+impl PoseSensor {
+    /// Get back the sensor this pose sensor extends.
+    ///
+    /// Any subscription to relocalization events is ended.
+    pub fn into_sensor(self) -> Sensor {
+        let PoseSensor {
+            subscription,
+            sensor,
+        } = self;
+        drop(subscription);
+        sensor
+    }
+
+    /// Load a localization map previously obtained from
+    /// [`export_localization_map`](PoseSensor::export_localization_map).
+    ///
+    /// The map can only be imported while the sensor is not streaming. Once streaming starts, the
+    /// device relocalizes as soon as it recognizes its surroundings, which is reported through
+    /// [`relocalization_events`](PoseSensor::relocalization_events).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PoseSensorError::CouldNotImportLocalizationMap`] if the map could not be sent to
+    /// the device, e.g. because the sensor is streaming.
+    ///
+    /// Returns [`PoseSensorError::LocalizationMapRejected`] if the device did not accept the map.
+    pub fn import_localization_map(&mut self, map: &[u8]) -> Result<(), PoseSensorError> {
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            let imported = sys::rs2_import_localization_map(
+                self.sensor.sensor_ptr.as_ptr(),
+                map.as_ptr(),
+                map.len() as u32,
+                &mut err,
+            );
+            check_rs2_error!(err, PoseSensorError::CouldNotImportLocalizationMap)?;
+
+            if imported == 0 {
+                Err(PoseSensorError::LocalizationMapRejected)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Get the localization map of the sensor, including its static nodes.
+    ///
+    /// The map can only be exported while the sensor is not streaming, so stop the pipeline
+    /// before calling this. The map is an opaque blob meant to be saved and passed to
+    /// [`import_localization_map`](PoseSensor::import_localization_map) later.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PoseSensorError::CouldNotExportLocalizationMap`] if the map could not be read
+    /// from the device.
+    pub fn export_localization_map(&self) -> Result<Vec<u8>, PoseSensorError> {
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            let buffer =
+                sys::rs2_export_localization_map(self.sensor.sensor_ptr.as_ptr(), &mut err);
+            check_rs2_error!(err, PoseSensorError::CouldNotExportLocalizationMap)?;
+
+            let size = sys::rs2_get_raw_data_size(buffer, &mut err);
+            if let Err(e) = check_rs2_error!(err, PoseSensorError::CouldNotExportLocalizationMap) {
+                sys::rs2_delete_raw_data(buffer);
+                return Err(e);
+            }
+
+            let data = sys::rs2_get_raw_data(buffer, &mut err);
+            if let Err(e) = check_rs2_error!(err, PoseSensorError::CouldNotExportLocalizationMap) {
+                sys::rs2_delete_raw_data(buffer);
+                return Err(e);
+            }
+
+            let map = std::slice::from_raw_parts(data, size.max(0) as usize).to_vec();
+            sys::rs2_delete_raw_data(buffer);
+            Ok(map)
+        }
+    }
+
+    /// Store a static node with the given GUID in the localization map.
+    ///
+    /// The pose is given relative to the origin of the current session, i.e. in the same frame
+    /// as the poses streamed by the sensor. Setting a node with an existing GUID replaces it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PoseSensorError::InvalidGuid`] if the GUID is longer than 127 bytes or contains
+    /// a null byte.
+    ///
+    /// Returns [`PoseSensorError::CouldNotSetStaticNode`] if the node could not be sent to the
+    /// device.
+    ///
+    /// Returns [`PoseSensorError::StaticNodeRejected`] if the device did not store the node. This
+    /// happens when tracking confidence is not high enough, e.g. right after streaming starts.
+    pub fn set_static_node(&mut self, guid: &str, node: StaticNode) -> Result<(), PoseSensorError> {
+        let c_guid = guid_to_cstring(guid)?;
+        let [x, y, z] = node.translation;
+        let [qx, qy, qz, qw] = node.rotation;
+
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            let stored = sys::rs2_set_static_node(
+                self.sensor.sensor_ptr.as_ptr(),
+                c_guid.as_ptr(),
+                sys::rs2_vector { x, y, z },
+                sys::rs2_quaternion {
+                    x: qx,
+                    y: qy,
+                    z: qz,
+                    w: qw,
+                },
+                &mut err,
+            );
+            check_rs2_error!(err, PoseSensorError::CouldNotSetStaticNode)?;
+
+            if stored == 0 {
+                Err(PoseSensorError::StaticNodeRejected(guid.to_owned()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Get the static node with the given GUID from the localization map.
+    ///
+    /// Returns `None` if there is no such node, or the device has not relocalized against the map
+    /// the node belongs to yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PoseSensorError::InvalidGuid`] if the GUID is longer than 127 bytes or contains
+    /// a null byte.
+    ///
+    /// Returns [`PoseSensorError::CouldNotGetStaticNode`] if the node could not be requested from
+    /// the device.
+    pub fn static_node(&self, guid: &str) -> Result<Option<StaticNode>, PoseSensorError> {
+        let c_guid = guid_to_cstring(guid)?;
+
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            let mut pos = MaybeUninit::<sys::rs2_vector>::uninit();
+            let mut orient = MaybeUninit::<sys::rs2_quaternion>::uninit();
+
+            let found = sys::rs2_get_static_node(
+                self.sensor.sensor_ptr.as_ptr(),
+                c_guid.as_ptr(),
+                pos.as_mut_ptr(),
+                orient.as_mut_ptr(),
+                &mut err,
+            );
+            check_rs2_error!(err, PoseSensorError::CouldNotGetStaticNode)?;
+
+            if found == 0 {
+                return Ok(None);
+            }
+
+            let sys::rs2_vector { x, y, z } = pos.assume_init();
+            let sys::rs2_quaternion {
+                x: qx,
+                y: qy,
+                z: qz,
+                w: qw,
+            } = orient.assume_init();
+            Ok(Some(StaticNode {
+                translation: [x, y, z],
+                rotation: [qx, qy, qz, qw],
+            }))
+        }
+    }
+
+    /// Remove the static node with the given GUID from the localization map.
+    ///
+    /// Returns whether the node was removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PoseSensorError::InvalidGuid`] if the GUID is longer than 127 bytes or contains
+    /// a null byte.
+    ///
+    /// Returns [`PoseSensorError::CouldNotRemoveStaticNode`] if the request could not be sent to
+    /// the device.
+    pub fn remove_static_node(&mut self, guid: &str) -> Result<bool, PoseSensorError> {
+        let c_guid = guid_to_cstring(guid)?;
+
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            let removed = sys::rs2_remove_static_node(
+                self.sensor.sensor_ptr.as_ptr(),
+                c_guid.as_ptr(),
+                &mut err,
+            );
+            check_rs2_error!(err, PoseSensorError::CouldNotRemoveStaticNode)?;
+
+            Ok(removed != 0)
+        }
+    }
+
+    /// Subscribe to relocalization events of the sensor.
+    ///
+    /// Events are sent to the returned receiver from librealsense's notification thread. Other
+    /// notifications of the sensor are ignored.
+    ///
+    /// A sensor has a single notifications callback, so calling this again replaces the previous
+    /// subscription. The subscription also ends when the pose sensor is dropped or turned back
+    /// into a [`Sensor`]. Once it ends, the receiver gets the remaining events and then reports
+    /// that it is disconnected. Until then `recv` blocks as long as no relocalization happens,
+    /// which may be forever; prefer `recv_timeout` or `try_recv` when polling alongside a
+    /// pipeline.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PoseSensorError::CouldNotSetNotificationsCallback`] if the callback could not be
+    /// registered with the sensor.
+    pub fn relocalization_events(
+        &mut self,
+    ) -> Result<Receiver<RelocalizationEvent>, PoseSensorError> {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Box::into_raw(Box::new(sender));
+
+        unsafe {
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            sys::rs2_set_notifications_callback(
+                self.sensor.sensor_ptr.as_ptr(),
+                Some(on_notification),
+                sender.cast::<c_void>(),
+                &mut err,
+            );
+            if let Err(e) = check_rs2_error!(err, PoseSensorError::CouldNotSetNotificationsCallback)
+            {
+                drop(Box::from_raw(sender));
+                return Err(e);
+            }
+        }
+
+        // librealsense no longer calls back with the previous sender, so it can be freed.
+        if let Some(mut previous) = self.subscription.take() {
+            unsafe { previous.release() };
+        }
+        self.subscription = Some(Subscription {
+            sensor_ptr: self.sensor.sensor_ptr,
+            sender,
+        });
+
+        Ok(receiver)
+    }
+}
+
+/// The sender registered as user data of the notifications callback of a sensor.
+struct Subscription {
+    /// The sensor the callback is registered with.
+    sensor_ptr: NonNull<sys::rs2_sensor>,
+    /// The sender passed to [`on_notification`], or null once released.
+    sender: *mut Sender<RelocalizationEvent>,
+}
+
+unsafe impl Send for Subscription {}
+
+impl Subscription {
+    /// Free the sender, which librealsense must no longer be calling back with.
+    unsafe fn release(&mut self) {
+        if !self.sender.is_null() {
+            drop(Box::from_raw(self.sender));
+            self.sender = std::ptr::null_mut();
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        unsafe {
+            // Replace the user data with null so that librealsense stops using the sender. The
+            // callback cannot be removed altogether, and `on_notification` ignores null user data.
+            let mut err = std::ptr::null_mut::<sys::rs2_error>();
+            sys::rs2_set_notifications_callback(
+                self.sensor_ptr.as_ptr(),
+                Some(on_notification),
+                std::ptr::null_mut(),
+                &mut err,
+            );
+            if err.as_ref().is_some() {
+                // The sender may still be in use, so leak it rather than risk a use after free.
+                sys::rs2_free_error(err);
+                return;
+            }
+            self.release();
+        }
+    }
+}
+
+/// Callback registered with librealsense that forwards relocalization notifications to the
+/// `Sender` behind `user`, if any.
+unsafe extern "C" fn on_notification(notification: *mut sys::rs2_notification, user: *mut c_void) {
+    let sender = match user.cast::<Sender<RelocalizationEvent>>().as_ref() {
+        Some(sender) => sender,
+        None => return,
+    };
+    let mut err = std::ptr::null_mut::<sys::rs2_error>();
+
+    let category = sys::rs2_get_notification_category(notification, &mut err);
+    if err.as_ref().is_some() {
+        sys::rs2_free_error(err);
+        return;
+    }
+    if !is_relocalization(category) {
+        return;
+    }
+
+    let timestamp = sys::rs2_get_notification_timestamp(notification, &mut err);
+    if err.as_ref().is_some() {
+        sys::rs2_free_error(err);
+        return;
+    }
+
+    let description = sys::rs2_get_notification_description(notification, &mut err);
+    let description = notification_string(description, &mut err);
+    let serialized_data = sys::rs2_get_notification_serialized_data(notification, &mut err);
+    let serialized_data = notification_string(serialized_data, &mut err);
+
+    let event = RelocalizationEvent {
+        timestamp,
+        description,
+        serialized_data,
+    };
+
+    // The receiver may have been dropped, in which case there is nobody to notify.
+    let _ = sender.send(event);
+}
+
+/// Copy a string returned from a notification getter, or an empty string if the getter failed.
+unsafe fn notification_string(
+    value: *const std::os::raw::c_char,
+    err: &mut *mut sys::rs2_error,
+) -> String {
+    if err.as_ref().is_some() {
+        sys::rs2_free_error(*err);
+        *err = std::ptr::null_mut();
+        return String::new();
+    }
+    if value.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(value).to_string_lossy().into_owned()
+}
+
+/// Predicate for whether a notification category reports a relocalization.
+pub(crate) fn is_relocalization(category: sys::rs2_notification_category) -> bool {
+    category == sys::rs2_notification_category_RS2_NOTIFICATION_CATEGORY_POSE_RELOCALIZATION
+}
+
+/// Convert a static node GUID to the null-terminated string librealsense expects.
+///
+/// # Errors
+///
+/// Returns [`PoseSensorError::InvalidGuid`] if the GUID is longer than 127 bytes or contains a
+/// null byte.
+pub(crate) fn guid_to_cstring(guid: &str) -> Result<CString, PoseSensorError> {
+    if guid.len() > MAX_GUID_LENGTH {
+        return Err(PoseSensorError::InvalidGuid(guid.to_owned()));
+    }
+    CString::new(guid).map_err(|_| PoseSensorError::InvalidGuid(guid.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_guids() {
+        assert_eq!(guid_to_cstring("dock").unwrap().as_bytes(), b"dock");
+        assert!(guid_to_cstring(&"a".repeat(MAX_GUID_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_guids() {
+        assert!(matches!(
+            guid_to_cstring(&"a".repeat(MAX_GUID_LENGTH + 1)),
+            Err(PoseSensorError::InvalidGuid(_))
+        ));
+        assert!(matches!(
+            guid_to_cstring("do\0ck"),
+            Err(PoseSensorError::InvalidGuid(_))
+        ));
+    }
+
+    #[test]
+    fn filters_relocalization_notifications() {
+        assert!(is_relocalization(
+            sys::rs2_notification_category_RS2_NOTIFICATION_CATEGORY_POSE_RELOCALIZATION
+        ));
+        assert!(!is_relocalization(
+            sys::rs2_notification_category_RS2_NOTIFICATION_CATEGORY_FRAMES_TIMEOUT
+        ));
+    }
+
+    #[test]
+    fn static_node_to_extrinsics() {
+        let node = StaticNode {
+            translation: [1.0, 2.0, 3.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+        };
+        let point = node.to_extrinsics().transform_point([1.0, 0.0, 0.0]);
+        assert_eq!(point, [2.0, 2.0, 3.0]);
+    }
+}